@@ -0,0 +1,330 @@
+//! Batch scanning for `pepeek scan`.
+//!
+//! Every file found is scanned in parallel, and produces exactly one [`ScanRecord`], even if it could not be read.
+//! Records are printed as each file finishes, so their order varies from run to run. Files that are not images, object
+//! files or archives are skipped rather than treated as failures, and are never read past their headers.
+
+use crate::cli::{ScanArgs, ScanFormat};
+use crate::pe::headers::CoffCharacteristics;
+use crate::table::format_row;
+use rayon::prelude::*;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use walkdir::WalkDir;
+
+/// Columns of the CSV output, in the order returned by [`ScanRecord::csv_fields`].
+const CSV_COLUMNS: [&str; 19] = [
+    "path",
+    "status",
+    "error",
+    "kind",
+    "size",
+    "machine",
+    "format",
+    "subsystem",
+    "is_dll",
+    "number_of_sections",
+    "time_date_stamp",
+    "entry_point",
+    "image_base",
+    "imported_dlls",
+    "exported_functions",
+    "overlay_size",
+    "md5",
+    "sha256",
+    "imphash",
+];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ScanStatus {
+    Ok,
+    /// The file is not an image, object file or archive.
+    Skipped,
+    /// The file looked like an image, object file or archive, but could not be read.
+    Failed,
+}
+
+/// The result of scanning one file. Fields other than `path` and `status` are `None` when not applicable, or when the
+/// file could not be read far enough to fill them in.
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ScanRecord {
+    pub path: String,
+    pub status: ScanStatus,
+    pub error: Option<String>,
+    /// `"image"`, `"object"` or `"archive"`.
+    pub kind: Option<&'static str>,
+    pub size: Option<u64>,
+    pub machine: Option<String>,
+    /// `"PE32"` or `"PE32+"`.
+    pub format: Option<&'static str>,
+    pub subsystem: Option<String>,
+    pub is_dll: Option<bool>,
+    pub number_of_sections: Option<u16>,
+    pub time_date_stamp: Option<u32>,
+    pub entry_point: Option<u32>,
+    pub image_base: Option<u64>,
+    pub imported_dlls: Option<usize>,
+    pub exported_functions: Option<usize>,
+    pub overlay_size: Option<u64>,
+    pub md5: Option<String>,
+    pub sha256: Option<String>,
+    pub imphash: Option<String>,
+}
+
+/// Counts of the records printed so far, for the summary at the end of a scan.
+#[derive(Default)]
+struct ScanSummary {
+    ok: usize,
+    skipped: usize,
+    /// The path and error of each file that failed.
+    failed: Vec<(String, String)>,
+}
+
+/// Why a file could not be scanned.
+enum ScanError {
+    NotPe(String),
+    Failed(String),
+}
+
+impl ScanRecord {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.display().to_string(),
+            status: ScanStatus::Ok,
+            error: None,
+            kind: None,
+            size: None,
+            machine: None,
+            format: None,
+            subsystem: None,
+            is_dll: None,
+            number_of_sections: None,
+            time_date_stamp: None,
+            entry_point: None,
+            image_base: None,
+            imported_dlls: None,
+            exported_functions: None,
+            overlay_size: None,
+            md5: None,
+            sha256: None,
+            imphash: None,
+        }
+    }
+
+    /// The record's fields as strings, in the order of [`CSV_COLUMNS`].
+    pub fn csv_fields(&self) -> Vec<String> {
+        fn opt<T: ToString>(value: &Option<T>) -> String {
+            value.as_ref().map(T::to_string).unwrap_or_default()
+        }
+
+        let status = match self.status {
+            ScanStatus::Ok => "ok",
+            ScanStatus::Skipped => "skipped",
+            ScanStatus::Failed => "failed",
+        };
+        vec![
+            self.path.clone(),
+            status.to_string(),
+            opt(&self.error),
+            opt(&self.kind),
+            opt(&self.size),
+            opt(&self.machine),
+            opt(&self.format),
+            opt(&self.subsystem),
+            opt(&self.is_dll),
+            opt(&self.number_of_sections),
+            opt(&self.time_date_stamp),
+            opt(&self.entry_point),
+            opt(&self.image_base),
+            opt(&self.imported_dlls),
+            opt(&self.exported_functions),
+            opt(&self.overlay_size),
+            opt(&self.md5),
+            opt(&self.sha256),
+            opt(&self.imphash),
+        ]
+    }
+}
+
+/// Runs `pepeek scan`, exiting with status 1 if any file failed.
+pub fn run_scan(args: &ScanArgs) {
+    if args.format == ScanFormat::Jsonl && !cfg!(feature = "serde") {
+        eprintln!("pepeek was built without the serde feature, so JSON Lines output is not available");
+        process::exit(1);
+    }
+    if let Some(jobs) = args.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()
+            .expect("could not start thread pool!!");
+    }
+
+    let (paths, walk_errors) = collect_paths(&args.paths);
+    if args.format == ScanFormat::Csv {
+        println!("{}", format_row(&CSV_COLUMNS, ','));
+    }
+    let mut summary = ScanSummary::default();
+    let (sender, receiver) = mpsc::channel::<ScanRecord>();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            paths.par_iter().for_each_with(sender, |sender, path| {
+                // the receiver only goes away if printing panicked
+                let _ = sender.send(scan_file(path));
+            })
+        });
+        for record in receiver.iter().chain(walk_errors) {
+            print_record(&record, args.format);
+            summary.add(record);
+        }
+    });
+    summary.print();
+
+    if !summary.failed.is_empty() {
+        process::exit(1);
+    }
+}
+
+/// Expands directories into the files beneath them, returning failed records for anything that could not be walked.
+fn collect_paths(inputs: &[PathBuf]) -> (Vec<PathBuf>, Vec<ScanRecord>) {
+    let mut paths: Vec<PathBuf> = Vec::new();
+    let mut errors: Vec<ScanRecord> = Vec::new();
+    for input in inputs {
+        for entry in WalkDir::new(input).sort_by_file_name() {
+            match entry {
+                Ok(entry) if entry.file_type().is_file() => paths.push(entry.into_path()),
+                Ok(_) => {}
+                Err(err) => {
+                    let mut record = ScanRecord::new(err.path().unwrap_or(input));
+                    record.status = ScanStatus::Failed;
+                    record.error = Some(err.to_string());
+                    errors.push(record);
+                }
+            }
+        }
+    }
+    (paths, errors)
+}
+
+fn scan_file(path: &Path) -> ScanRecord {
+    let mut record = ScanRecord::new(path);
+    match fill_record(path, &mut record) {
+        Ok(()) => {}
+        Err(ScanError::NotPe(msg)) => {
+            record.status = ScanStatus::Skipped;
+            record.error = Some(msg);
+        }
+        Err(ScanError::Failed(msg)) => {
+            record.status = ScanStatus::Failed;
+            record.error = Some(msg);
+        }
+    }
+    record
+}
+
+fn fill_record(path: &Path, record: &mut ScanRecord) -> Result<(), ScanError> {
+    let mut handle = File::open(path).map_err(failed)?;
+    record.size = Some(handle.metadata().map_err(failed)?.len());
+
+    if crate::pe::archive::is_archive(&mut handle) {
+        crate::pe::archive::get_archive_from_file(&mut handle).map_err(failed)?;
+        record.kind = Some("archive");
+        fill_digests(&mut handle, record)?;
+        return Ok(());
+    }
+
+    // anything without a PE signature, including a bare DOS program, is only worth reporting as an object file if
+    // its COFF header makes sense
+    let is_image = crate::pe::deser::is_image(&mut handle);
+    let from_file = match crate::pe::deser::get_headers_from_file(&mut handle) {
+        Ok(from_file) => from_file,
+        Err(err) if is_image => return Err(failed(err)),
+        Err(_) => return Err(ScanError::NotPe(String::from("not an image, object file or archive"))),
+    };
+    let coff_header = from_file.coff_header();
+    if !is_image && (coff_header.size_of_optional_header != 0 || coff_header.target_machine as u16 == 0) {
+        return Err(ScanError::NotPe(String::from("not an image, object file or archive")));
+    }
+    let section_table = crate::pe::deser::get_section_table(&mut handle, from_file.as_ref()).map_err(failed)?;
+
+    record.kind = Some(if is_image { "image" } else { "object" });
+    record.machine = Some(format!("{:?}", coff_header.target_machine));
+    record.is_dll = Some(coff_header.characteristics.contains(CoffCharacteristics::Dll));
+    record.number_of_sections = Some(coff_header.number_of_sections);
+    record.time_date_stamp = Some(coff_header.time_date_stamp);
+    if let Some(pe32_header) = from_file.optional_header_pe32() {
+        record.format = Some("PE32");
+        record.subsystem = Some(format!("{:?}", pe32_header.windows_fields.subsystem));
+        record.entry_point = Some(pe32_header.standard_fields.address_of_entry_point);
+        record.image_base = Some(pe32_header.windows_fields.image_base as u64);
+    }
+    if let Some(pe32plus_header) = from_file.optional_header_pe32plus() {
+        record.format = Some("PE32+");
+        record.subsystem = Some(format!("{:?}", pe32plus_header.windows_fields.subsystem));
+        record.entry_point = Some(pe32plus_header.standard_fields.address_of_entry_point);
+        record.image_base = Some(pe32plus_header.windows_fields.image_base);
+    }
+    fill_digests(&mut handle, record)?;
+
+    if is_image {
+        let imports = crate::pe::imports::get_imports(&mut handle, from_file.as_ref(), &section_table).map_err(failed)?;
+        let exports = crate::pe::exports::get_exports(&mut handle, from_file.as_ref(), &section_table).map_err(failed)?;
+        let overlay = crate::pe::overlay::get_overlay(&mut handle, from_file.as_ref(), &section_table).map_err(failed)?;
+        record.imported_dlls = Some(imports.len());
+        record.exported_functions = Some(exports.map_or(0, |exports| exports.functions.len()));
+        record.overlay_size = Some(overlay.map_or(0, |overlay| overlay.size));
+        record.imphash = crate::pe::hashes::get_imphash(&imports);
+    }
+    Ok(())
+}
+
+fn failed(err: impl ToString) -> ScanError {
+    ScanError::Failed(err.to_string())
+}
+
+fn fill_digests(handle: &mut File, record: &mut ScanRecord) -> Result<(), ScanError> {
+    let digests = crate::pe::hashes::get_file_digests(handle).map_err(failed)?;
+    record.md5 = Some(digests.md5);
+    record.sha256 = Some(digests.sha256);
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+fn print_record(record: &ScanRecord, format: ScanFormat) {
+    match format {
+        ScanFormat::Jsonl => println!("{}", serde_json::to_string(record).expect("could not serialise record!!")),
+        ScanFormat::Csv => println!("{}", format_row(&record.csv_fields(), ',')),
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_record(record: &ScanRecord, _format: ScanFormat) {
+    println!("{}", format_row(&record.csv_fields(), ','));
+}
+
+impl ScanSummary {
+    fn add(&mut self, record: ScanRecord) {
+        match record.status {
+            ScanStatus::Ok => self.ok += 1,
+            ScanStatus::Skipped => self.skipped += 1,
+            ScanStatus::Failed => self.failed.push((record.path, record.error.unwrap_or_default())),
+        }
+    }
+
+    fn print(&self) {
+        eprintln!(
+            "Scanned {} file(s): {} ok, {} skipped, {} failed",
+            self.ok + self.skipped + self.failed.len(),
+            self.ok,
+            self.skipped,
+            self.failed.len()
+        );
+        for (path, error) in &self.failed {
+            eprintln!("\t{}: {}", path, error);
+        }
+    }
+}
@@ -0,0 +1,828 @@
+//! Command-line interface definitions.
+
+use crate::pe::body::SectionFlags;
+use crate::pe::checksec::MITIGATION_IDS;
+use crate::pe::headers::{DllCharacteristics, WindowsSubsystem};
+use crate::pe::lint::{Severity, LINTS};
+use crate::pe::resources::ResourceId;
+use clap::builder::{PossibleValue, PossibleValuesParser};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+/// Peeks inside PE images, COFF object files and archives.
+#[derive(Parser)]
+#[command(version, about, args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// File to print everything about, if no subcommand is given
+    #[arg(required = true)]
+    pub file: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub all: AllArgs,
+
+    /// How to print numbers
+    #[arg(long, value_enum, global = true, default_value_t = Radix::Both)]
+    pub radix: Radix,
+}
+
+/// Options that only apply when printing everything.
+#[derive(Args)]
+pub struct AllArgs {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Write the overlay, if any, to this path
+    #[arg(long, value_name = "OUTPUT")]
+    pub dump_overlay: Option<PathBuf>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the Rich, COFF and optional headers
+    Headers(HeadersArgs),
+    /// Print the section table, with hashes and entropy of each section
+    Sections(SectionsArgs),
+    /// Print the imported DLLs and functions
+    Imports(TableArgs),
+    /// Print the exported functions
+    Exports(TableArgs),
+    /// Print the resources
+    Resources(FileArgs),
+    /// Print the file's MD5, SHA-1 and SHA-256, the imphash, exphash and Authentihash, and a fuzzy hash
+    Hashes(FileArgs),
+    /// Hex dump a range of the file, annotated with the headers, sections and data directories it covers
+    Dump(DumpArgs),
+    /// Compare the headers, sections, imports and exports of two files. Exits with status 1 if they differ
+    Diff(DiffArgs),
+    /// Scan many files and directory trees in parallel, printing one record per file
+    Scan(ScanArgs),
+    /// Report which security mitigations an image was built with. Exits with status 2 if any check fails, or 1 if the
+    /// file cannot be read
+    Checksec(ChecksecArgs),
+    /// Report anomalies in the headers and section table. Exits with status 1 if any are at or above --fail-on
+    Lint(LintArgs),
+    /// Identify packers, protectors and toolchains from a database of rules
+    Identify(IdentifyArgs),
+    /// Print the strings in each section and the overlay, with their file offsets and RVAs
+    Strings(StringsArgs),
+    /// Disassemble x86, x64 or, if built with the disasm-arm64 feature, ARM64 code at the entry point, an export, a TLS
+    /// callback or an RVA
+    Disasm(DisasmArgs),
+    /// Set header fields such as the subsystem, DLL characteristics and versions, recomputing the checksum
+    Patch(PatchArgs),
+    /// Add, remove or resize a section, updating the headers and data directories to match
+    #[command(subcommand)]
+    Section(SectionCommand),
+    /// Add, replace or remove resources, such as the version info, icons and manifest, rebuilding the resource section
+    #[command(subcommand)]
+    Resource(ResourceCommand),
+    /// Add an import of named functions from a DLL, rebuilding the import directory in a new section
+    AddImport(AddImportArgs),
+    /// Clear the time stamps, Rich header, PDB GUID and directory, and repro hash, so that reproducible builds give
+    /// identical files
+    Normalise(NormaliseArgs),
+    /// Move an image to a new image base, applying its base relocations to the section data
+    Rebase(RebaseArgs),
+}
+
+#[derive(Subcommand)]
+pub enum SectionCommand {
+    /// Append a section holding the contents of a file
+    Add(SectionAddArgs),
+    /// Remove a section, clearing any data directories in it
+    Remove(SectionRemoveArgs),
+    /// Change the size of a section, zero-filling it if it grows
+    Resize(SectionResizeArgs),
+}
+
+#[derive(Subcommand)]
+pub enum ResourceCommand {
+    /// Add a resource holding the contents of a file, or replace an existing one
+    Set(ResourceSetArgs),
+    /// Remove resources of a type, optionally only those with a name and language
+    Remove(ResourceRemoveArgs),
+    /// Replace an icon group with the images in an .ico file
+    Icon(ResourceIconArgs),
+    /// Set the version numbers and strings in the version resource
+    Version(ResourceVersionArgs),
+}
+
+#[derive(Args)]
+pub struct FileArgs {
+    pub file: PathBuf,
+}
+
+#[derive(Args)]
+pub struct TableArgs {
+    pub file: PathBuf,
+
+    /// Output format. The csv and tsv formats print one row per entry, with numbers in decimal
+    #[arg(long, value_enum, default_value_t = TableFormat::Text)]
+    pub format: TableFormat,
+}
+
+#[derive(Args)]
+pub struct HeadersArgs {
+    pub file: PathBuf,
+
+    /// Print the Rich header
+    #[arg(long)]
+    pub rich: bool,
+
+    /// Print the COFF header
+    #[arg(long)]
+    pub coff: bool,
+
+    /// Print the optional header, without the data directories
+    #[arg(long)]
+    pub optional: bool,
+
+    /// Print the data directories
+    #[arg(long)]
+    pub data_directories: bool,
+}
+
+#[derive(Args)]
+pub struct SectionsArgs {
+    pub file: PathBuf,
+
+    /// Only print sections with this name, such as `.text`. May be given more than once
+    #[arg(short, long = "name", value_name = "NAME")]
+    pub names: Vec<String>,
+
+    /// Output format. The csv and tsv formats print one row per section, with numbers in decimal
+    #[arg(long, value_enum, default_value_t = TableFormat::Text)]
+    pub format: TableFormat,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("start").args(["offset", "rva", "va", "section"])))]
+pub struct DumpArgs {
+    pub file: PathBuf,
+
+    /// File offset to start at, in decimal or with a 0x prefix [default: 0]
+    #[arg(long, value_parser = parse_number)]
+    pub offset: Option<u64>,
+
+    /// RVA to start at, in decimal or with a 0x prefix
+    #[arg(long, value_parser = parse_number)]
+    pub rva: Option<u64>,
+
+    /// VA to start at, in decimal or with a 0x prefix, relative to the image base
+    #[arg(long, value_parser = parse_number)]
+    pub va: Option<u64>,
+
+    /// Section to start in, with an optional offset into its raw data such as `.text+0x40`
+    #[arg(long, value_name = "NAME[+OFFSET]")]
+    pub section: Option<String>,
+
+    /// Number of bytes to dump, in decimal or with a 0x prefix
+    #[arg(long, default_value = "256", value_parser = parse_number)]
+    pub length: u64,
+}
+
+#[derive(Args)]
+pub struct DiffArgs {
+    pub old: PathBuf,
+    pub new: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+}
+
+#[derive(Args)]
+pub struct ScanArgs {
+    /// Files to scan. Directories are scanned recursively
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = ScanFormat::Jsonl)]
+    pub format: ScanFormat,
+
+    /// Number of files to scan at once. Defaults to the number of CPUs
+    #[arg(short, long)]
+    pub jobs: Option<usize>,
+}
+
+#[derive(Args)]
+pub struct ChecksecArgs {
+    pub file: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Do not fail if these checks fail. May be given more than once, or as a comma-separated list
+    #[arg(long, value_name = "CHECK", value_delimiter = ',', value_parser = PossibleValuesParser::new(MITIGATION_IDS))]
+    pub ignore: Vec<String>,
+
+    /// Fail, rather than warn, if these checks are missing, such as force-integrity and authenticode for release builds.
+    /// May be given more than once, or as a comma-separated list
+    #[arg(long, value_name = "CHECK", value_delimiter = ',', value_parser = PossibleValuesParser::new(MITIGATION_IDS))]
+    pub require: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct LintArgs {
+    pub file: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// Do not report this lint. May be given more than once, or as a comma-separated list
+    #[arg(short = 'A', long, value_name = "LINT", value_delimiter = ',', value_parser = lint_ids())]
+    pub allow: Vec<String>,
+
+    /// Lowest severity that makes the exit status 1
+    #[arg(long, value_enum, default_value_t = FailOn::Warning)]
+    pub fail_on: FailOn,
+}
+
+#[derive(Args)]
+pub struct IdentifyArgs {
+    pub file: PathBuf,
+
+    /// Output format
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub format: OutputFormat,
+
+    /// TOML file of extra rules, in the same format as the built-in ones. May be given more than once
+    #[arg(long = "rules", value_name = "FILE")]
+    pub rule_files: Vec<PathBuf>,
+
+    /// Only use the rules given with --rules
+    #[arg(long, requires = "rule_files")]
+    pub no_builtin_rules: bool,
+}
+
+#[derive(Args)]
+pub struct StringsArgs {
+    pub file: PathBuf,
+
+    /// Fewest characters a string must have to be printed
+    #[arg(short = 'n', long, default_value_t = 4, value_parser = clap::value_parser!(u64).range(1..))]
+    pub min_len: u64,
+
+    /// Which encodings to look for
+    #[arg(short, long, value_enum, default_value_t = StringEncodingArg::Both)]
+    pub encoding: StringEncodingArg,
+
+    /// Only search sections with initialised data, and the overlay
+    #[arg(long)]
+    pub data_only: bool,
+
+    /// Output format. The csv and tsv formats print one row per string, with numbers in decimal
+    #[arg(long, value_enum, default_value_t = TableFormat::Text)]
+    pub format: TableFormat,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("start").args(["rva", "export", "tls"])))]
+pub struct DisasmArgs {
+    pub file: PathBuf,
+
+    /// Start at this RVA, in decimal or with a 0x prefix, rather than the entry point
+    #[arg(long, value_parser = parse_number)]
+    pub rva: Option<u64>,
+
+    /// Start at the export with this name, or `#<ordinal>`, rather than the entry point
+    #[arg(long)]
+    pub export: Option<String>,
+
+    /// Start at the TLS callback with this index, from 0, rather than the entry point
+    #[arg(long, value_name = "INDEX")]
+    pub tls: Option<usize>,
+
+    /// Number of instructions to disassemble
+    #[arg(short, long, default_value_t = 32)]
+    pub count: usize,
+}
+
+#[derive(Args)]
+pub struct SectionAddArgs {
+    pub file: PathBuf,
+
+    /// Name of the new section, up to 8 bytes
+    #[arg(long)]
+    pub name: String,
+
+    /// File holding the section's contents
+    #[arg(long)]
+    pub data: PathBuf,
+
+    /// Section flags, separated by commas
+    #[arg(long, value_enum, value_delimiter = ',', default_value = "cnt-initialised-data,mem-read")]
+    pub flags: Vec<SectionFlagArg>,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct SectionRemoveArgs {
+    pub file: PathBuf,
+
+    /// Name of the section to remove. If several sections have this name, the first is removed
+    #[arg(long)]
+    pub name: String,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct SectionResizeArgs {
+    pub file: PathBuf,
+
+    /// Name of the section to resize. If several sections have this name, the first is resized
+    #[arg(long)]
+    pub name: String,
+
+    /// New virtual size, in decimal or with a 0x prefix. The raw size is this rounded up to the file alignment
+    #[arg(long, value_parser = parse_number)]
+    pub size: u64,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct ResourceSetArgs {
+    pub file: PathBuf,
+
+    /// Resource type, as a number, a predefined type such as RT_MANIFEST, or a name
+    #[arg(long = "type", value_name = "TYPE", value_parser = parse_resource_type)]
+    pub resource_type: ResourceId,
+
+    /// Resource name, as a number or a name
+    #[arg(long, value_parser = parse_resource_id)]
+    pub name: ResourceId,
+
+    /// Language ID. If not given, every language of an existing resource is replaced, and a new one is language neutral
+    #[arg(long, value_parser = parse_resource_id)]
+    pub language: Option<ResourceId>,
+
+    /// File holding the resource's contents
+    #[arg(long)]
+    pub data: PathBuf,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct ResourceRemoveArgs {
+    pub file: PathBuf,
+
+    /// Resource type, as a number, a predefined type such as RT_MANIFEST, or a name
+    #[arg(long = "type", value_name = "TYPE", value_parser = parse_resource_type)]
+    pub resource_type: ResourceId,
+
+    /// Resource name, as a number or a name. If not given, every resource of the type is removed
+    #[arg(long, value_parser = parse_resource_id)]
+    pub name: Option<ResourceId>,
+
+    /// Language ID. If not given, every language is removed
+    #[arg(long, value_parser = parse_resource_id)]
+    pub language: Option<ResourceId>,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct ResourceIconArgs {
+    pub file: PathBuf,
+
+    /// The .ico file holding the new images
+    #[arg(long)]
+    pub ico: PathBuf,
+
+    /// Name of the icon group to replace. If not given, the first group is replaced, which is the one Explorer shows
+    #[arg(long, value_parser = parse_resource_id)]
+    pub name: Option<ResourceId>,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("changes").required(true).multiple(true)))]
+pub struct ResourceVersionArgs {
+    pub file: PathBuf,
+
+    /// File version, as up to four numbers separated by dots. This also sets the FileVersion string
+    #[arg(long, value_name = "VERSION", value_parser = parse_file_version, group = "changes")]
+    pub file_version: Option<[u16; 4]>,
+
+    /// Product version, as up to four numbers separated by dots. This also sets the ProductVersion string
+    #[arg(long, value_name = "VERSION", value_parser = parse_file_version, group = "changes")]
+    pub product_version: Option<[u16; 4]>,
+
+    /// A string to set in every string table, such as CompanyName=Example. Can be given more than once
+    #[arg(long = "string", value_name = "KEY=VALUE", value_parser = parse_key_value, group = "changes")]
+    pub strings: Vec<(String, String)>,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct AddImportArgs {
+    pub file: PathBuf,
+
+    /// Name of the DLL to import from, such as hook.dll
+    #[arg(long)]
+    pub dll: String,
+
+    /// Name of a function to import. Can be given more than once
+    #[arg(long = "function", value_name = "NAME", required = true)]
+    pub functions: Vec<String>,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct NormaliseArgs {
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+#[derive(Args)]
+pub struct RebaseArgs {
+    pub file: PathBuf,
+
+    /// New image base, in decimal or with a 0x prefix. This must be a multiple of 0x10000
+    #[arg(long, value_parser = parse_number)]
+    pub image_base: u64,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+}
+
+/// Where to write a modified file.
+#[derive(Args)]
+#[group(required = true, multiple = false)]
+pub struct OutputArgs {
+    /// Write the modified file here
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+
+    /// Overwrite the input file
+    #[arg(long)]
+    pub in_place: bool,
+}
+
+#[derive(Args)]
+pub struct PatchArgs {
+    pub file: PathBuf,
+
+    #[command(flatten)]
+    pub destination: OutputArgs,
+
+    /// COFF time stamp, in seconds since 1st Jan 1970, in decimal or with a 0x prefix
+    #[arg(long, value_parser = parse_number)]
+    pub timestamp: Option<u64>,
+
+    /// Subsystem that the image runs under
+    #[arg(long, value_enum)]
+    pub subsystem: Option<SubsystemArg>,
+
+    /// DLL characteristics to set, separated by commas
+    #[arg(long, value_name = "FLAGS", value_enum, value_delimiter = ',')]
+    pub set_dll_characteristics: Vec<DllCharacteristicArg>,
+
+    /// DLL characteristics to clear, separated by commas
+    #[arg(long, value_name = "FLAGS", value_enum, value_delimiter = ',')]
+    pub clear_dll_characteristics: Vec<DllCharacteristicArg>,
+
+    /// Operating system version, as major.minor
+    #[arg(long, value_name = "VERSION", value_parser = parse_version)]
+    pub os_version: Option<(u16, u16)>,
+
+    /// Image version, as major.minor
+    #[arg(long, value_name = "VERSION", value_parser = parse_version)]
+    pub image_version: Option<(u16, u16)>,
+
+    /// Subsystem version, as major.minor
+    #[arg(long, value_name = "VERSION", value_parser = parse_version)]
+    pub subsystem_version: Option<(u16, u16)>,
+
+    /// Stack reserve size, in decimal or with a 0x prefix
+    #[arg(long, value_name = "SIZE", value_parser = parse_number)]
+    pub stack_reserve: Option<u64>,
+
+    /// Stack commit size, in decimal or with a 0x prefix
+    #[arg(long, value_name = "SIZE", value_parser = parse_number)]
+    pub stack_commit: Option<u64>,
+
+    /// Heap reserve size, in decimal or with a 0x prefix
+    #[arg(long, value_name = "SIZE", value_parser = parse_number)]
+    pub heap_reserve: Option<u64>,
+
+    /// Heap commit size, in decimal or with a 0x prefix
+    #[arg(long, value_name = "SIZE", value_parser = parse_number)]
+    pub heap_commit: Option<u64>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TableFormat {
+    Text,
+    /// Comma-separated values, with a header row
+    Csv,
+    /// Tab-separated values, with a header row
+    Tsv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScanFormat {
+    /// One JSON object per line
+    Jsonl,
+    /// Comma-separated values, with a header row
+    Csv,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum StringEncodingArg {
+    Ascii,
+    /// UTF-16LE, as used by most Windows APIs
+    Utf16le,
+    Both,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FailOn {
+    Info,
+    Warning,
+    Error,
+    /// Always exit with status 0
+    Never,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Radix {
+    /// Hexadecimal, such as `00001000h`
+    Hex,
+    /// Decimal, such as `4096`
+    Dec,
+    /// Hexadecimal followed by decimal, such as `00001000h (4096)`
+    Both,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SubsystemArg {
+    /// Device driver or native Windows process
+    Native,
+    WindowsGui,
+    WindowsCui,
+    Os2Cui,
+    PosixCui,
+    /// Native Win9x driver
+    NativeWindows,
+    WindowsCeGui,
+    EfiApplication,
+    EfiBootServiceDriver,
+    EfiRuntimeDriver,
+    EfiRom,
+    Xbox,
+    WindowsBootApplication,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "kebab-case")]
+pub enum DllCharacteristicArg {
+    /// Can use a 64-bit address space with ASLR
+    HighEntropyVa,
+    /// Can be relocated at load time
+    DynamicBase,
+    /// Code integrity checks are enforced
+    ForceIntegrity,
+    /// Compatible with DEP
+    NxCompat,
+    NoIsolation,
+    /// Does not use structured exception handling
+    NoSeh,
+    NoBind,
+    /// Must run in an AppContainer
+    AppContainer,
+    WdmDriver,
+    /// Supports Control Flow Guard
+    GuardCf,
+    TerminalServerAware,
+}
+
+/// The section flags that apply to a section in an image, leaving out those only used by object files, such as the
+/// alignment.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+#[value(rename_all = "kebab-case")]
+pub enum SectionFlagArg {
+    /// Contains executable code
+    CntCode,
+    /// Contains initialised data
+    CntInitialisedData,
+    /// Contains uninitialised data
+    CntUninitialisedData,
+    /// Can be discarded once loaded, like relocations
+    MemDiscardable,
+    MemNotCached,
+    MemNotPaged,
+    /// Shared between every process that loads the image
+    MemShared,
+    /// Can be executed
+    MemExecute,
+    /// Can be read
+    MemRead,
+    /// Can be written
+    MemWrite,
+}
+
+impl TableFormat {
+    /// The field delimiter, or `None` for text output.
+    pub fn delimiter(self) -> Option<char> {
+        match self {
+            TableFormat::Text => None,
+            TableFormat::Csv => Some(','),
+            TableFormat::Tsv => Some('\t'),
+        }
+    }
+}
+
+impl FailOn {
+    /// Checks whether a lint of this severity should make the exit status 1.
+    pub fn fails(self, severity: Severity) -> bool {
+        match self {
+            FailOn::Info => true,
+            FailOn::Warning => severity >= Severity::Warning,
+            FailOn::Error => severity >= Severity::Error,
+            FailOn::Never => false,
+        }
+    }
+}
+
+impl SubsystemArg {
+    pub fn subsystem(self) -> WindowsSubsystem {
+        match self {
+            SubsystemArg::Native => WindowsSubsystem::Native,
+            SubsystemArg::WindowsGui => WindowsSubsystem::WindowsGui,
+            SubsystemArg::WindowsCui => WindowsSubsystem::WindowsCui,
+            SubsystemArg::Os2Cui => WindowsSubsystem::Os2Cui,
+            SubsystemArg::PosixCui => WindowsSubsystem::PosixCui,
+            SubsystemArg::NativeWindows => WindowsSubsystem::NativeWindows,
+            SubsystemArg::WindowsCeGui => WindowsSubsystem::WindowsCeGui,
+            SubsystemArg::EfiApplication => WindowsSubsystem::EfiApplication,
+            SubsystemArg::EfiBootServiceDriver => WindowsSubsystem::EfiBootServiceDriver,
+            SubsystemArg::EfiRuntimeDriver => WindowsSubsystem::EfiRuntimeDriver,
+            SubsystemArg::EfiRom => WindowsSubsystem::EfiRom,
+            SubsystemArg::Xbox => WindowsSubsystem::Xbox,
+            SubsystemArg::WindowsBootApplication => WindowsSubsystem::WindowsBootApplication,
+        }
+    }
+}
+
+impl DllCharacteristicArg {
+    pub fn flag(self) -> DllCharacteristics {
+        match self {
+            DllCharacteristicArg::HighEntropyVa => DllCharacteristics::HighEntropyVa,
+            DllCharacteristicArg::DynamicBase => DllCharacteristics::DynamicBase,
+            DllCharacteristicArg::ForceIntegrity => DllCharacteristics::ForceIntegrity,
+            DllCharacteristicArg::NxCompat => DllCharacteristics::NxCompat,
+            DllCharacteristicArg::NoIsolation => DllCharacteristics::NoIsolation,
+            DllCharacteristicArg::NoSeh => DllCharacteristics::NoSeh,
+            DllCharacteristicArg::NoBind => DllCharacteristics::NoBind,
+            DllCharacteristicArg::AppContainer => DllCharacteristics::AppContainer,
+            DllCharacteristicArg::WdmDriver => DllCharacteristics::WdmDriver,
+            DllCharacteristicArg::GuardCf => DllCharacteristics::GuardCf,
+            DllCharacteristicArg::TerminalServerAware => DllCharacteristics::TerminalServerAware,
+        }
+    }
+}
+
+impl SectionFlagArg {
+    pub fn flag(self) -> SectionFlags {
+        match self {
+            SectionFlagArg::CntCode => SectionFlags::CntCode,
+            SectionFlagArg::CntInitialisedData => SectionFlags::CntInitialisedData,
+            SectionFlagArg::CntUninitialisedData => SectionFlags::CntUninitialisedData,
+            SectionFlagArg::MemDiscardable => SectionFlags::MemDiscardable,
+            SectionFlagArg::MemNotCached => SectionFlags::MemNotCached,
+            SectionFlagArg::MemNotPaged => SectionFlags::MemNotPaged,
+            SectionFlagArg::MemShared => SectionFlags::MemShared,
+            SectionFlagArg::MemExecute => SectionFlags::MemExecute,
+            SectionFlagArg::MemRead => SectionFlags::MemRead,
+            SectionFlagArg::MemWrite => SectionFlags::MemWrite,
+        }
+    }
+}
+
+impl Radix {
+    /// Formats a number, zero-padding the hexadecimal form to `width` digits.
+    pub fn format(self, value: impl Into<u64>, width: usize) -> String {
+        let value: u64 = value.into();
+        match self {
+            Radix::Hex => format!("{:0width$X}h", value, width = width),
+            Radix::Dec => format!("{}", value),
+            Radix::Both => format!("{0:0width$X}h ({0})", value, width = width),
+        }
+    }
+}
+
+/// The lint IDs, with their descriptions for `--help`.
+fn lint_ids() -> PossibleValuesParser {
+    PossibleValuesParser::new(LINTS.iter().map(|lint| PossibleValue::new(lint.id).help(lint.description)))
+}
+
+/// Parses a version written as `major.minor`.
+fn parse_version(s: &str) -> Result<(u16, u16), String> {
+    let (major, minor) = s.split_once('.').ok_or_else(|| String::from("expected major.minor"))?;
+    match (major.parse::<u16>(), minor.parse::<u16>()) {
+        (Ok(major), Ok(minor)) => Ok((major, minor)),
+        (Err(err), _) | (_, Err(err)) => Err(err.to_string()),
+    }
+}
+
+/// Parses a version written as up to four numbers separated by dots, such as `1.2.3.4`, filling in the rest with zeros.
+fn parse_file_version(s: &str) -> Result<[u16; 4], String> {
+    let parts: Vec<&str> = s.split('.').collect();
+    if parts.len() > 4 {
+        return Err(String::from("expected at most four numbers"));
+    }
+    let mut version = [0u16; 4];
+    for (part, number) in parts.iter().zip(&mut version) {
+        *number = part.parse::<u16>().map_err(|err| err.to_string())?;
+    }
+    Ok(version)
+}
+
+/// Parses a resource type, which can also be given as the name of a predefined type.
+fn parse_resource_type(s: &str) -> Result<ResourceId, String> {
+    match ResourceId::from_type_name(s) {
+        Some(id) => Ok(id),
+        None => parse_resource_id(s),
+    }
+}
+
+/// Parses a resource ID as a number if it is one, or else as a name. Names are upper-cased, as resource compilers store
+/// them that way and the loader upper-cases the names it is asked to find.
+fn parse_resource_id(s: &str) -> Result<ResourceId, String> {
+    match parse_number(s) {
+        Ok(id) => u32::try_from(id).map(ResourceId::Id).map_err(|err| err.to_string()),
+        Err(_) if s.is_empty() => Err(String::from("expected a number or a name")),
+        Err(_) => Ok(ResourceId::Name(s.to_uppercase())),
+    }
+}
+
+/// Parses a `KEY=VALUE` pair.
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((String::from(key), String::from(value))),
+        _ => Err(String::from("expected KEY=VALUE")),
+    }
+}
+
+/// Parses a decimal number, or a hexadecimal one with a `0x` prefix.
+pub fn parse_number(s: &str) -> Result<u64, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse::<u64>(),
+    };
+    parsed.map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn cli_is_valid() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn disasm_start_options_conflict() {
+        for args in [
+            ["--rva", "0x1000", "--export", "main"],
+            ["--rva", "0x1000", "--tls", "0"],
+            ["--export", "main", "--tls", "0"],
+        ] {
+            let result = Cli::try_parse_from(["pepeek", "disasm", "a.exe"].into_iter().chain(args));
+            assert!(result.is_err_and(|err| err.kind() == clap::error::ErrorKind::ArgumentConflict));
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! JSON output for `pepeek --format json`.
+//!
+//! The report is a single object with these keys, which are always present:
+//!
+//! - `schema_version`: integer, currently `1`. Bumped whenever a key is removed or changes meaning.
+//! - `file_name`: the file name of the input, without its directory.
+//! - `dos_header`: the MS-DOS header with its fields named as in [`DosHeader`], or `null` for object files.
+//! - `coff_header`: the COFF header with its fields named as in [`CoffHeader`]. `target_machine` is the
+//!   [`MachineType`](crate::pe::headers::MachineType) variant name, such as `"Amd64"`, and `characteristics` is a
+//!   `" | "`-separated list of [`CoffCharacteristics`](crate::pe::headers::CoffCharacteristics) flag names.
+//! - `optional_header`: `null` for object files, otherwise an object with `format` set to `"PE32"` or `"PE32+"` and
+//!   `standard_fields` and `windows_fields` objects with fields named as in the PE32 or PE32+ optional header
+//!   structs. `subsystem` is the [`WindowsSubsystem`](crate::pe::headers::WindowsSubsystem) variant name and
+//!   `dll_characteristics` is a `" | "`-separated list of flag names.
+//! - `data_directories`: an array with an object per data directory, with `index`, `name` (the display name, or
+//!   `null` past the sixteen standard directories), `virtual_address` and `size`.
+//! - `section_table`: an array with an object per section header, with fields named as in [`SectionHeader`].
+//!   `name` is the section name as a string with null padding removed, and `characteristics` is a `" | "`-separated
+//!   list of [`SectionFlags`](crate::pe::body::SectionFlags) flag names.
+//!
+//! All numbers are emitted as plain decimal integers.
+//!
+//! `pepeek diff --format json` emits an object with `schema_version`, `old` and `new` (the paths compared) and
+//! `changes`, an array of [`Change`] objects with `kind` (`"added"`, `"removed"` or `"changed"`), `category`, `item`,
+//! `field`, `old` and `new`. `old` and `new` are numbers or strings, or `null` for added and removed items.
+//!
+//! `pepeek checksec --format json` emits an object with `schema_version`, `file`, `passed` (whether every check that
+//! is not ignored passed, warned or does not apply), `ignored` (the IDs given to `--ignore`) and `checks`, an array of
+//! [`MitigationCheck`] objects with `id`, `name`, `status` (`"pass"`, `"fail"`, `"warn"` or `"not_applicable"`) and `detail`.
+//!
+//! `pepeek lint --format json` emits an object with `schema_version`, `file`, `allowed` (the IDs given to `--allow`)
+//! and `anomalies`, an array of [`Anomaly`] objects with `id`, `severity` (`"info"`, `"warning"` or `"error"`) and
+//! `message`. Allowed lints are left out of `anomalies`.
+//!
+//! `pepeek identify --format json` emits an object with `schema_version`, `file` and `detections`, an array of
+//! [`Detection`] objects with `name`, `kind` (`"packer"`, `"protector"` or `"toolchain"`), `description` (a string or
+//! `null`) and `reasons`, an array of strings describing the conditions that matched.
+
+use crate::pe::body::SectionHeader;
+use crate::pe::checksec::{CheckStatus, MitigationCheck};
+use crate::pe::diff::Change;
+use crate::pe::headers::{CoffHeader, DataDirectory, DosHeader, OptionalHeaderPe32, OptionalHeaderPe32Plus};
+use crate::pe::ident::Detection;
+use crate::pe::lint::Anomaly;
+use crate::pe::traits::PEHeader;
+use serde::Serialize;
+
+const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct Report<'a> {
+    schema_version: u32,
+    file_name: &'a str,
+    dos_header: Option<&'a DosHeader>,
+    coff_header: &'a CoffHeader,
+    optional_header: Option<OptionalHeader<'a>>,
+    data_directories: Vec<DataDirectoryEntry>,
+    section_table: &'a [SectionHeader],
+}
+
+#[derive(Serialize)]
+struct DiffReport<'a> {
+    schema_version: u32,
+    old: &'a str,
+    new: &'a str,
+    changes: &'a [Change],
+}
+
+#[derive(Serialize)]
+struct ChecksecReport<'a> {
+    schema_version: u32,
+    file: &'a str,
+    passed: bool,
+    ignored: &'a [String],
+    checks: &'a [MitigationCheck],
+}
+
+#[derive(Serialize)]
+struct LintReport<'a> {
+    schema_version: u32,
+    file: &'a str,
+    allowed: &'a [String],
+    anomalies: &'a [Anomaly],
+}
+
+#[derive(Serialize)]
+struct IdentifyReport<'a> {
+    schema_version: u32,
+    file: &'a str,
+    detections: &'a [Detection],
+}
+
+#[derive(Serialize)]
+#[serde(tag = "format")]
+enum OptionalHeader<'a> {
+    #[serde(rename = "PE32")]
+    Pe32(&'a OptionalHeaderPe32),
+    #[serde(rename = "PE32+")]
+    Pe32Plus(&'a OptionalHeaderPe32Plus),
+}
+
+#[derive(Serialize)]
+struct DataDirectoryEntry {
+    index: usize,
+    name: Option<&'static str>,
+    virtual_address: u32,
+    size: u32,
+}
+
+/// Builds the JSON report for an image or object file.
+pub fn get_report(file_name: &str, dos_header: Option<&DosHeader>, full_header: &dyn PEHeader, section_table: &[SectionHeader]) -> String {
+    let optional_header = match (full_header.optional_header_pe32(), full_header.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => Some(OptionalHeader::Pe32(pe32_header)),
+        (_, Some(pe32plus_header)) => Some(OptionalHeader::Pe32Plus(pe32plus_header)),
+        _ => None,
+    };
+    let data_directories = full_header
+        .data_directories()
+        .map(|dirs| {
+            dirs.iter()
+                .enumerate()
+                .map(|(i, dir)| DataDirectoryEntry {
+                    index: i,
+                    name: DataDirectory::DISPLAY_NAMES.get(i).copied(),
+                    virtual_address: dir.virtual_address,
+                    size: dir.size,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let report = Report {
+        schema_version: SCHEMA_VERSION,
+        file_name,
+        dos_header,
+        coff_header: full_header.coff_header(),
+        optional_header,
+        data_directories,
+        section_table,
+    };
+    serde_json::to_string_pretty(&report).expect("could not serialise report!!")
+}
+
+/// Builds the JSON report for a diff between two files.
+pub fn get_diff_report(old: &str, new: &str, changes: &[Change]) -> String {
+    let report = DiffReport {
+        schema_version: SCHEMA_VERSION,
+        old,
+        new,
+        changes,
+    };
+    serde_json::to_string_pretty(&report).expect("could not serialise report!!")
+}
+
+/// Builds the JSON report for the security mitigations of an image.
+pub fn get_checksec_report(file: &str, checks: &[MitigationCheck], ignored: &[String]) -> String {
+    let passed = !checks
+        .iter()
+        .any(|check| check.status == CheckStatus::Fail && !ignored.iter().any(|id| id == check.id));
+    let report = ChecksecReport {
+        schema_version: SCHEMA_VERSION,
+        file,
+        passed,
+        ignored,
+        checks,
+    };
+    serde_json::to_string_pretty(&report).expect("could not serialise report!!")
+}
+
+/// Builds the JSON report for the anomalies found in a file.
+pub fn get_lint_report(file: &str, anomalies: &[Anomaly], allowed: &[String]) -> String {
+    let report = LintReport {
+        schema_version: SCHEMA_VERSION,
+        file,
+        allowed,
+        anomalies,
+    };
+    serde_json::to_string_pretty(&report).expect("could not serialise report!!")
+}
+
+/// Builds the JSON report for the packers, protectors and toolchains identified in an image.
+pub fn get_identify_report(file: &str, detections: &[Detection]) -> String {
+    let report = IdentifyReport {
+        schema_version: SCHEMA_VERSION,
+        file,
+        detections,
+    };
+    serde_json::to_string_pretty(&report).expect("could not serialise report!!")
+}
@@ -1,52 +1,839 @@
 use chrono::prelude::DateTime;
 use chrono::Utc;
+use clap::Parser;
+use cli::{
+    AddImportArgs, AllArgs, ChecksecArgs, Cli, Command, DiffArgs, DisasmArgs, DllCharacteristicArg, DumpArgs, HeadersArgs, IdentifyArgs, LintArgs,
+    NormaliseArgs, OutputArgs, OutputFormat, PatchArgs, Radix, RebaseArgs, ResourceCommand, SectionCommand, SectionsArgs, StringEncodingArg, StringsArgs,
+};
 use pe::headers::CoffHeader;
 use pe::traits::PEHeader;
-use std::env;
-use std::fs::File;
+use std::fs::{self, File};
 use std::path::Path;
 use std::process;
 use std::time::{Duration, UNIX_EPOCH};
 
+mod batch;
+mod cli;
+#[cfg(feature = "serde")]
+mod json;
 pub mod pe;
-use crate::pe::body::SectionHeader;
-use crate::pe::headers::{CoffCharacteristics, DataDirectory, OptionalHeaderPe32, OptionalHeaderPe32Plus};
-
-const DATA_DIRECTORY_DISPLAY_NAMES: [&str; 16] = [
-    "Export Table",
-    "Import Table",
-    "Resource Table",
-    "Exception Table",
-    "Certificate Table",
-    "Base Relocation Table",
-    "Debug",
-    "Architecture",
-    "Global Ptr",
-    "TLS Table",
-    "Load Config Table",
-    "Bound Import",
-    "IAT",
-    "Delay Import Descriptor",
-    "CLR Runtime Header",
-    "Reserved",
-];
+mod table;
+use crate::pe::archive::{Archive, ArchiveMemberContents};
+use crate::pe::body::{SectionFlags, SectionHeader};
+use crate::pe::checksec::{CheckStatus, MitigationCheck};
+use crate::pe::diff::Change;
+use crate::pe::entropy::shannon_entropy;
+use crate::pe::err::PEError;
+use crate::pe::exports::Exports;
+use crate::pe::file::PeFile;
+use crate::pe::fuzzy::get_fuzzy_hash;
+use crate::pe::hashes::{get_authentihash, get_digests, get_exphash, get_imphash};
+use crate::pe::headers::{CoffCharacteristics, DataDirectory, DllCharacteristics, OptionalHeaderPe32, OptionalHeaderPe32Plus};
+use crate::pe::imports::ImportedDll;
+use crate::pe::layout::Region;
+use crate::pe::lint::Anomaly;
+use crate::pe::overlay::Overlay;
+use crate::pe::patch::FieldPatch;
+use crate::pe::resource_edit::ResourceItem;
+use crate::pe::resources::{ResourceDirectory, ResourceId};
+use crate::pe::rich::RichHeader;
+use crate::pe::strings::{FoundString, StringOptions};
+
+/// Bytes per line of a hex dump.
+const DUMP_LINE_LEN: usize = 16;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: pepeek <path to exe/dll>");
-        process::exit(1);
+    let cli = Cli::parse();
+    let radix = cli.radix;
+    match cli.command {
+        Some(Command::Headers(args)) => run_headers(&args, radix),
+        Some(Command::Sections(args)) => run_sections(&args, radix),
+        Some(Command::Imports(args)) => {
+            let (mut handle, from_file, section_table) = open_image(&args.file);
+            let imports = or_exit(crate::pe::imports::get_imports(&mut handle, from_file.as_ref(), &section_table));
+            match args.format.delimiter() {
+                Some(delimiter) => table::print_imports_table(&imports, delimiter),
+                None => print_imports(&imports, radix),
+            }
+        }
+        Some(Command::Exports(args)) => {
+            let (mut handle, from_file, section_table) = open_image(&args.file);
+            let exports = or_exit(crate::pe::exports::get_exports(&mut handle, from_file.as_ref(), &section_table));
+            match args.format.delimiter() {
+                Some(delimiter) => table::print_exports_table(exports.as_ref(), delimiter),
+                None => print_exports(exports.as_ref(), radix),
+            }
+        }
+        Some(Command::Resources(args)) => {
+            let (mut handle, from_file, section_table) = open_image(&args.file);
+            let resources = or_exit(crate::pe::resources::get_resources(&mut handle, from_file.as_ref(), &section_table));
+            print_resources(resources.as_ref(), radix);
+        }
+        Some(Command::Hashes(args)) => {
+            let (mut handle, from_file, section_table) = open_image(&args.file);
+            let imports = or_exit(crate::pe::imports::get_imports(&mut handle, from_file.as_ref(), &section_table));
+            let exports = or_exit(crate::pe::exports::get_exports(&mut handle, from_file.as_ref(), &section_table));
+            let file_data = fs::read(&args.file).expect("could not read file!!");
+            print_hashes(&file_data, from_file.as_ref(), &imports, exports.as_ref());
+        }
+        Some(Command::Dump(args)) => run_dump(&args),
+        Some(Command::Diff(args)) => run_diff(&args),
+        Some(Command::Scan(args)) => batch::run_scan(&args),
+        Some(Command::Checksec(args)) => run_checksec(&args),
+        Some(Command::Lint(args)) => run_lint(&args),
+        Some(Command::Identify(args)) => run_identify(&args),
+        Some(Command::Strings(args)) => run_strings(&args, radix),
+        Some(Command::Disasm(args)) => run_disasm(&args, radix),
+        Some(Command::Patch(args)) => run_patch(&args),
+        Some(Command::Section(command)) => run_section(&command),
+        Some(Command::Resource(command)) => run_resource(&command),
+        Some(Command::AddImport(args)) => run_add_import(&args),
+        Some(Command::Normalise(args)) => run_normalise(&args),
+        Some(Command::Rebase(args)) => run_rebase(&args),
+        // clap requires the file when there is no subcommand
+        None => run_all(&cli.file.unwrap(), &cli.all, radix),
     }
+}
 
-    let path = Path::new(&args[1]);
-    let mut handle = File::open(path).expect("could not open file!!");
+fn run_all(path: &Path, args: &AllArgs, radix: Radix) {
+    let mut handle = open_file(path);
 
-    let from_file = crate::pe::deser::get_headers_from_file(&mut handle).unwrap();
-    let section_table = crate::pe::deser::get_section_table(&mut handle, from_file.as_ref()).unwrap();
+    if crate::pe::archive::is_archive(&mut handle) {
+        if args.format == OutputFormat::Json {
+            eprintln!("JSON output is not supported for archives");
+            process::exit(1);
+        }
+        let archive = or_exit(crate::pe::archive::get_archive_from_file(&mut handle));
+        println!("{}", path.file_name().unwrap().to_str().unwrap());
+        print_archive(&archive, radix);
+        return;
+    }
+
+    let from_file = or_exit(crate::pe::deser::get_headers_from_file(&mut handle));
+    let section_table = or_exit(crate::pe::deser::get_section_table(&mut handle, from_file.as_ref()));
+    if args.format == OutputFormat::Json {
+        print_json(&mut handle, path, from_file.as_ref(), &section_table);
+        return;
+    }
     println!("{}", path.file_name().unwrap().to_str().unwrap());
+    // object files have no DOS stub, and so no Rich header
+    if let Ok(Some(rich_header)) = crate::pe::rich::get_rich_header(&mut handle) {
+        print_rich_header(&rich_header, radix);
+    }
     print_coff_info(from_file.as_ref());
-    print_optional_info(&from_file);
-    print_section_headers(&section_table);
+    let file_data = fs::read(path).expect("could not read file!!");
+    let computed_checksum = crate::pe::checksum::compute_checksum(&file_data).ok();
+    print_optional_info(from_file.as_ref(), computed_checksum, radix);
+    if let Some(dirs) = from_file.data_directories() {
+        print_data_directories(dirs, radix);
+    }
+    let section_data: Vec<Vec<u8>> = section_table
+        .iter()
+        .map(|header| or_exit(crate::pe::deser::get_raw_section_data(&mut handle, header)))
+        .collect();
+    print_section_headers(section_table.iter().enumerate(), &section_data, radix);
+    print_coff_tables(&mut handle, from_file.coff_header(), &section_table);
+
+    let overlay = or_exit(crate::pe::overlay::get_overlay(&mut handle, from_file.as_ref(), &section_table));
+    let overlay_data = overlay.map(|overlay| or_exit(crate::pe::overlay::get_overlay_data(&mut handle, &overlay)));
+    print_overlay(overlay.as_ref(), overlay_data.as_deref(), radix);
+
+    let imports = or_exit(crate::pe::imports::get_imports(&mut handle, from_file.as_ref(), &section_table));
+    let exports = or_exit(crate::pe::exports::get_exports(&mut handle, from_file.as_ref(), &section_table));
+    let resources = or_exit(crate::pe::resources::get_resources(&mut handle, from_file.as_ref(), &section_table));
+    print_imports(&imports, radix);
+    print_exports(exports.as_ref(), radix);
+    print_resources(resources.as_ref(), radix);
+    print_hashes(&file_data, from_file.as_ref(), &imports, exports.as_ref());
+
+    if let Some(output) = &args.dump_overlay {
+        match overlay_data {
+            Some(data) => {
+                fs::write(output, data).expect("could not write overlay!!");
+                println!("Overlay written to {}", output.display());
+            }
+            None => {
+                println!("No overlay to write");
+                process::exit(1);
+            }
+        }
+    }
+}
+
+fn run_headers(args: &HeadersArgs, radix: Radix) {
+    let (mut handle, from_file, _) = open_image(&args.file);
+    let print_all = !(args.rich || args.coff || args.optional || args.data_directories);
+
+    if print_all || args.rich {
+        if let Ok(Some(rich_header)) = crate::pe::rich::get_rich_header(&mut handle) {
+            print_rich_header(&rich_header, radix);
+        }
+    }
+    if print_all || args.coff {
+        print_coff_info(from_file.as_ref());
+    }
+    if print_all || args.optional {
+        let file_data = fs::read(&args.file).expect("could not read file!!");
+        let computed_checksum = crate::pe::checksum::compute_checksum(&file_data).ok();
+        print_optional_info(from_file.as_ref(), computed_checksum, radix);
+    }
+    if print_all || args.data_directories {
+        if let Some(dirs) = from_file.data_directories() {
+            print_data_directories(dirs, radix);
+        }
+    }
+}
+
+fn run_sections(args: &SectionsArgs, radix: Radix) {
+    let (mut handle, _, section_table) = open_image(&args.file);
+    let selected: Vec<(usize, &SectionHeader)> = section_table
+        .iter()
+        .enumerate()
+        .filter(|(_, header)| args.names.is_empty() || args.names.contains(&header.name_str()))
+        .collect();
+    let section_data: Vec<Vec<u8>> = selected
+        .iter()
+        .map(|(_, header)| or_exit(crate::pe::deser::get_raw_section_data(&mut handle, header)))
+        .collect();
+    match args.format.delimiter() {
+        Some(delimiter) => table::print_sections_table(&selected, &section_data, delimiter),
+        None => print_section_headers(selected.into_iter(), &section_data, radix),
+    }
+}
+
+fn run_dump(args: &DumpArgs) {
+    let mut handle = open_file(&args.file);
+    // any file can be dumped by offset, but the other ways of addressing it and the annotations need its headers
+    let parsed = if crate::pe::archive::is_archive(&mut handle) {
+        None
+    } else {
+        crate::pe::deser::get_headers_from_file(&mut handle).ok().and_then(|from_file| {
+            let section_table = crate::pe::deser::get_section_table(&mut handle, from_file.as_ref()).ok()?;
+            Some((from_file, section_table))
+        })
+    };
+    let offset = match &parsed {
+        Some((from_file, section_table)) => get_dump_start(from_file.as_ref(), section_table, args),
+        None if args.offset.is_some() || (args.rva.is_none() && args.va.is_none() && args.section.is_none()) => args.offset.unwrap_or(0),
+        None => {
+            eprintln!("{} is not an image or object file, so can only be dumped by file offset", args.file.display());
+            process::exit(1);
+        }
+    };
+    let regions = match &parsed {
+        Some((from_file, section_table)) => crate::pe::layout::get_layout(&mut handle, from_file.as_ref(), section_table).unwrap_or_default(),
+        None => Vec::new(),
+    };
+    let data = crate::pe::internal::agnostic_fio::read_up_to(&mut handle, offset, args.length as usize).expect("could not read file!!");
+    print_hex_dump(offset, &data, &regions);
+}
+
+/// Works out the file offset to start dumping at from however the start was given.
+fn get_dump_start(full_header: &dyn PEHeader, section_table: &[SectionHeader], args: &DumpArgs) -> u64 {
+    let image_base = match (full_header.optional_header_pe32(), full_header.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => Some(pe32_header.windows_fields.image_base as u64),
+        (_, Some(pe32plus_header)) => Some(pe32plus_header.windows_fields.image_base),
+        _ => None,
+    };
+    let rva = match (args.rva, args.va) {
+        (Some(rva), _) => Some(rva),
+        (_, Some(va)) => match image_base {
+            Some(image_base) if va >= image_base => Some(va - image_base),
+            Some(image_base) => {
+                eprintln!("VA {:X}h is below the image base {:X}h", va, image_base);
+                process::exit(1);
+            }
+            None => {
+                eprintln!("object files have no image base, so cannot be dumped by VA");
+                process::exit(1);
+            }
+        },
+        _ => None,
+    };
+    if let Some(rva) = rva {
+        if image_base.is_none() {
+            eprintln!("object files are not loaded, so cannot be dumped by RVA");
+            process::exit(1);
+        }
+        return match u32::try_from(rva).ok().and_then(|rva| crate::pe::body::rva_to_file_offset(section_table, rva)) {
+            Some(offset) => offset as u64,
+            None => {
+                eprintln!("RVA {:X}h is not backed by data in the file", rva);
+                process::exit(1);
+            }
+        };
+    }
+    if let Some(section_arg) = &args.section {
+        let (name, delta) = match section_arg.rsplit_once('+') {
+            Some((name, delta)) => match cli::parse_number(delta) {
+                Ok(delta) => (name, delta),
+                Err(err) => {
+                    eprintln!("bad offset {:?} into section: {}", delta, err);
+                    process::exit(1);
+                }
+            },
+            None => (section_arg.as_str(), 0),
+        };
+        return match section_table.iter().find(|section| section.name_str() == name) {
+            Some(section) if delta < section.size_of_raw_data as u64 => section.pointer_to_raw_data as u64 + delta,
+            Some(section) => {
+                eprintln!(
+                    "offset {:X}h is past the {:X}h bytes of raw data in section {}",
+                    delta, section.size_of_raw_data, name
+                );
+                process::exit(1);
+            }
+            None => {
+                eprintln!("no section named {}", name);
+                process::exit(1);
+            }
+        };
+    }
+    args.offset.unwrap_or(0)
+}
+
+fn run_diff(args: &DiffArgs) {
+    let mut old_handle = open_file(&args.old);
+    let mut new_handle = open_file(&args.new);
+    let changes = or_exit(crate::pe::diff::diff_files(&mut old_handle, &mut new_handle));
+    match args.format {
+        OutputFormat::Text => {
+            println!("--- {}", args.old.display());
+            println!("+++ {}", args.new.display());
+            for change in &changes {
+                println!("{}", change);
+            }
+        }
+        OutputFormat::Json => print_diff_json(args, &changes),
+    }
+    if !changes.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn run_checksec(args: &ChecksecArgs) {
+    let (mut handle, from_file, section_table) = open_image(&args.file);
+    let mut checks = or_exit(crate::pe::checksec::check_mitigations(&mut handle, from_file.as_ref(), &section_table));
+    for check in &mut checks {
+        if check.status == CheckStatus::Warn && args.require.iter().any(|id| id == check.id) {
+            check.status = CheckStatus::Fail;
+        }
+    }
+    match args.format {
+        OutputFormat::Text => print_checksec(&checks, &args.ignore),
+        OutputFormat::Json => print_checksec_json(args, &checks),
+    }
+    let failed = checks
+        .iter()
+        .any(|check| check.status == CheckStatus::Fail && !args.ignore.iter().any(|id| id == check.id));
+    if failed {
+        process::exit(2);
+    }
+}
+
+fn run_lint(args: &LintArgs) {
+    let (mut handle, from_file, section_table) = open_image(&args.file);
+    let mut anomalies = or_exit(crate::pe::lint::find_anomalies(&mut handle, from_file.as_ref(), &section_table));
+    anomalies.retain(|anomaly| !args.allow.iter().any(|id| id == anomaly.id));
+    match args.format {
+        OutputFormat::Text => {
+            for anomaly in &anomalies {
+                println!("{}", anomaly);
+            }
+        }
+        OutputFormat::Json => print_lint_json(args, &anomalies),
+    }
+    if anomalies.iter().any(|anomaly| args.fail_on.fails(anomaly.severity)) {
+        process::exit(1);
+    }
+}
+
+#[cfg(feature = "serde")]
+fn run_identify(args: &IdentifyArgs) {
+    let mut rules = if args.no_builtin_rules {
+        Vec::new()
+    } else {
+        or_exit(crate::pe::ident::builtin_rules())
+    };
+    for path in &args.rule_files {
+        rules.extend(or_exit(crate::pe::ident::load_rules(path)));
+    }
+    let (mut handle, from_file, section_table) = open_image(&args.file);
+    let detections = or_exit(crate::pe::ident::identify(&mut handle, from_file.as_ref(), &section_table, &rules));
+    match args.format {
+        OutputFormat::Text => {
+            if detections.is_empty() {
+                println!("Nothing identified");
+            }
+            for detection in &detections {
+                let name = match &detection.description {
+                    Some(description) => format!("{} ({})", detection.name, description),
+                    None => detection.name.clone(),
+                };
+                println!("{:<10} {}", detection.kind, name);
+                println!("\t{}", detection.reasons.join("; "));
+            }
+        }
+        OutputFormat::Json => println!("{}", json::get_identify_report(&args.file.display().to_string(), &detections)),
+    }
+}
+
+#[cfg(not(feature = "serde"))]
+fn run_identify(_args: &IdentifyArgs) {
+    eprintln!("pepeek was built without the serde feature, so identification is not available");
+    process::exit(1);
+}
+
+fn run_patch(args: &PatchArgs) {
+    let mut pe_file = open_pe_file(&args.file);
+    let dll_characteristics = |args: &[DllCharacteristicArg]| args.iter().map(|arg| arg.flag()).fold(DllCharacteristics::empty(), |flags, flag| flags | flag);
+    let mut patches: Vec<FieldPatch> = Vec::new();
+    if let Some(timestamp) = args.timestamp {
+        match u32::try_from(timestamp) {
+            Ok(timestamp) => patches.push(FieldPatch::TimeDateStamp(timestamp)),
+            Err(_) => {
+                eprintln!("time stamp {} does not fit in 32 bits", timestamp);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(subsystem) = args.subsystem {
+        patches.push(FieldPatch::Subsystem(subsystem.subsystem()));
+    }
+    if !args.set_dll_characteristics.is_empty() {
+        patches.push(FieldPatch::SetDllCharacteristics(dll_characteristics(&args.set_dll_characteristics)));
+    }
+    if !args.clear_dll_characteristics.is_empty() {
+        patches.push(FieldPatch::ClearDllCharacteristics(dll_characteristics(&args.clear_dll_characteristics)));
+    }
+    if let Some((major, minor)) = args.os_version {
+        patches.push(FieldPatch::OperatingSystemVersion(major, minor));
+    }
+    if let Some((major, minor)) = args.image_version {
+        patches.push(FieldPatch::ImageVersion(major, minor));
+    }
+    if let Some((major, minor)) = args.subsystem_version {
+        patches.push(FieldPatch::SubsystemVersion(major, minor));
+    }
+    patches.extend(args.stack_reserve.map(FieldPatch::StackReserve));
+    patches.extend(args.stack_commit.map(FieldPatch::StackCommit));
+    patches.extend(args.heap_reserve.map(FieldPatch::HeapReserve));
+    patches.extend(args.heap_commit.map(FieldPatch::HeapCommit));
+    if patches.is_empty() {
+        eprintln!("no fields to patch were given");
+        process::exit(1);
+    }
+
+    for patch in &patches {
+        or_exit(crate::pe::patch::apply_patch(&mut pe_file, *patch));
+    }
+    println!("Patched {} field(s)", patches.len());
+    write_modified(&mut pe_file, &args.file, &args.destination);
+}
+
+fn run_section(command: &SectionCommand) {
+    match command {
+        SectionCommand::Add(args) => {
+            let mut pe_file = open_pe_file(&args.file);
+            let data = read_input(&args.data);
+            let flags = args.flags.iter().map(|arg| arg.flag()).fold(SectionFlags::empty(), |flags, flag| flags | flag);
+            let index = or_exit(crate::pe::section_edit::add_section(&mut pe_file, &args.name, flags, &data));
+            let header = &pe_file.sections[index].header;
+            println!(
+                "Added section {} at RVA {:08X}h, file offset {:08X}h",
+                args.name, header.virtual_address, header.pointer_to_raw_data
+            );
+            write_modified(&mut pe_file, &args.file, &args.destination);
+        }
+        SectionCommand::Remove(args) => {
+            let mut pe_file = open_pe_file(&args.file);
+            let index = find_section(&pe_file, &args.name);
+            let cleared = or_exit(crate::pe::section_edit::remove_section(&mut pe_file, index));
+            println!("Removed section {}", args.name);
+            for i in cleared {
+                println!(
+                    "Cleared the {} data directory",
+                    DataDirectory::DISPLAY_NAMES.get(i).copied().unwrap_or("unknown")
+                );
+            }
+            write_modified(&mut pe_file, &args.file, &args.destination);
+        }
+        SectionCommand::Resize(args) => {
+            let mut pe_file = open_pe_file(&args.file);
+            let index = find_section(&pe_file, &args.name);
+            let size = match u32::try_from(args.size) {
+                Ok(size) => size,
+                Err(_) => {
+                    eprintln!("size {:X}h is larger than 4 GiB", args.size);
+                    process::exit(1);
+                }
+            };
+            or_exit(crate::pe::section_edit::resize_section(&mut pe_file, index, size));
+            println!("Resized section {} to {:08X}h bytes", args.name, size);
+            write_modified(&mut pe_file, &args.file, &args.destination);
+        }
+    }
+}
+
+fn run_resource(command: &ResourceCommand) {
+    let (path, destination) = match command {
+        ResourceCommand::Set(args) => (&args.file, &args.destination),
+        ResourceCommand::Remove(args) => (&args.file, &args.destination),
+        ResourceCommand::Icon(args) => (&args.file, &args.destination),
+        ResourceCommand::Version(args) => (&args.file, &args.destination),
+    };
+    let mut pe_file = open_pe_file(path);
+    let section_table = pe_file.section_table();
+    let mut tree = or_exit(crate::pe::resource_edit::get_resource_tree(&mut open_file(path), &pe_file, &section_table)).unwrap_or_default();
+
+    let summary = match command {
+        ResourceCommand::Set(args) => {
+            let data = read_input(&args.data);
+            match tree.set(&args.resource_type, &args.name, args.language.as_ref(), &data) {
+                0 => format!("Added resource {}", format_resource_type(&args.resource_type)),
+                replaced => format!("Replaced {} resource(s)", replaced),
+            }
+        }
+        ResourceCommand::Remove(args) => match tree.remove(&args.resource_type, args.name.as_ref(), args.language.as_ref()) {
+            0 => {
+                eprintln!("no matching resources in {}", path.display());
+                process::exit(1);
+            }
+            removed => format!("Removed {} resource(s)", removed),
+        },
+        ResourceCommand::Icon(args) => {
+            let ico = read_input(&args.ico);
+            let name = match &args.name {
+                Some(name) => name.clone(),
+                None => tree.first_name(&ResourceId::GROUP_ICON).cloned().unwrap_or(ResourceId::Id(1)),
+            };
+            let count = or_exit(crate::pe::resource_edit::set_icon_group(&mut tree, &name, &ico));
+            format!("Replaced icon group {} with {} image(s)", name, count)
+        }
+        ResourceCommand::Version(args) => {
+            let versions: Vec<&mut ResourceItem> = tree.resources.iter_mut().filter(|item| item.resource_type == ResourceId::VERSION).collect();
+            if versions.is_empty() {
+                eprintln!("{} has no version resource", path.display());
+                process::exit(1);
+            }
+            for item in versions {
+                let mut version_info = or_exit(crate::pe::version_info::parse_version_info(&item.data));
+                if let Some(version) = args.file_version {
+                    or_exit(version_info.set_file_version(version));
+                }
+                if let Some(version) = args.product_version {
+                    or_exit(version_info.set_product_version(version));
+                }
+                for (key, value) in &args.strings {
+                    if version_info.set_string(key, value) == 0 {
+                        eprintln!("the version resource has no string tables to set {} in", key);
+                        process::exit(1);
+                    }
+                }
+                item.data = or_exit(crate::pe::version_info::version_info_to_bytes(&version_info));
+            }
+            String::from("Updated the version resource")
+        }
+    };
+
+    let index = or_exit(crate::pe::resource_edit::write_resource_tree(&mut pe_file, &tree));
+    println!("{}", summary);
+    let header = &pe_file.sections[index].header;
+    println!("Wrote resources to section {} at RVA {:08X}h", header.name_str(), header.virtual_address);
+    write_modified(&mut pe_file, path, destination);
+}
+
+fn run_add_import(args: &AddImportArgs) {
+    let mut pe_file = open_pe_file(&args.file);
+    let import = or_exit(crate::pe::import_edit::add_import(&mut pe_file, &args.dll, &args.functions));
+    println!(
+        "Added an import of {} function(s) from {}, with the import directory rebuilt at RVA {:08X}h",
+        import.functions.len(),
+        import.dll_name,
+        pe_file.data_directories[DataDirectory::IMPORT_TABLE].virtual_address
+    );
+    for function in &import.functions {
+        println!("\t{:08X}h  {}", function.thunk_rva, function.display_name());
+    }
+    write_modified(&mut pe_file, &args.file, &args.destination);
+}
+
+fn run_normalise(args: &NormaliseArgs) {
+    let mut pe_file = open_pe_file(&args.file);
+    // object files have no DOS stub, and so no Rich header
+    let rich_header = crate::pe::rich::get_rich_header(&mut open_file(&args.file)).ok().flatten();
+    let report = or_exit(crate::pe::normalise::normalise(&mut pe_file, rich_header.as_ref()));
+    println!("Cleared {} time stamp(s)", report.time_stamps_cleared);
+    if report.rich_header_cleared {
+        println!("Cleared the Rich header");
+    }
+    for (old_path, new_path) in &report.pdb_paths_stripped {
+        println!("Stripped PDB path {} to {}", old_path, new_path);
+    }
+    if report.pdb_ids_cleared > 0 {
+        println!("Cleared {} PDB GUID(s) and age(s)", report.pdb_ids_cleared);
+    }
+    if report.repro_hash_cleared {
+        println!("Cleared the repro hash");
+    }
+    write_modified(&mut pe_file, &args.file, &args.destination);
+}
+
+fn run_rebase(args: &RebaseArgs) {
+    let mut pe_file = open_pe_file(&args.file);
+    let report = or_exit(crate::pe::rebase::rebase(&mut pe_file, args.image_base));
+    println!(
+        "Rebased from {:X}h to {:X}h, applying {} fixup(s)",
+        report.old_image_base, report.new_image_base, report.fixups_applied
+    );
+    for rva in &report.fixups_skipped {
+        eprintln!("warning: fixup at RVA {:08X}h is outside the sections' raw data, so was not applied", rva);
+    }
+    write_modified(&mut pe_file, &args.file, &args.destination);
+}
+
+/// Finds the first section with a name, exiting if there is none.
+fn find_section(pe_file: &PeFile, name: &str) -> usize {
+    match pe_file.sections.iter().position(|section| section.header.name_str() == name) {
+        Some(index) => index,
+        None => {
+            eprintln!("no section named {}", name);
+            process::exit(1);
+        }
+    }
+}
+
+/// Recomputes the checksum of a modified file and writes it out, warning if this invalidates a signature.
+fn write_modified(pe_file: &mut PeFile, path: &Path, destination: &OutputArgs) {
+    if pe_file.data_directory(DataDirectory::CERTIFICATE_TABLE).is_some() {
+        eprintln!("warning: {} is signed, and modifying it invalidates the signature", path.display());
+    }
+    let checksum = or_exit(crate::pe::patch::update_checksum(pe_file));
+    let output = destination.output.as_deref().unwrap_or(path);
+    or_exit(crate::pe::ser::write_pe_file(pe_file, output));
+    if let Some(checksum) = checksum {
+        println!("Checksum updated to {:08X}h", checksum);
+    }
+    println!("Wrote {}", output.display());
+}
+
+fn run_strings(args: &StringsArgs, radix: Radix) {
+    let (mut handle, from_file, section_table) = open_image(&args.file);
+    let options = StringOptions {
+        min_len: args.min_len as usize,
+        ascii: args.encoding != StringEncodingArg::Utf16le,
+        utf16le: args.encoding != StringEncodingArg::Ascii,
+        data_only: args.data_only,
+    };
+    let strings = or_exit(crate::pe::strings::get_strings(&mut handle, from_file.as_ref(), &section_table, &options));
+    match args.format.delimiter() {
+        Some(delimiter) => table::print_strings_table(&strings, delimiter),
+        None => print_strings(&strings, radix),
+    }
+}
+
+#[cfg(feature = "disasm")]
+fn run_disasm(args: &DisasmArgs, radix: Radix) {
+    let (mut handle, from_file, section_table) = open_image(&args.file);
+    let (rva, label) = get_disasm_start(&mut handle, from_file.as_ref(), &section_table, args);
+    let symbols = crate::pe::disasm::get_symbols(&mut handle, from_file.as_ref(), &section_table);
+    let instructions = or_exit(crate::pe::disasm::disassemble(
+        &mut handle,
+        from_file.as_ref(),
+        &section_table,
+        rva,
+        args.count,
+        &symbols,
+    ));
+
+    let va_width = if from_file.is_pe32plus() { 16 } else { 8 };
+    println!("Disassembly of {} at RVA {}:", label, radix.format(rva, 8));
+    for instruction in &instructions {
+        let bytes: Vec<String> = instruction.bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        let line = format!("\t{:0width$X}  {:<30}{}", instruction.va, bytes.join(" "), instruction.text, width = va_width);
+        match &instruction.symbol {
+            Some(symbol) => println!("{}  ; {}", line, symbol),
+            None => println!("{}", line),
+        }
+    }
+}
+
+#[cfg(not(feature = "disasm"))]
+fn run_disasm(_args: &DisasmArgs, _radix: Radix) {
+    eprintln!("pepeek was built without the disasm feature, so disassembly is not available");
+    process::exit(1);
+}
+
+/// Works out where to start disassembling, returning the RVA and a description of it.
+#[cfg(feature = "disasm")]
+fn get_disasm_start(handle: &mut File, full_header: &dyn PEHeader, section_table: &[SectionHeader], args: &DisasmArgs) -> (u32, String) {
+    if let Some(rva) = args.rva {
+        match u32::try_from(rva) {
+            Ok(rva) => return (rva, String::from("RVA")),
+            Err(_) => {
+                eprintln!("RVA {:#x} is too large", rva);
+                process::exit(1);
+            }
+        }
+    }
+    if let Some(name) = &args.export {
+        let exports = or_exit(crate::pe::exports::get_exports(handle, full_header, section_table));
+        let function = exports
+            .as_ref()
+            .and_then(|exports| exports.functions.iter().find(|function| function.display_name() == *name));
+        return match function {
+            Some(function) if function.forwarder.is_some() => {
+                eprintln!("export {} is forwarded to {}", name, function.forwarder.as_deref().unwrap_or_default());
+                process::exit(1);
+            }
+            Some(function) => (function.rva, format!("export {}", name)),
+            None => {
+                eprintln!("no export named {}", name);
+                process::exit(1);
+            }
+        };
+    }
+    if let Some(index) = args.tls {
+        let tls = or_exit(crate::pe::tls::get_tls(handle, full_header, section_table));
+        return match tls.and_then(|tls| tls.callbacks.get(index).copied()) {
+            Some(rva) => (rva, format!("TLS callback {}", index)),
+            None => {
+                eprintln!("no TLS callback with index {}", index);
+                process::exit(1);
+            }
+        };
+    }
+    let entry_point = match (full_header.optional_header_pe32(), full_header.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => pe32_header.standard_fields.address_of_entry_point,
+        (_, Some(pe32plus_header)) => pe32plus_header.standard_fields.address_of_entry_point,
+        _ => 0,
+    };
+    if entry_point == 0 {
+        eprintln!("the image has no entry point");
+        process::exit(1);
+    }
+    (entry_point, String::from("entry point"))
+}
+
+/// Unwraps the result of parsing, exiting with an error message if the file is malformed.
+fn or_exit<T>(result: Result<T, PEError>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Opens a file, exiting with an error message if it cannot be opened.
+fn open_file(path: &Path) -> File {
+    match File::open(path) {
+        Ok(handle) => handle,
+        Err(err) => {
+            eprintln!("could not open {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Reads the whole of a file given as input to an edit, such as a new section's contents.
+fn read_input(path: &Path) -> Vec<u8> {
+    match fs::read(path) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("could not read {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+/// Opens an image or object file and reads the whole of it, for modifying.
+fn open_pe_file(path: &Path) -> PeFile {
+    let mut handle = open_file(path);
+    if crate::pe::archive::is_archive(&mut handle) {
+        eprintln!("{} is an archive, which cannot be modified", path.display());
+        process::exit(1);
+    }
+    or_exit(crate::pe::deser::get_pe_file(&mut handle))
+}
+
+/// Opens an image or object file and reads its headers and section table.
+fn open_image(path: &Path) -> (File, Box<dyn PEHeader>, Vec<SectionHeader>) {
+    let mut handle = open_file(path);
+    if crate::pe::archive::is_archive(&mut handle) {
+        eprintln!("{} is an archive, which only supports being printed in full", path.display());
+        process::exit(1);
+    }
+    let from_file = or_exit(crate::pe::deser::get_headers_from_file(&mut handle));
+    let section_table = or_exit(crate::pe::deser::get_section_table(&mut handle, from_file.as_ref()));
+    (handle, from_file, section_table)
+}
+
+#[cfg(feature = "serde")]
+fn print_json(handle: &mut File, path: &Path, full_header: &dyn PEHeader, section_table: &[SectionHeader]) {
+    // object files have no DOS header
+    let dos_header = crate::pe::deser::get_dos_header(handle).ok();
+    let file_name = path.file_name().unwrap().to_str().unwrap();
+    println!("{}", json::get_report(file_name, dos_header.as_ref(), full_header, section_table));
+}
+
+#[cfg(feature = "serde")]
+fn print_diff_json(args: &DiffArgs, changes: &[Change]) {
+    println!(
+        "{}",
+        json::get_diff_report(&args.old.display().to_string(), &args.new.display().to_string(), changes)
+    );
+}
+
+#[cfg(feature = "serde")]
+fn print_checksec_json(args: &ChecksecArgs, checks: &[MitigationCheck]) {
+    println!("{}", json::get_checksec_report(&args.file.display().to_string(), checks, &args.ignore));
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_checksec_json(_args: &ChecksecArgs, _checks: &[MitigationCheck]) {
+    eprintln!("pepeek was built without the serde feature, so JSON output is not available");
+    process::exit(1);
+}
+
+#[cfg(feature = "serde")]
+fn print_lint_json(args: &LintArgs, anomalies: &[Anomaly]) {
+    println!("{}", json::get_lint_report(&args.file.display().to_string(), anomalies, &args.allow));
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_lint_json(_args: &LintArgs, _anomalies: &[Anomaly]) {
+    eprintln!("pepeek was built without the serde feature, so JSON output is not available");
+    process::exit(1);
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_diff_json(_args: &DiffArgs, _changes: &[Change]) {
+    eprintln!("pepeek was built without the serde feature, so JSON output is not available");
+    process::exit(1);
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json(_handle: &mut File, _path: &Path, _full_header: &dyn PEHeader, _section_table: &[SectionHeader]) {
+    eprintln!("pepeek was built without the serde feature, so JSON output is not available");
+    process::exit(1);
+}
+
+fn print_rich_header(rich_header: &RichHeader, radix: Radix) {
+    println!("Rich header:");
+    println!("\tOffset:            {}", radix.format(rich_header.offset, 8));
+    println!("\tChecksum:          {:08X}h", rich_header.key);
+    if rich_header.is_checksum_valid() {
+        println!("\tChecksum valid?    yes");
+    } else {
+        println!("\tChecksum valid?    no (computed {:08X}h)", rich_header.computed_checksum);
+    }
+    println!("\tRich hash:         {}", rich_header.rich_hash());
+    println!("\tEntries:");
+    for entry in &rich_header.entries {
+        println!("\t\t{:08X}h  {:<32} count {}", entry.comp_id(), entry.to_string(), entry.count);
+    }
 }
 
 fn print_coff_info(full_header: &(impl PEHeader + ?Sized)) {
@@ -68,19 +855,17 @@ fn print_coff_info(full_header: &(impl PEHeader + ?Sized)) {
     println!("\tCharacteristics: {:?}", coff_header.characteristics);
 }
 
-fn print_optional_info(full_header: &Box<dyn PEHeader>) {
+fn print_optional_info(full_header: &dyn PEHeader, computed_checksum: Option<u32>, radix: Radix) {
     if let Some(pe32_header) = full_header.optional_header_pe32() {
-        print_optional_info_pe32(pe32_header);
-        print_data_directories(&full_header.data_directories().unwrap());
+        print_optional_info_pe32(pe32_header, computed_checksum, radix);
     }
 
     if let Some(pe32plus_header) = full_header.optional_header_pe32plus() {
-        print_optional_info_pe32plus(pe32plus_header);
-        print_data_directories(&full_header.data_directories().unwrap());
+        print_optional_info_pe32plus(pe32plus_header, computed_checksum, radix);
     }
 }
 
-fn print_optional_info_pe32(header: &OptionalHeaderPe32) {
+fn print_optional_info_pe32(header: &OptionalHeaderPe32, computed_checksum: Option<u32>, radix: Radix) {
     let standard_fields = &header.standard_fields;
     let windows_fields = &header.windows_fields;
     println!("PE32 optional header:");
@@ -90,17 +875,20 @@ fn print_optional_info_pe32(header: &OptionalHeaderPe32) {
         "\t\tLinker version:             {}.{}",
         standard_fields.major_linker_version, standard_fields.minor_linker_version
     );
-    println!("\t\tBase of code:               {0:08X}h ({0})", standard_fields.base_of_code);
-    println!("\t\tSize of code:               {0:08X}h ({0})", standard_fields.size_of_code);
-    println!("\t\tBase of data:               {0:08X}h ({0})", standard_fields.base_of_data);
-    println!("\t\tSize of initialised data:   {0:08X}h ({0})", standard_fields.size_of_initialised_data);
-    println!("\t\tSize of uninitialised data: {0:08X}h ({0})", standard_fields.size_of_uninitialised_data);
-    println!("\t\tEntry point address:        {0:08X}h ({0})", standard_fields.address_of_entry_point);
+    println!("\t\tBase of code:               {}", radix.format(standard_fields.base_of_code, 8));
+    println!("\t\tSize of code:               {}", radix.format(standard_fields.size_of_code, 8));
+    println!("\t\tBase of data:               {}", radix.format(standard_fields.base_of_data, 8));
+    println!("\t\tSize of initialised data:   {}", radix.format(standard_fields.size_of_initialised_data, 8));
+    println!(
+        "\t\tSize of uninitialised data: {}",
+        radix.format(standard_fields.size_of_uninitialised_data, 8)
+    );
+    println!("\t\tEntry point address:        {}", radix.format(standard_fields.address_of_entry_point, 8));
 
     println!("\tWindows fields:");
-    println!("\t\tImage base:                 {0:08X}h ({0})", windows_fields.image_base);
-    println!("\t\tSection alignment:          {0:08X}h ({0})", windows_fields.section_alignment);
-    println!("\t\tFile alignment:             {0:08X}h ({0})", windows_fields.file_alignment);
+    println!("\t\tImage base:                 {}", radix.format(windows_fields.image_base, 8));
+    println!("\t\tSection alignment:          {}", radix.format(windows_fields.section_alignment, 8));
+    println!("\t\tFile alignment:             {}", radix.format(windows_fields.file_alignment, 8));
     println!(
         "\t\tOS version:                 {}.{}",
         windows_fields.major_operating_system_version, windows_fields.minor_operating_system_version
@@ -114,17 +902,21 @@ fn print_optional_info_pe32(header: &OptionalHeaderPe32) {
         "\t\tSubsystem version:          {}.{}",
         windows_fields.major_subsystem_version, windows_fields.minor_subsystem_version
     );
-    println!("\t\tSize of image:              {0:08X}h ({0})", windows_fields.size_of_image);
-    println!("\t\tSize of headers:            {0:08X}h ({0})", windows_fields.size_of_headers);
-    println!("\t\tSize of stack reserve:      {0:08X}h ({0})", windows_fields.size_of_stack_reserve);
-    println!("\t\tSize of stack commit:       {0:08X}h ({0})", windows_fields.size_of_stack_commit);
-    println!("\t\tSize of heap reserve:       {0:08X}h ({0})", windows_fields.size_of_heap_reserve);
-    println!("\t\tSize of heap commit:        {0:08X}h ({0})", windows_fields.size_of_heap_commit);
+    println!("\t\tSize of image:              {}", radix.format(windows_fields.size_of_image, 8));
+    println!("\t\tSize of headers:            {}", radix.format(windows_fields.size_of_headers, 8));
+    println!(
+        "\t\tChecksum:                   {}",
+        format_checksum(windows_fields.checksum, computed_checksum)
+    );
+    println!("\t\tSize of stack reserve:      {}", radix.format(windows_fields.size_of_stack_reserve, 8));
+    println!("\t\tSize of stack commit:       {}", radix.format(windows_fields.size_of_stack_commit, 8));
+    println!("\t\tSize of heap reserve:       {}", radix.format(windows_fields.size_of_heap_reserve, 8));
+    println!("\t\tSize of heap commit:        {}", radix.format(windows_fields.size_of_heap_commit, 8));
     println!("\t\tNumber of data directories: {}", windows_fields.number_of_rva_and_sizes);
     println!("\t\tDLL characteristics:        {:?}", windows_fields.dll_characteristics);
 }
 
-fn print_optional_info_pe32plus(header: &OptionalHeaderPe32Plus) {
+fn print_optional_info_pe32plus(header: &OptionalHeaderPe32Plus, computed_checksum: Option<u32>, radix: Radix) {
     let standard_fields = &header.standard_fields;
     let windows_fields = &header.windows_fields;
     println!("PE32+ optional header:");
@@ -134,16 +926,19 @@ fn print_optional_info_pe32plus(header: &OptionalHeaderPe32Plus) {
         "\t\tLinker version:             {}.{}",
         standard_fields.major_linker_version, standard_fields.minor_linker_version
     );
-    println!("\t\tBase of code:               {0:08X}h ({0})", standard_fields.base_of_code);
-    println!("\t\tSize of code:               {0:08X}h ({0})", standard_fields.size_of_code);
-    println!("\t\tSize of initialised data:   {0:08X}h ({0})", standard_fields.size_of_initialised_data);
-    println!("\t\tSize of uninitialised data: {0:08X}h ({0})", standard_fields.size_of_uninitialised_data);
-    println!("\t\tEntry point address:        {0:08X}h ({0})", standard_fields.address_of_entry_point);
+    println!("\t\tBase of code:               {}", radix.format(standard_fields.base_of_code, 8));
+    println!("\t\tSize of code:               {}", radix.format(standard_fields.size_of_code, 8));
+    println!("\t\tSize of initialised data:   {}", radix.format(standard_fields.size_of_initialised_data, 8));
+    println!(
+        "\t\tSize of uninitialised data: {}",
+        radix.format(standard_fields.size_of_uninitialised_data, 8)
+    );
+    println!("\t\tEntry point address:        {}", radix.format(standard_fields.address_of_entry_point, 8));
 
     println!("\tWindows fields:");
-    println!("\t\tImage base:                 {0:016X}h ({0})", windows_fields.image_base);
-    println!("\t\tSection alignment:                  {0:08X}h ({0})", windows_fields.section_alignment);
-    println!("\t\tFile alignment:                     {0:08X}h ({0})", windows_fields.file_alignment);
+    println!("\t\tImage base:                 {}", radix.format(windows_fields.image_base, 16));
+    println!("\t\tSection alignment:                  {}", radix.format(windows_fields.section_alignment, 8));
+    println!("\t\tFile alignment:                     {}", radix.format(windows_fields.file_alignment, 8));
     println!(
         "\t\tOS version:                 {}.{}",
         windows_fields.major_operating_system_version, windows_fields.minor_operating_system_version
@@ -157,41 +952,303 @@ fn print_optional_info_pe32plus(header: &OptionalHeaderPe32Plus) {
         "\t\tSubsystem version:          {}.{}",
         windows_fields.major_subsystem_version, windows_fields.minor_subsystem_version
     );
-    println!("\t\tSize of image:                      {0:08X}h ({0})", windows_fields.size_of_image);
-    println!("\t\tSize of headers:                    {0:08X}h ({0})", windows_fields.size_of_headers);
-    println!("\t\tSize of stack reserve:      {0:016X}h ({0})", windows_fields.size_of_stack_reserve);
-    println!("\t\tSize of stack commit:       {0:016X}h ({0})", windows_fields.size_of_stack_commit);
-    println!("\t\tSize of heap reserve:       {0:016X}h ({0})", windows_fields.size_of_heap_reserve);
-    println!("\t\tSize of heap commit:        {0:016X}h ({0})", windows_fields.size_of_heap_commit);
+    println!("\t\tSize of image:                      {}", radix.format(windows_fields.size_of_image, 8));
+    println!("\t\tSize of headers:                    {}", radix.format(windows_fields.size_of_headers, 8));
+    println!(
+        "\t\tChecksum:                   {}",
+        format_checksum(windows_fields.checksum, computed_checksum)
+    );
+    println!("\t\tSize of stack reserve:      {}", radix.format(windows_fields.size_of_stack_reserve, 16));
+    println!("\t\tSize of stack commit:       {}", radix.format(windows_fields.size_of_stack_commit, 16));
+    println!("\t\tSize of heap reserve:       {}", radix.format(windows_fields.size_of_heap_reserve, 16));
+    println!("\t\tSize of heap commit:        {}", radix.format(windows_fields.size_of_heap_commit, 16));
     println!("\t\tNumber of data directories: {}", windows_fields.number_of_rva_and_sizes);
     println!("\t\tDLL characteristics:        {:?}", windows_fields.dll_characteristics);
 }
 
-fn print_data_directories(dirs: &Vec<DataDirectory>) {
+fn print_data_directories(dirs: &[DataDirectory], radix: Radix) {
     println!("\tData directories:");
     for (i, dir) in dirs.iter().enumerate() {
         if i < 16 {
-            println!("\t\tTable {}: {}", i, DATA_DIRECTORY_DISPLAY_NAMES[i]);
+            println!("\t\tTable {}: {}", i, DataDirectory::DISPLAY_NAMES[i]);
         } else {
             println!("\t\tTable {}", i);
         }
-        println!("\t\t\tVirtual address: {0:08X}h ({0})", dir.virtual_address);
-        println!("\t\t\tSize:            {0:08X}h ({0})", dir.size);
+        println!("\t\t\tVirtual address: {}", radix.format(dir.virtual_address, 8));
+        println!("\t\t\tSize:            {}", radix.format(dir.size, 8));
     }
 }
 
-fn print_section_headers(section_headers: &Vec<SectionHeader>) {
+fn print_section_headers<'a>(section_headers: impl Iterator<Item = (usize, &'a SectionHeader)>, section_data: &[Vec<u8>], radix: Radix) {
     println!("Section headers:");
-    for (i, header) in section_headers.iter().enumerate() {
+    for ((i, header), data) in section_headers.zip(section_data) {
         let name_str = String::from_utf8_lossy(&header.name);
         println!("\tSection {}: {}", i, name_str);
-        println!("\t\tVirtual size:           {0:08X}h ({0})", header.virtual_size);
-        println!("\t\tVirtual address:        {0:08X}h ({0})", header.virtual_address);
-        println!("\t\tSize of raw data:       {0:08X}h ({0})", header.size_of_raw_data);
-        println!("\t\tPointer to raw data:    {0:08X}h ({0})", header.pointer_to_raw_data);
-        println!("\t\tPointer to relocations: {0:08X}h ({0})", header.pointer_to_relocations);
-        println!("\t\tNumber of relocations:  {0:08X}h ({0})", header.number_of_relocations);
-        println!("\t\tFlags:                  {:?}", header.characteristics);
+        println!("\t\tVirtual size:            {}", radix.format(header.virtual_size, 8));
+        println!("\t\tVirtual address:         {}", radix.format(header.virtual_address, 8));
+        println!("\t\tSize of raw data:        {}", radix.format(header.size_of_raw_data, 8));
+        println!("\t\tPointer to raw data:     {}", radix.format(header.pointer_to_raw_data, 8));
+        println!("\t\tPointer to relocations:  {}", radix.format(header.pointer_to_relocations, 8));
+        println!("\t\tNumber of relocations:   {}", radix.format(header.number_of_relocations, 8));
+        println!("\t\tPointer to line numbers: {}", radix.format(header.pointer_to_line_numbers, 8));
+        println!("\t\tNumber of line numbers:  {}", radix.format(header.number_of_line_numbers, 8));
+        println!("\t\tFlags:                   {:?}", header.characteristics);
+        let digests = get_digests(data);
+        println!("\t\tEntropy:                 {:.4}", shannon_entropy(data));
+        println!("\t\tMD5:                     {}", digests.md5);
+        println!("\t\tSHA-1:                   {}", digests.sha1);
+        println!("\t\tSHA-256:                 {}", digests.sha256);
+    }
+}
+
+fn print_coff_tables(handle: &mut File, coff_header: &CoffHeader, section_headers: &[SectionHeader]) {
+    if !section_headers
+        .iter()
+        .any(|header| header.number_of_relocations > 0 || header.number_of_line_numbers > 0)
+    {
+        return;
+    }
+
+    let symbols = or_exit(crate::pe::coff::get_symbol_table(handle, coff_header));
+    println!("Relocations and line numbers:");
+    for (i, header) in section_headers.iter().enumerate() {
+        if header.number_of_relocations > 0 {
+            let relocations = or_exit(crate::pe::coff::get_relocations(handle, coff_header, header, symbols.as_ref()));
+            println!("\tSection {}: {} relocation(s)", i, relocations.len());
+            for reloc in relocations {
+                let symbol_name = reloc.symbol_name.unwrap_or_else(|| String::from("?"));
+                println!(
+                    "\t\t{:08X}h  {:<32} {} (symbol {})",
+                    reloc.virtual_address, reloc.relocation_type, symbol_name, reloc.symbol_table_index
+                );
+            }
+        }
+
+        if header.number_of_line_numbers > 0 {
+            let functions = or_exit(crate::pe::coff::get_line_numbers(handle, header, symbols.as_ref()));
+            println!("\tSection {}: line numbers for {} function(s)", i, functions.len());
+            for function in functions {
+                let function_name = function.function_name.unwrap_or_else(|| String::from("?"));
+                println!("\t\t{} (symbol {})", function_name, function.symbol_table_index);
+                for line in function.lines {
+                    println!("\t\t\tLine {:<5} at {:08X}h", line.line_number, line.virtual_address);
+                }
+            }
+        }
+    }
+}
+
+fn print_overlay(overlay: Option<&Overlay>, overlay_data: Option<&[u8]>, radix: Radix) {
+    println!("Overlay:");
+    match (overlay, overlay_data) {
+        (Some(overlay), Some(data)) => {
+            println!("\tOffset:  {}", radix.format(overlay.offset, 8));
+            println!("\tSize:    {}", radix.format(overlay.size, 8));
+            println!("\tEntropy: {:.4}", shannon_entropy(data));
+            if let Some((cert_offset, cert_size)) = overlay.certificate_table {
+                println!("\tCertificate table:");
+                println!("\t\tOffset: {}", radix.format(cert_offset, 8));
+                println!("\t\tSize:   {}", radix.format(cert_size, 8));
+            }
+        }
+        _ => println!("\tNone"),
+    }
+}
+
+fn print_imports(imports: &[ImportedDll], radix: Radix) {
+    println!("Imports:");
+    if imports.is_empty() {
+        println!("\tNone");
+    }
+    for dll in imports {
+        println!("\t{}: {} function(s)", dll.dll_name, dll.functions.len());
+        println!("\t\tImport lookup table:    {}", radix.format(dll.descriptor.import_lookup_table_rva, 8));
+        println!("\t\tImport address table:   {}", radix.format(dll.descriptor.import_address_table_rva, 8));
+        for function in &dll.functions {
+            match function.hint {
+                Some(hint) => println!("\t\t\t{}  {} (hint {})", radix.format(function.thunk_rva, 8), function.display_name(), hint),
+                None => println!("\t\t\t{}  {}", radix.format(function.thunk_rva, 8), function.display_name()),
+            }
+        }
+    }
+}
+
+fn print_exports(exports: Option<&Exports>, radix: Radix) {
+    println!("Exports:");
+    let Some(exports) = exports else {
+        println!("\tNone");
+        return;
+    };
+    println!("\tDLL name:               {}", exports.dll_name);
+    println!("\tOrdinal base:           {}", exports.directory.ordinal_base);
+    println!("\tFunctions:              {}", exports.functions.len());
+    for function in &exports.functions {
+        match &function.forwarder {
+            Some(forwarder) => println!(
+                "\t\t{:<5} {}  {} -> {}",
+                function.ordinal,
+                radix.format(function.rva, 8),
+                function.display_name(),
+                forwarder
+            ),
+            None => println!("\t\t{:<5} {}  {}", function.ordinal, radix.format(function.rva, 8), function.display_name()),
+        }
+    }
+}
+
+fn print_resources(resources: Option<&ResourceDirectory>, radix: Radix) {
+    println!("Resources:");
+    let Some(resources) = resources else {
+        println!("\tNone");
+        return;
+    };
+    for resource in resources.resources() {
+        println!(
+            "\t{} / {} / {}",
+            format_resource_type(&resource.resource_type),
+            resource.name,
+            resource.language
+        );
+        println!("\t\tData RVA:               {}", radix.format(resource.data.data_rva, 8));
+        println!("\t\tSize:                   {}", radix.format(resource.data.size, 8));
+        println!("\t\tCodepage:               {}", resource.data.codepage);
+    }
+}
+
+fn print_checksec(checks: &[MitigationCheck], ignored: &[String]) {
+    println!("Security mitigations:");
+    for check in checks {
+        let note = if ignored.iter().any(|id| id == check.id) { " (ignored)" } else { "" };
+        println!("\t{:<18}{:<6}{}{}", check.name, check.status, check.detail, note);
+    }
+}
+
+fn print_strings(strings: &[FoundString], radix: Radix) {
+    for found in strings {
+        let section = found.section.as_deref().unwrap_or("(overlay)");
+        let rva = found.rva.map_or(String::from("-"), |rva| radix.format(rva, 8));
+        println!("{:<9} {} {} {:<7} {}", section, radix.format(found.offset, 8), rva, found.encoding, found.value);
+    }
+}
+
+/// Prints a hex dump, annotating each line with the regions of the file that it overlaps.
+fn print_hex_dump(start_offset: u64, data: &[u8], regions: &[Region]) {
+    for (i, line) in data.chunks(DUMP_LINE_LEN).enumerate() {
+        let line_offset = start_offset + (i * DUMP_LINE_LEN) as u64;
+        let names: Vec<&str> = regions
+            .iter()
+            .filter(|region| region.overlaps(line_offset, line_offset + line.len() as u64))
+            .map(|region| region.name.as_str())
+            .collect();
+        let hex: Vec<String> = line.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let ascii: String = line
+            .iter()
+            .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+            .collect();
+        let line_text = format!(
+            "{:08X}h  {:<width$}  |{:<len$}|",
+            line_offset,
+            hex.join(" "),
+            ascii,
+            width = DUMP_LINE_LEN * 3 - 1,
+            len = DUMP_LINE_LEN
+        );
+        if names.is_empty() {
+            println!("{}", line_text);
+        } else {
+            println!("{}  {}", line_text, names.join(", "));
+        }
+    }
+}
+
+fn print_hashes(file_data: &[u8], full_header: &dyn PEHeader, imports: &[ImportedDll], exports: Option<&Exports>) {
+    let digests = get_digests(file_data);
+    println!("Hashes:");
+    println!("\tMD5:                  {}", digests.md5);
+    println!("\tSHA-1:                {}", digests.sha1);
+    println!("\tSHA-256:              {}", digests.sha256);
+    if let Some(imphash) = get_imphash(imports) {
+        println!("\tImphash:              {}", imphash);
+    }
+    if let Some(exphash) = exports.and_then(get_exphash) {
+        println!("\tExphash:              {}", exphash);
+    }
+    // object files are never signed
+    if full_header.optional_header_pe32().is_some() || full_header.optional_header_pe32plus().is_some() {
+        if let Ok(authentihash) = get_authentihash(file_data) {
+            println!("\tAuthentihash SHA-1:   {}", authentihash.sha1);
+            println!("\tAuthentihash SHA-256: {}", authentihash.sha256);
+        }
+    }
+    if let Some(fuzzy_hash) = get_fuzzy_hash(file_data) {
+        println!("\tFuzzy hash:           {}", fuzzy_hash);
+    }
+}
+
+fn print_archive(archive: &Archive, radix: Radix) {
+    println!("Archive:");
+    println!("\tLinker member symbols: {}", archive.first_linker_symbols.len());
+    if let Some(symbols) = &archive.second_linker_symbols {
+        println!("\tSecond linker member symbols: {}", symbols.len());
+    }
+    println!("\tMembers: {}", archive.members.len());
+    for (i, member) in archive.members.iter().enumerate() {
+        println!("\tMember {}: {}", i, member.name);
+        println!("\t\tOffset:                 {}", radix.format(member.data_offset, 8));
+        println!("\t\tSize:                   {}", radix.format(member.size, 8));
+        match &member.contents {
+            ArchiveMemberContents::Object(object) => {
+                let coff_header = &object.coff_header;
+                println!("\t\tKind:                   COFF object");
+                println!(
+                    "\t\tMachine type:           {:?} ({}) (0x{:04x})",
+                    coff_header.target_machine, coff_header.target_machine, coff_header.target_machine as u16
+                );
+                let section_names: Vec<String> = object
+                    .section_table
+                    .iter()
+                    .map(|header| String::from_utf8_lossy(&header.name).trim_end_matches('\0').to_string())
+                    .collect();
+                println!("\t\tSections:               {}", section_names.join(", "));
+                println!("\t\tSymbols:                {}", coff_header.number_of_symbols);
+            }
+            ArchiveMemberContents::Import(import) => {
+                let header = &import.header;
+                println!("\t\tKind:                   Import object");
+                println!(
+                    "\t\tMachine type:           {:?} ({}) (0x{:04x})",
+                    header.machine, header.machine, header.machine as u16
+                );
+                println!("\t\tDLL:                    {}", import.dll_name);
+                println!("\t\tSymbol:                 {}", import.symbol_name);
+                println!("\t\tImport type:            {}", header.import_type());
+                println!("\t\tName type:              {}", header.name_type());
+                match import.import_name() {
+                    Some(name) => {
+                        println!("\t\tImport name:            {}", name);
+                        println!("\t\tHint:                   {}", header.ordinal_or_hint);
+                    }
+                    None => println!("\t\tOrdinal:                {}", header.ordinal_or_hint),
+                }
+            }
+            ArchiveMemberContents::Other => println!("\t\tKind:                   Other"),
+        }
+    }
+}
+
+fn format_checksum(stored: u32, computed: Option<u32>) -> String {
+    match computed {
+        Some(computed) if stored == 0 => format!("{:08X}h (not set, computed {:08X}h)", stored, computed),
+        Some(computed) if stored == computed => format!("{:08X}h (valid)", stored),
+        Some(computed) => format!("{:08X}h (invalid, computed {:08X}h)", stored, computed),
+        None => format!("{:08X}h", stored),
+    }
+}
+
+fn format_resource_type(resource_type: &ResourceId) -> String {
+    match resource_type.type_name() {
+        Some(name) => format!("{} ({})", name, resource_type),
+        None => resource_type.to_string(),
     }
 }
 
@@ -14,3 +14,95 @@ pub mod traits;
 
 /// PE file deserialisation.
 pub mod deser;
+
+/// Editable model of a whole file.
+pub mod file;
+
+/// PE file serialisation.
+pub mod ser;
+
+/// COFF relocations, symbols and line numbers.
+pub mod coff;
+
+/// Static and import library archives.
+pub mod archive;
+
+/// Rich header decoding.
+pub mod rich;
+
+/// Overlay detection.
+pub mod overlay;
+
+/// Entropy measures over byte buffers.
+pub mod entropy;
+
+/// PE checksum computation.
+pub mod checksum;
+
+/// Hashing of files and sections.
+pub mod hashes;
+
+/// Import directory parsing.
+pub mod imports;
+
+/// Export directory parsing.
+pub mod exports;
+
+/// Resource directory parsing.
+pub mod resources;
+
+/// Similarity-preserving fuzzy hashing.
+pub mod fuzzy;
+
+/// Structural comparison of two files.
+pub mod diff;
+
+/// Debug directory parsing.
+pub mod debug;
+
+/// Load config directory parsing.
+pub mod load_config;
+
+/// Security mitigation checks.
+pub mod checksec;
+
+/// Anomaly and malformation lints.
+pub mod lint;
+
+/// Packer, protector and toolchain identification from a rule database.
+#[cfg(feature = "serde")]
+pub mod ident;
+
+/// String extraction with section and RVA attribution.
+pub mod strings;
+
+/// File layout of headers, sections and data directories.
+pub mod layout;
+
+/// TLS directory and callback parsing.
+pub mod tls;
+
+/// Disassembly of x86, x64 and ARM64 code.
+#[cfg(feature = "disasm")]
+pub mod disasm;
+
+/// Header field patching.
+pub mod patch;
+
+/// Adding, removing and resizing sections.
+pub mod section_edit;
+
+/// Resource tree editing and rebuilding of the resource section.
+pub mod resource_edit;
+
+/// Version resource parsing and editing.
+pub mod version_info;
+
+/// Import directory rebuilding and import injection.
+pub mod import_edit;
+
+/// Clearing of build-specific data for reproducible builds.
+pub mod normalise;
+
+/// Moving an image to a new image base using its base relocations.
+pub mod rebase;
@@ -0,0 +1,475 @@
+use super::body::SectionHeader;
+use super::deser::{get_coff_header_at, get_section_table_at};
+use super::err::PEError;
+use super::headers::{CoffHeader, MachineType};
+use super::internal::agnostic_fio::{read_exact, read_vec};
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::mem::{size_of, transmute};
+
+/// Signature at the start of every archive file.
+pub const ARCHIVE_SIGNATURE: [u8; 8] = *b"!<arch>\n";
+
+/// A raw archive member header (`IMAGE_ARCHIVE_MEMBER_HEADER`). All fields are ASCII text, padded with spaces.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ArchiveMemberHeader {
+    pub name: [u8; 16],
+    pub date: [u8; 12],
+    pub user_id: [u8; 6],
+    pub group_id: [u8; 6],
+    pub mode: [u8; 8],
+    pub size: [u8; 10],
+    pub end_of_header: [u8; 2],
+}
+
+/// Whether an import object imports code, data or a constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportType {
+    Code,
+    Data,
+    Const,
+    Unknown(u16),
+}
+
+/// How the imported name is derived from the symbol name of an import object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportNameType {
+    /// Imported by ordinal, given in `ordinal_or_hint`.
+    Ordinal,
+    /// The imported name is the symbol name.
+    Name,
+    /// The imported name is the symbol name without a leading `?`, `@` or `_`.
+    NameNoPrefix,
+    /// The imported name is the symbol name without a leading `?`, `@` or `_`, truncated at the first `@`.
+    NameUndecorate,
+    /// The imported name is given explicitly after the DLL name.
+    NameExportAs,
+    Unknown(u16),
+}
+
+/// A short-form import object header (`IMPORT_OBJECT_HEADER`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ImportObjectHeader {
+    /// Always zero.
+    pub sig1: u16,
+    /// Always `0xffff`.
+    pub sig2: u16,
+    pub version: u16,
+    pub machine: MachineType,
+    pub time_date_stamp: u32,
+    /// Size of the strings following the header.
+    pub size_of_data: u32,
+    pub ordinal_or_hint: u16,
+    /// Import type in bits 0-1, name type in bits 2-4.
+    pub type_info: u16,
+}
+
+/// A short-form import object, describing a single import from a DLL.
+#[derive(Debug, Clone)]
+pub struct ImportObject {
+    pub header: ImportObjectHeader,
+    pub symbol_name: String,
+    pub dll_name: String,
+    /// Explicit export name, present when the name type is `NameExportAs`.
+    pub export_name: Option<String>,
+}
+
+/// A COFF object file stored in an archive.
+#[derive(Debug)]
+pub struct ArchiveObject {
+    pub coff_header: CoffHeader,
+    /// Section headers, with data pointers relative to the start of the member.
+    pub section_table: Vec<SectionHeader>,
+}
+
+/// The decoded contents of an archive member.
+#[derive(Debug)]
+pub enum ArchiveMemberContents {
+    Object(ArchiveObject),
+    Import(ImportObject),
+    /// A member that is neither a COFF object nor an import object, such as an LTCG object or LLVM bitcode.
+    Other,
+}
+
+/// A member of an archive, other than the linker and longnames members.
+#[derive(Debug)]
+pub struct ArchiveMember {
+    /// Name of the member, with long names resolved from the longnames member.
+    pub name: String,
+    /// Modification time, in seconds since 1st Jan 1970.
+    pub date: u32,
+    /// File offset of the member's data, just past its header.
+    pub data_offset: u32,
+    pub size: u32,
+    pub contents: ArchiveMemberContents,
+}
+
+/// A public symbol from a linker member, with the file offset of the header of the member defining it.
+#[derive(Debug, Clone)]
+pub struct ArchiveSymbol {
+    pub name: String,
+    pub member_offset: u32,
+}
+
+/// A static or import library archive (`.lib`).
+#[derive(Debug)]
+pub struct Archive {
+    /// Symbols from the first linker member, in the order given.
+    pub first_linker_symbols: Vec<ArchiveSymbol>,
+    /// Symbols from the second linker member, sorted by name, or `None` if it is absent.
+    pub second_linker_symbols: Option<Vec<ArchiveSymbol>>,
+    /// Raw contents of the longnames member, if present.
+    pub longnames: Option<Vec<u8>>,
+    pub members: Vec<ArchiveMember>,
+}
+
+/// Checks whether a file starts with the archive signature.
+pub fn is_archive(fh: &mut File) -> bool {
+    matches!(read_exact::<8>(fh, 0), Ok(signature) if signature == ARCHIVE_SIGNATURE)
+}
+
+/// Reads an archive, decoding its linker members and each of its object and import members.
+pub fn get_archive_from_file(fh: &mut File) -> Result<Archive, PEError> {
+    let raw_members = match get_raw_members(fh) {
+        Ok(raw_members) => raw_members,
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+
+    let mut archive = Archive {
+        first_linker_symbols: Vec::new(),
+        second_linker_symbols: None,
+        longnames: None,
+        members: Vec::new(),
+    };
+    let mut linker_members_seen = 0;
+    for (data_offset, header) in raw_members {
+        let name = header_field_str(&header.name);
+        let size = match parse_decimal_field(&header.size) {
+            Ok(size) => size,
+            Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+        };
+        match name.as_str() {
+            "/" if linker_members_seen == 0 => {
+                let data = read_member_data(fh, data_offset, size)?;
+                archive.first_linker_symbols = parse_first_linker_member(&data)?;
+                linker_members_seen += 1;
+            }
+            "/" if linker_members_seen == 1 => {
+                let data = read_member_data(fh, data_offset, size)?;
+                archive.second_linker_symbols = Some(parse_second_linker_member(&data)?);
+                linker_members_seen += 1;
+            }
+            "//" => {
+                archive.longnames = Some(read_member_data(fh, data_offset, size)?);
+            }
+            "/SYM64/" => {
+                // GNU 64-bit symbol table, which MSVC archives never contain
+            }
+            _ if name.starts_with("/<") && name.ends_with(">/") => {
+                // other special members, such as the ARM64EC symbol table /<ECSYMBOLS>/ and /<XFGHASHMAP>/
+            }
+            _ => {
+                let member_name = resolve_member_name(&name, archive.longnames.as_deref());
+                let contents = get_member_contents(fh, data_offset, size)?;
+                archive.members.push(ArchiveMember {
+                    name: member_name,
+                    date: parse_decimal_field(&header.date).unwrap_or(0),
+                    data_offset,
+                    size,
+                    contents,
+                });
+            }
+        }
+    }
+    Ok(archive)
+}
+
+impl ImportObjectHeader {
+    pub fn import_type(&self) -> ImportType {
+        match self.type_info & 0x3 {
+            0 => ImportType::Code,
+            1 => ImportType::Data,
+            2 => ImportType::Const,
+            other => ImportType::Unknown(other),
+        }
+    }
+
+    pub fn name_type(&self) -> ImportNameType {
+        match (self.type_info >> 2) & 0x7 {
+            0 => ImportNameType::Ordinal,
+            1 => ImportNameType::Name,
+            2 => ImportNameType::NameNoPrefix,
+            3 => ImportNameType::NameUndecorate,
+            4 => ImportNameType::NameExportAs,
+            other => ImportNameType::Unknown(other),
+        }
+    }
+}
+
+impl ImportObject {
+    /// The name the symbol is imported by from the DLL, or `None` if it is imported by ordinal.
+    pub fn import_name(&self) -> Option<String> {
+        let symbol_name = self.symbol_name.as_str();
+        match self.header.name_type() {
+            ImportNameType::Ordinal => None,
+            ImportNameType::Name | ImportNameType::Unknown(_) => Some(symbol_name.to_string()),
+            ImportNameType::NameNoPrefix => Some(strip_import_prefix(symbol_name).to_string()),
+            ImportNameType::NameUndecorate => {
+                let stripped = strip_import_prefix(symbol_name);
+                Some(stripped.split('@').next().unwrap_or(stripped).to_string())
+            }
+            ImportNameType::NameExportAs => self.export_name.clone(),
+        }
+    }
+}
+
+impl Display for ImportType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Code => write!(f, "Code"),
+            Self::Data => write!(f, "Data"),
+            Self::Const => write!(f, "Const"),
+            Self::Unknown(value) => write!(f, "Unknown ({})", value),
+        }
+    }
+}
+
+impl Display for ImportNameType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Ordinal => write!(f, "Ordinal"),
+            Self::Name => write!(f, "Name"),
+            Self::NameNoPrefix => write!(f, "Name without prefix"),
+            Self::NameUndecorate => write!(f, "Undecorated name"),
+            Self::NameExportAs => write!(f, "Export-as name"),
+            Self::Unknown(value) => write!(f, "Unknown ({})", value),
+        }
+    }
+}
+
+fn get_raw_members(fh: &mut File) -> io::Result<Vec<(u32, ArchiveMemberHeader)>> {
+    if read_exact::<8>(fh, 0)? != ARCHIVE_SIGNATURE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing archive signature"));
+    }
+
+    let len = fh.metadata()?.len();
+    let mut ret: Vec<(u32, ArchiveMemberHeader)> = Vec::new();
+    let mut addr = ARCHIVE_SIGNATURE.len() as u64;
+    while addr + (size_of::<ArchiveMemberHeader>() as u64) <= len {
+        let header: ArchiveMemberHeader = unsafe { transmute(read_exact::<{ size_of::<ArchiveMemberHeader>() }>(fh, addr)?) };
+        if header.end_of_header != *b"`\n" {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad archive member header"));
+        }
+
+        let size = parse_decimal_field(&header.size)?;
+        let data_addr = addr + size_of::<ArchiveMemberHeader>() as u64;
+        ret.push((data_addr as u32, header));
+
+        // members are aligned to even offsets
+        addr = data_addr + size as u64 + (size as u64 & 1);
+    }
+    Ok(ret)
+}
+
+fn get_member_contents(fh: &mut File, data_offset: u32, size: u32) -> Result<ArchiveMemberContents, PEError> {
+    if size < 4 {
+        return Ok(ArchiveMemberContents::Other);
+    }
+
+    let signature = match read_exact::<4>(fh, data_offset as u64) {
+        Ok(signature) => signature,
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+    if signature == [0x00, 0x00, 0xff, 0xff] {
+        // import objects have version 0, anything later is an anonymous object such as an LTCG object
+        let version = match read_exact::<2>(fh, data_offset as u64 + 4) {
+            Ok(version) => u16::from_le_bytes(version),
+            Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+        };
+        if version != 0 {
+            return Ok(ArchiveMemberContents::Other);
+        }
+        return Ok(ArchiveMemberContents::Import(get_import_object(fh, data_offset)?));
+    }
+
+    // anything else that isn't a COFF object, such as LLVM bitcode or Rust metadata, fails on the machine type
+    let Ok(coff_header) = get_coff_header_at(fh, data_offset) else {
+        return Ok(ArchiveMemberContents::Other);
+    };
+    let section_table = get_section_table_at(fh, data_offset, &coff_header)?;
+    Ok(ArchiveMemberContents::Object(ArchiveObject { coff_header, section_table }))
+}
+
+fn get_import_object(fh: &mut File, data_offset: u32) -> Result<ImportObject, PEError> {
+    let header: ImportObjectHeader = match read_exact::<{ size_of::<ImportObjectHeader>() }>(fh, data_offset as u64) {
+        Ok(bytes) => match MachineType::try_from(u16::from_le_bytes([bytes[6], bytes[7]])) {
+            Ok(_) => unsafe { transmute::<[u8; 20], ImportObjectHeader>(bytes) },
+            Err(machine) => return Err(PEError::DeserialiseError(format!("unknown machine type {:#06x}", machine))),
+        },
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+    let strings_addr = data_offset + size_of::<ImportObjectHeader>() as u32;
+    let data = read_member_data(fh, strings_addr, header.size_of_data)?;
+
+    // symbol name, DLL name and optional export name, each null terminated
+    let mut strings = data.split(|&b| b == 0).map(|s| String::from_utf8_lossy(s).into_owned());
+    let symbol_name = strings.next().unwrap_or_default();
+    let dll_name = strings.next().unwrap_or_default();
+    let export_name = match header.name_type() {
+        ImportNameType::NameExportAs => strings.next(),
+        _ => None,
+    };
+
+    Ok(ImportObject {
+        header,
+        symbol_name,
+        dll_name,
+        export_name,
+    })
+}
+
+fn parse_first_linker_member(data: &[u8]) -> Result<Vec<ArchiveSymbol>, PEError> {
+    // big-endian symbol count and member offsets, then the symbol names
+    let num_symbols = read_count(data, 0, 4, u32::from_be_bytes)?;
+    let mut offsets: Vec<u32> = Vec::with_capacity(num_symbols);
+    for i in 0..num_symbols {
+        offsets.push(read_u32(data, 4 + i * 4, u32::from_be_bytes)?);
+    }
+
+    let names = split_names(&data[(4 + num_symbols * 4).min(data.len())..], num_symbols);
+    Ok(offsets
+        .into_iter()
+        .zip(names)
+        .map(|(member_offset, name)| ArchiveSymbol { name, member_offset })
+        .collect())
+}
+
+fn parse_second_linker_member(data: &[u8]) -> Result<Vec<ArchiveSymbol>, PEError> {
+    // little-endian member offsets, then one-based member indices for each symbol, then the symbol names
+    let num_members = read_count(data, 0, 4, u32::from_le_bytes)?;
+    let mut offsets: Vec<u32> = Vec::with_capacity(num_members);
+    for i in 0..num_members {
+        offsets.push(read_u32(data, 4 + i * 4, u32::from_le_bytes)?);
+    }
+
+    let symbols_addr = 4 + num_members * 4;
+    let num_symbols = read_count(data, symbols_addr, 2, u32::from_le_bytes)?;
+    let indices_addr = symbols_addr + 4;
+    let mut indices: Vec<u16> = Vec::with_capacity(num_symbols);
+    for i in 0..num_symbols {
+        match data.get(indices_addr + i * 2..indices_addr + i * 2 + 2) {
+            Some(bytes) => indices.push(u16::from_le_bytes(bytes.try_into().unwrap())),
+            None => return Err(PEError::DeserialiseError(String::from("second linker member is truncated"))),
+        }
+    }
+
+    let names = split_names(&data[(indices_addr + num_symbols * 2).min(data.len())..], num_symbols);
+    Ok(indices
+        .into_iter()
+        .zip(names)
+        .map(|(index, name)| ArchiveSymbol {
+            name,
+            member_offset: offsets.get((index as usize).wrapping_sub(1)).copied().unwrap_or(0),
+        })
+        .collect())
+}
+
+fn resolve_member_name(raw_name: &str, longnames: Option<&[u8]>) -> String {
+    if let (Some(offset), Some(longnames)) = (raw_name.strip_prefix('/').and_then(|s| s.parse::<usize>().ok()), longnames) {
+        if let Some(bytes) = longnames.get(offset..) {
+            // MSVC terminates long names with a null, GNU with "/\n"
+            let end = bytes.iter().position(|&b| b == 0 || b == b'\n').unwrap_or(bytes.len());
+            let name = String::from_utf8_lossy(&bytes[..end]);
+            return name.strip_suffix('/').unwrap_or(&name).to_string();
+        }
+    }
+    raw_name.strip_suffix('/').unwrap_or(raw_name).to_string()
+}
+
+fn strip_import_prefix(name: &str) -> &str {
+    name.strip_prefix(['?', '@', '_']).unwrap_or(name)
+}
+
+fn split_names(data: &[u8], count: usize) -> Vec<String> {
+    data.split(|&b| b == 0).take(count).map(|s| String::from_utf8_lossy(s).into_owned()).collect()
+}
+
+fn read_u32(data: &[u8], offset: usize, from_bytes: fn([u8; 4]) -> u32) -> Result<u32, PEError> {
+    match data.get(offset..offset + 4) {
+        Some(bytes) => Ok(from_bytes(bytes.try_into().unwrap())),
+        None => Err(PEError::DeserialiseError(String::from("linker member is truncated"))),
+    }
+}
+
+/// Reads the count of a table of `entry_size` byte entries that follows it, failing if the rest of the data is too
+/// short to hold that many, so that an untrusted count is never used to reserve memory.
+fn read_count(data: &[u8], offset: usize, entry_size: usize, from_bytes: fn([u8; 4]) -> u32) -> Result<usize, PEError> {
+    let count = read_u32(data, offset, from_bytes)? as usize;
+    let remaining = data.len() - (offset + 4);
+    if count > remaining / entry_size {
+        return Err(PEError::DeserialiseError(format!(
+            "linker member has {} entries but only {} bytes to hold them",
+            count, remaining
+        )));
+    }
+    Ok(count)
+}
+
+fn read_member_data(fh: &mut File, data_offset: u32, size: u32) -> Result<Vec<u8>, PEError> {
+    match read_vec(fh, data_offset as u64, size as usize) {
+        Ok(data) => Ok(data),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn header_field_str(field: &[u8]) -> String {
+    String::from_utf8_lossy(field).trim_end().to_string()
+}
+
+fn parse_decimal_field(field: &[u8]) -> io::Result<u32> {
+    let text = header_field_str(field);
+    match text.parse::<u32>() {
+        Ok(value) => Ok(value),
+        Err(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("bad numeric field in archive member header: {:?}", text),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::internal::testdata;
+
+    /// Appends a member with the given name and data to an archive.
+    fn push_member(archive: &mut Vec<u8>, name: &str, data: &[u8]) {
+        archive.extend_from_slice(format!("{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n", name, 0, "", "", 644, data.len()).as_bytes());
+        archive.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            archive.push(b'\n');
+        }
+    }
+
+    #[test]
+    fn members_that_are_not_objects_are_other() {
+        let mut bytes = ARCHIVE_SIGNATURE.to_vec();
+        push_member(&mut bytes, "/<ECSYMBOLS>/", &[0; 4]);
+        push_member(&mut bytes, "test-x64.obj/", &testdata::read("test-x64.obj"));
+        push_member(&mut bytes, "lto.bc/", b"BC\xC0\xDE\x35\x14\x00\x00\x05\x00\x00\x00\x62\x0C\x30\x24");
+        push_member(&mut bytes, "lib.rmeta/", b"rust\x00\x00\x00\x09");
+
+        let archive = get_archive_from_file(&mut testdata::open_bytes(&bytes)).unwrap();
+        let names: Vec<&str> = archive.members.iter().map(|member| member.name.as_str()).collect();
+        assert_eq!(names, ["test-x64.obj", "lto.bc", "lib.rmeta"]);
+        let ArchiveMemberContents::Object(object) = &archive.members[0].contents else {
+            panic!("{:?}", archive.members[0].contents);
+        };
+        assert!(matches!(object.coff_header.target_machine, MachineType::Amd64));
+        assert!(matches!(archive.members[1].contents, ArchiveMemberContents::Other));
+        assert!(matches!(archive.members[2].contents, ArchiveMemberContents::Other));
+    }
+}
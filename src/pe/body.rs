@@ -3,6 +3,8 @@ use bitflags::bitflags;
 bitflags! {
     /// PE section characteristic flags given in the section headers.
     #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
     pub struct SectionFlags: u32 {
         const TypeNoPad = 0x00000008;
         const CntCode = 0x00000020;
@@ -43,8 +45,10 @@ bitflags! {
 
 /// A row from the section table.
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct SectionHeader {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_section_name"))]
     pub name: [u8; 8], // TODO string of fixed size?
     pub virtual_size: u32,
     pub virtual_address: u32,
@@ -56,3 +60,54 @@ pub struct SectionHeader {
     pub number_of_line_numbers: u16,
     pub characteristics: SectionFlags,
 }
+
+impl SectionHeader {
+    /// The section name, with trailing null padding removed.
+    pub fn name_str(&self) -> String {
+        let end = self.name.iter().position(|&b| b == 0).unwrap_or(self.name.len());
+        String::from_utf8_lossy(&self.name[..end]).into_owned()
+    }
+
+    /// How many bytes of memory the section takes up once loaded, before rounding up to the section alignment, which is
+    /// its raw size if the virtual size is not given.
+    pub fn mapped_size(&self) -> u32 {
+        if self.virtual_size == 0 {
+            self.size_of_raw_data
+        } else {
+            self.virtual_size
+        }
+    }
+
+    /// Checks whether an RVA falls within the section once it is loaded.
+    pub fn contains_rva(&self, rva: u32) -> bool {
+        let size = self.virtual_size.max(self.size_of_raw_data);
+        rva >= self.virtual_address && (rva as u64) < self.virtual_address as u64 + size as u64
+    }
+}
+
+/// Maps an RVA to a file offset, or `None` if it is not backed by data in the file.
+///
+/// RVAs below the first section map to the headers, which are loaded at the same offsets they have in the file.
+pub fn rva_to_file_offset(section_table: &[SectionHeader], rva: u32) -> Option<u32> {
+    if let Some(section) = section_table.iter().find(|section| section.contains_rva(rva)) {
+        let delta = rva - section.virtual_address;
+        if delta >= section.size_of_raw_data {
+            // in the zero-filled tail of the section
+            return None;
+        }
+        return section.pointer_to_raw_data.checked_add(delta);
+    }
+
+    let first_section_rva = section_table.iter().map(|section| section.virtual_address).min().unwrap_or(u32::MAX);
+    if rva < first_section_rva {
+        Some(rva)
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_section_name<S: serde::Serializer>(name: &[u8; 8], serializer: S) -> Result<S::Ok, S::Error> {
+    let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+    serializer.serialize_str(&String::from_utf8_lossy(&name[..end]))
+}
@@ -0,0 +1,129 @@
+use super::body::SectionHeader;
+use super::debug::{get_debug_directories, get_ex_dll_characteristics, ExDllCharacteristics};
+use super::err::PEError;
+use super::headers::{CoffCharacteristics, DataDirectory, DllCharacteristics, MachineType};
+use super::load_config::{get_load_config, GuardFlags};
+use super::traits::PEHeader;
+use std::fmt::Display;
+use std::fs::File;
+
+/// IDs of every mitigation checked, in the order they are reported.
+pub const MITIGATION_IDS: [&str; 8] = ["aslr", "high-entropy-va", "dep", "cfg", "safeseh", "cet", "force-integrity", "authenticode"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum CheckStatus {
+    Pass,
+    Fail,
+    /// The mitigation is missing, but is not expected of most images, such as a signature on an in-house build.
+    Warn,
+    /// The mitigation does not apply to this image, such as SafeSEH on a 64-bit image.
+    NotApplicable,
+}
+
+/// Whether an image has one security mitigation enabled.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MitigationCheck {
+    /// Short ID for selecting the check, such as `aslr`. One of [`MITIGATION_IDS`].
+    pub id: &'static str,
+    /// Display name, such as `ASLR`.
+    pub name: &'static str,
+    pub status: CheckStatus,
+    /// Why the check passed, failed or does not apply.
+    pub detail: String,
+}
+
+impl Display for CheckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CheckStatus::Pass => f.pad("pass"),
+            CheckStatus::Fail => f.pad("FAIL"),
+            CheckStatus::Warn => f.pad("warn"),
+            CheckStatus::NotApplicable => f.pad("n/a"),
+        }
+    }
+}
+
+/// Checks which security mitigations an image was built with.
+///
+/// Authenticode is only checked for the presence of a certificate table; the signature is not verified. A missing
+/// ForceIntegrity flag or certificate table is a [`CheckStatus::Warn`] rather than a failure, as only images that are
+/// signed for release need them.
+pub fn check_mitigations(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Vec<MitigationCheck>, PEError> {
+    let dll_characteristics = match (headers.optional_header_pe32(), headers.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => pe32_header.windows_fields.dll_characteristics,
+        (_, Some(pe32plus_header)) => pe32plus_header.windows_fields.dll_characteristics,
+        _ => return Err(PEError::DeserialiseError(String::from("object files have no security mitigations"))),
+    };
+    let load_config = get_load_config(fh, headers, section_table)?;
+    let debug_directories = get_debug_directories(fh, headers, section_table)?;
+    let ex_dll_characteristics = get_ex_dll_characteristics(fh, &debug_directories)?;
+
+    let has_relocations = !headers.coff_header().characteristics.contains(CoffCharacteristics::RelocsStripped)
+        && headers.data_directory(DataDirectory::BASE_RELOCATION_TABLE).is_some_and(|dir| dir.size > 0);
+    let dynamic_base = dll_characteristics.contains(DllCharacteristics::DynamicBase);
+    let guard_cf = dll_characteristics.contains(DllCharacteristics::GuardCf);
+    let cf_instrumented = load_config.is_some_and(|load_config| load_config.guard_flags.contains(GuardFlags::CfInstrumented));
+
+    let mut ret: Vec<MitigationCheck> = Vec::with_capacity(MITIGATION_IDS.len());
+    ret.push(match (dynamic_base, has_relocations) {
+        (true, true) => check("aslr", "ASLR", CheckStatus::Pass, "DynamicBase set and relocations present"),
+        (true, false) => check("aslr", "ASLR", CheckStatus::Fail, "DynamicBase set but relocations stripped"),
+        (false, _) => check("aslr", "ASLR", CheckStatus::Fail, "DynamicBase not set"),
+    });
+    ret.push(if !headers.is_pe32plus() {
+        check("high-entropy-va", "High entropy VA", CheckStatus::NotApplicable, "only applies to PE32+ images")
+    } else if !dll_characteristics.contains(DllCharacteristics::HighEntropyVa) {
+        check("high-entropy-va", "High entropy VA", CheckStatus::Fail, "HighEntropyVa not set")
+    } else if !dynamic_base {
+        check("high-entropy-va", "High entropy VA", CheckStatus::Fail, "DynamicBase not set")
+    } else {
+        check("high-entropy-va", "High entropy VA", CheckStatus::Pass, "HighEntropyVa set")
+    });
+    ret.push(if dll_characteristics.contains(DllCharacteristics::NxCompat) {
+        check("dep", "DEP", CheckStatus::Pass, "NxCompat set")
+    } else {
+        check("dep", "DEP", CheckStatus::Fail, "NxCompat not set")
+    });
+    ret.push(match (guard_cf, load_config.is_some(), cf_instrumented) {
+        (true, _, true) => check("cfg", "CFG", CheckStatus::Pass, "GuardCf set and load config has CfInstrumented"),
+        (true, true, false) => check("cfg", "CFG", CheckStatus::Fail, "GuardCf set but load config lacks CfInstrumented"),
+        (true, false, _) => check("cfg", "CFG", CheckStatus::Fail, "GuardCf set but there is no load config"),
+        (false, _, _) => check("cfg", "CFG", CheckStatus::Fail, "GuardCf not set"),
+    });
+    ret.push(if !matches!(headers.coff_header().target_machine, MachineType::I386) {
+        check("safeseh", "SafeSEH", CheckStatus::NotApplicable, "only applies to x86 images")
+    } else if dll_characteristics.contains(DllCharacteristics::NoSeh) {
+        check("safeseh", "SafeSEH", CheckStatus::Pass, "NoSeh set, so no handlers can be registered")
+    } else if load_config.is_some_and(|load_config| load_config.se_handler_table != 0) {
+        check("safeseh", "SafeSEH", CheckStatus::Pass, "load config has a safe exception handler table")
+    } else {
+        check("safeseh", "SafeSEH", CheckStatus::Fail, "no safe exception handler table")
+    });
+    ret.push(match ex_dll_characteristics {
+        Some(flags) if flags.contains(ExDllCharacteristics::CetCompat) => check("cet", "CET shadow stack", CheckStatus::Pass, "CetCompat set"),
+        Some(_) => check("cet", "CET shadow stack", CheckStatus::Fail, "CetCompat not set"),
+        None => check("cet", "CET shadow stack", CheckStatus::Fail, "no extended DLL characteristics debug entry"),
+    });
+    ret.push(if dll_characteristics.contains(DllCharacteristics::ForceIntegrity) {
+        check("force-integrity", "Force integrity", CheckStatus::Pass, "ForceIntegrity set")
+    } else {
+        check("force-integrity", "Force integrity", CheckStatus::Warn, "ForceIntegrity not set")
+    });
+    ret.push(match headers.data_directory(DataDirectory::CERTIFICATE_TABLE).filter(|dir| dir.size > 0) {
+        Some(_) => check("authenticode", "Authenticode", CheckStatus::Pass, "certificate table present"),
+        None => check("authenticode", "Authenticode", CheckStatus::Warn, "no certificate table"),
+    });
+    Ok(ret)
+}
+
+fn check(id: &'static str, name: &'static str, status: CheckStatus, detail: &str) -> MitigationCheck {
+    MitigationCheck {
+        id,
+        name,
+        status,
+        detail: String::from(detail),
+    }
+}
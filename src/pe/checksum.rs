@@ -0,0 +1,60 @@
+use super::err::PEError;
+
+/// Offset of the checksum field from the start of the optional header, which is the same for PE32 and PE32+.
+const CHECKSUM_OFFSET_IN_OPTIONAL_HEADER: usize = 64;
+
+/// Finds the file offset of the optional header's checksum field in an image.
+pub fn get_checksum_offset(data: &[u8]) -> Result<usize, PEError> {
+    let e_lfanew = match data.get(0x3c..0x40) {
+        Some(bytes) => u32::from_le_bytes(bytes.try_into().unwrap()) as usize,
+        None => return Err(PEError::DeserialiseError(String::from("missing header pointer"))),
+    };
+
+    // skip the PE signature and the COFF header
+    let offset = e_lfanew + 4 + 20 + CHECKSUM_OFFSET_IN_OPTIONAL_HEADER;
+    if offset + 4 > data.len() {
+        return Err(PEError::DeserialiseError(String::from("image too small for optional header")));
+    }
+    Ok(offset)
+}
+
+/// Computes the checksum of an image the same way as `CheckSumMappedFile`.
+///
+/// This is a 16-bit ones' complement style sum over the whole file, skipping the stored checksum, plus the file length.
+pub fn compute_checksum(data: &[u8]) -> Result<u32, PEError> {
+    let checksum_offset = get_checksum_offset(data)?;
+
+    let mut sum: u32 = 0;
+    for (i, chunk) in data.chunks(2).enumerate() {
+        let offset = i * 2;
+        if offset >= checksum_offset && offset < checksum_offset + 4 {
+            continue;
+        }
+
+        // an odd trailing byte is padded with zero
+        let word = match chunk {
+            [lo, hi] => u16::from_le_bytes([*lo, *hi]),
+            [lo] => *lo as u16,
+            _ => unreachable!(),
+        };
+        sum += word as u32;
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum = (sum & 0xffff) + (sum >> 16);
+
+    Ok(sum.wrapping_add(data.len() as u32))
+}
+
+/// Reads the checksum stored in an image's optional header.
+pub fn get_stored_checksum(data: &[u8]) -> Result<u32, PEError> {
+    let checksum_offset = get_checksum_offset(data)?;
+    Ok(u32::from_le_bytes(data[checksum_offset..checksum_offset + 4].try_into().unwrap()))
+}
+
+/// Recomputes an image's checksum and writes it into the optional header, returning the new value.
+pub fn update_checksum(data: &mut [u8]) -> Result<u32, PEError> {
+    let checksum_offset = get_checksum_offset(data)?;
+    let checksum = compute_checksum(data)?;
+    data[checksum_offset..checksum_offset + 4].copy_from_slice(&checksum.to_le_bytes());
+    Ok(checksum)
+}
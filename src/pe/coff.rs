@@ -0,0 +1,367 @@
+use super::body::{SectionFlags, SectionHeader};
+use super::err::PEError;
+use super::headers::{CoffHeader, MachineType};
+use super::internal::agnostic_fio::{check_range, read_exact, read_vec};
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::mem::{size_of, transmute};
+
+/// Defines a relocation type enum for one machine, along with its conversion from the raw `u16` and its display name.
+macro_rules! relocation_types {
+    ($(#[$meta:meta])* $name:ident { $($variant:ident = $value:literal => $display:literal,)* }) => {
+        $(#[$meta])*
+        #[repr(u16)]
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $name {
+            $($variant = $value,)*
+        }
+
+        impl TryFrom<u16> for $name {
+            type Error = u16;
+
+            fn try_from(value: u16) -> Result<Self, Self::Error> {
+                match value {
+                    $($value => Ok(Self::$variant),)*
+                    _ => Err(value),
+                }
+            }
+        }
+
+        impl Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match *self {
+                    $(Self::$variant => write!(f, $display),)*
+                }
+            }
+        }
+    };
+}
+
+relocation_types! {
+    /// Relocation types for x64 objects.
+    RelocationTypeAmd64 {
+        Absolute = 0x0000 => "IMAGE_REL_AMD64_ABSOLUTE",
+        Addr64 = 0x0001 => "IMAGE_REL_AMD64_ADDR64",
+        Addr32 = 0x0002 => "IMAGE_REL_AMD64_ADDR32",
+        Addr32Nb = 0x0003 => "IMAGE_REL_AMD64_ADDR32NB",
+        Rel32 = 0x0004 => "IMAGE_REL_AMD64_REL32",
+        Rel32_1 = 0x0005 => "IMAGE_REL_AMD64_REL32_1",
+        Rel32_2 = 0x0006 => "IMAGE_REL_AMD64_REL32_2",
+        Rel32_3 = 0x0007 => "IMAGE_REL_AMD64_REL32_3",
+        Rel32_4 = 0x0008 => "IMAGE_REL_AMD64_REL32_4",
+        Rel32_5 = 0x0009 => "IMAGE_REL_AMD64_REL32_5",
+        Section = 0x000a => "IMAGE_REL_AMD64_SECTION",
+        SecRel = 0x000b => "IMAGE_REL_AMD64_SECREL",
+        SecRel7 = 0x000c => "IMAGE_REL_AMD64_SECREL7",
+        Token = 0x000d => "IMAGE_REL_AMD64_TOKEN",
+        SRel32 = 0x000e => "IMAGE_REL_AMD64_SREL32",
+        Pair = 0x000f => "IMAGE_REL_AMD64_PAIR",
+        SSpan32 = 0x0010 => "IMAGE_REL_AMD64_SSPAN32",
+    }
+}
+
+relocation_types! {
+    /// Relocation types for Intel 386 objects.
+    RelocationTypeI386 {
+        Absolute = 0x0000 => "IMAGE_REL_I386_ABSOLUTE",
+        Dir16 = 0x0001 => "IMAGE_REL_I386_DIR16",
+        Rel16 = 0x0002 => "IMAGE_REL_I386_REL16",
+        Dir32 = 0x0006 => "IMAGE_REL_I386_DIR32",
+        Dir32Nb = 0x0007 => "IMAGE_REL_I386_DIR32NB",
+        Seg12 = 0x0009 => "IMAGE_REL_I386_SEG12",
+        Section = 0x000a => "IMAGE_REL_I386_SECTION",
+        SecRel = 0x000b => "IMAGE_REL_I386_SECREL",
+        Token = 0x000c => "IMAGE_REL_I386_TOKEN",
+        SecRel7 = 0x000d => "IMAGE_REL_I386_SECREL7",
+        Rel32 = 0x0014 => "IMAGE_REL_I386_REL32",
+    }
+}
+
+relocation_types! {
+    /// Relocation types for ARM64 objects.
+    RelocationTypeArm64 {
+        Absolute = 0x0000 => "IMAGE_REL_ARM64_ABSOLUTE",
+        Addr32 = 0x0001 => "IMAGE_REL_ARM64_ADDR32",
+        Addr32Nb = 0x0002 => "IMAGE_REL_ARM64_ADDR32NB",
+        Branch26 = 0x0003 => "IMAGE_REL_ARM64_BRANCH26",
+        PageBaseRel21 = 0x0004 => "IMAGE_REL_ARM64_PAGEBASE_REL21",
+        Rel21 = 0x0005 => "IMAGE_REL_ARM64_REL21",
+        PageOffset12A = 0x0006 => "IMAGE_REL_ARM64_PAGEOFFSET_12A",
+        PageOffset12L = 0x0007 => "IMAGE_REL_ARM64_PAGEOFFSET_12L",
+        SecRel = 0x0008 => "IMAGE_REL_ARM64_SECREL",
+        SecRelLow12A = 0x0009 => "IMAGE_REL_ARM64_SECREL_LOW12A",
+        SecRelHigh12A = 0x000a => "IMAGE_REL_ARM64_SECREL_HIGH12A",
+        SecRelLow12L = 0x000b => "IMAGE_REL_ARM64_SECREL_LOW12L",
+        Token = 0x000c => "IMAGE_REL_ARM64_TOKEN",
+        Section = 0x000d => "IMAGE_REL_ARM64_SECTION",
+        Addr64 = 0x000e => "IMAGE_REL_ARM64_ADDR64",
+        Branch19 = 0x000f => "IMAGE_REL_ARM64_BRANCH19",
+        Branch14 = 0x0010 => "IMAGE_REL_ARM64_BRANCH14",
+        Rel32 = 0x0011 => "IMAGE_REL_ARM64_REL32",
+    }
+}
+
+relocation_types! {
+    /// Relocation types for ARM and Thumb objects.
+    RelocationTypeArm {
+        Absolute = 0x0000 => "IMAGE_REL_ARM_ABSOLUTE",
+        Addr32 = 0x0001 => "IMAGE_REL_ARM_ADDR32",
+        Addr32Nb = 0x0002 => "IMAGE_REL_ARM_ADDR32NB",
+        Branch24 = 0x0003 => "IMAGE_REL_ARM_BRANCH24",
+        Branch11 = 0x0004 => "IMAGE_REL_ARM_BRANCH11",
+        Rel32 = 0x000a => "IMAGE_REL_ARM_REL32",
+        Section = 0x000e => "IMAGE_REL_ARM_SECTION",
+        SecRel = 0x000f => "IMAGE_REL_ARM_SECREL",
+        Mov32 = 0x0010 => "IMAGE_REL_ARM_MOV32",
+        ThumbMov32 = 0x0011 => "IMAGE_REL_THUMB_MOV32",
+        ThumbBranch20 = 0x0012 => "IMAGE_REL_THUMB_BRANCH20",
+        ThumbBranch24 = 0x0014 => "IMAGE_REL_THUMB_BRANCH24",
+        ThumbBlx23 = 0x0015 => "IMAGE_REL_THUMB_BLX23",
+        Pair = 0x0016 => "IMAGE_REL_ARM_PAIR",
+    }
+}
+
+/// A relocation type, interpreted according to the machine type in the COFF header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelocationType {
+    Amd64(RelocationTypeAmd64),
+    I386(RelocationTypeI386),
+    Arm64(RelocationTypeArm64),
+    Arm(RelocationTypeArm),
+    /// A type not known for the machine, or a machine whose relocation types are not decoded.
+    Other(u16),
+}
+
+impl RelocationType {
+    pub fn new(machine: MachineType, value: u16) -> Self {
+        let typed = match machine {
+            MachineType::Amd64 => RelocationTypeAmd64::try_from(value).map(Self::Amd64),
+            MachineType::I386 => RelocationTypeI386::try_from(value).map(Self::I386),
+            MachineType::Arm64 => RelocationTypeArm64::try_from(value).map(Self::Arm64),
+            MachineType::Arm | MachineType::ArmNT | MachineType::Thumb => RelocationTypeArm::try_from(value).map(Self::Arm),
+            _ => Err(value),
+        };
+        typed.unwrap_or(Self::Other(value))
+    }
+}
+
+impl Display for RelocationType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::Amd64(reloc_type) => write!(f, "{}", reloc_type),
+            Self::I386(reloc_type) => write!(f, "{}", reloc_type),
+            Self::Arm64(reloc_type) => write!(f, "{}", reloc_type),
+            Self::Arm(reloc_type) => write!(f, "{}", reloc_type),
+            Self::Other(value) => write!(f, "Unknown (0x{:04x})", value),
+        }
+    }
+}
+
+/// A raw relocation record (`IMAGE_RELOCATION`) from a section's relocation table.
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct CoffRelocation {
+    /// Address of the item to be relocated, relative to the start of the section.
+    pub virtual_address: u32,
+    /// Index of the target symbol in the symbol table.
+    pub symbol_table_index: u32,
+    /// Machine-specific relocation type.
+    pub relocation_type: u16,
+}
+
+/// A relocation with its type decoded and its target symbol resolved.
+#[derive(Debug, Clone)]
+pub struct Relocation {
+    pub virtual_address: u32,
+    pub symbol_table_index: u32,
+    pub relocation_type: RelocationType,
+    /// Name of the target symbol, if a symbol table was available and the index was valid.
+    pub symbol_name: Option<String>,
+}
+
+/// A raw COFF line number record (`IMAGE_LINENUMBER`).
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct CoffLineNumber {
+    /// Symbol table index of the function if `line_number` is zero, otherwise the address of the line's code.
+    pub symbol_table_index_or_virtual_address: u32,
+    /// Line number, relative to the start of the function, or zero for the record that starts a function.
+    pub line_number: u16,
+}
+
+/// A single line of a function.
+#[derive(Debug, Clone, Copy)]
+pub struct LineNumber {
+    pub virtual_address: u32,
+    pub line_number: u16,
+}
+
+/// The line number records belonging to one function.
+#[derive(Debug, Clone)]
+pub struct FunctionLineNumbers {
+    /// Symbol table index of the function.
+    pub symbol_table_index: u32,
+    /// Name of the function, if a symbol table was available and the index was valid.
+    pub function_name: Option<String>,
+    pub lines: Vec<LineNumber>,
+}
+
+/// A raw COFF symbol table record (`IMAGE_SYMBOL`).
+#[repr(C, packed)]
+#[derive(Debug, Clone, Copy)]
+pub struct CoffSymbol {
+    /// Short name, or four zero bytes followed by an offset into the string table.
+    pub name: [u8; 8],
+    pub value: u32,
+    /// One-based section index, or zero/negative for the special values (undefined, absolute, debug).
+    pub section_number: i16,
+    pub symbol_type: u16,
+    pub storage_class: u8,
+    pub number_of_aux_symbols: u8,
+}
+
+/// A COFF symbol table along with its string table.
+///
+/// Auxiliary records are kept in place so that indices from relocations and line numbers can be used directly.
+#[derive(Debug)]
+pub struct SymbolTable {
+    pub symbols: Vec<CoffSymbol>,
+    pub string_table: Vec<u8>,
+}
+
+impl SymbolTable {
+    /// Resolves the name of the symbol at the given index, following string table references for long names.
+    pub fn name(&self, index: u32) -> Option<String> {
+        let symbol = self.symbols.get(index as usize)?;
+        let name = &symbol.name;
+        if name[..4] == [0, 0, 0, 0] {
+            let offset = u32::from_le_bytes(name[4..].try_into().unwrap()) as usize;
+            let bytes = self.string_table.get(offset..)?;
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+            Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+        } else {
+            let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            Some(String::from_utf8_lossy(&name[..end]).into_owned())
+        }
+    }
+}
+
+/// Reads the COFF symbol table and the string table that follows it, or `None` if the file has no symbol table.
+pub fn get_symbol_table(fh: &mut File, coff_header: &CoffHeader) -> Result<Option<SymbolTable>, PEError> {
+    if coff_header.pointer_to_symbol_table == 0 || coff_header.number_of_symbols == 0 {
+        return Ok(None);
+    }
+
+    match do_get_symbol_table(fh, coff_header) {
+        Ok(symbol_table) => Ok(Some(symbol_table)),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads the relocations for a section, resolving target symbol names when a symbol table is given.
+pub fn get_relocations(fh: &mut File, coff_header: &CoffHeader, section: &SectionHeader, symbols: Option<&SymbolTable>) -> Result<Vec<Relocation>, PEError> {
+    match get_raw_relocations(fh, section) {
+        Ok(raw_relocations) => Ok(raw_relocations
+            .iter()
+            .map(|reloc| Relocation {
+                virtual_address: reloc.virtual_address,
+                symbol_table_index: reloc.symbol_table_index,
+                relocation_type: RelocationType::new(coff_header.target_machine, reloc.relocation_type),
+                symbol_name: symbols.and_then(|table| table.name(reloc.symbol_table_index)),
+            })
+            .collect()),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads the line numbers for a section, grouped by the function they belong to.
+pub fn get_line_numbers(fh: &mut File, section: &SectionHeader, symbols: Option<&SymbolTable>) -> Result<Vec<FunctionLineNumbers>, PEError> {
+    match get_raw_line_numbers(fh, section) {
+        Ok(raw_line_numbers) => Ok(group_line_numbers(&raw_line_numbers, symbols)),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn do_get_symbol_table(fh: &mut File, coff_header: &CoffHeader) -> io::Result<SymbolTable> {
+    let num_symbols = coff_header.number_of_symbols;
+    check_range(
+        fh,
+        coff_header.pointer_to_symbol_table as u64,
+        num_symbols as u64 * size_of::<CoffSymbol>() as u64,
+    )?;
+    let mut symbols: Vec<CoffSymbol> = Vec::with_capacity(num_symbols as usize);
+    let mut addr = coff_header.pointer_to_symbol_table as u64;
+    for _ in 0..num_symbols {
+        let symbol: CoffSymbol = unsafe { transmute(read_exact::<{ size_of::<CoffSymbol>() }>(fh, addr)?) };
+        symbols.push(symbol);
+        addr += size_of::<CoffSymbol>() as u64;
+    }
+
+    // the string table immediately follows the symbol table, and its size includes the size field itself
+    let string_table_size = u32::from_le_bytes(read_exact(fh, addr)?) as usize;
+    let string_table = if string_table_size > 4 {
+        let mut table = vec![0_u8; 4];
+        table.extend(read_vec(fh, addr + 4, string_table_size - 4)?);
+        table
+    } else {
+        Vec::new()
+    };
+
+    Ok(SymbolTable { symbols, string_table })
+}
+
+fn get_raw_relocations(fh: &mut File, section: &SectionHeader) -> io::Result<Vec<CoffRelocation>> {
+    let mut addr = section.pointer_to_relocations as u64;
+    let mut num_relocations = section.number_of_relocations as u32;
+
+    // with more than 0xffff relocations, the real count is in the first relocation's address field
+    if section.characteristics.contains(SectionFlags::LnkNrelocOvfl) && num_relocations == 0xffff {
+        let first: CoffRelocation = unsafe { transmute(read_exact::<{ size_of::<CoffRelocation>() }>(fh, addr)?) };
+        num_relocations = first.virtual_address.saturating_sub(1);
+        addr += size_of::<CoffRelocation>() as u64;
+    }
+
+    check_range(fh, addr, num_relocations as u64 * size_of::<CoffRelocation>() as u64)?;
+    let mut ret: Vec<CoffRelocation> = Vec::with_capacity(num_relocations as usize);
+    for _ in 0..num_relocations {
+        let reloc: CoffRelocation = unsafe { transmute(read_exact::<{ size_of::<CoffRelocation>() }>(fh, addr)?) };
+        ret.push(reloc);
+        addr += size_of::<CoffRelocation>() as u64;
+    }
+    Ok(ret)
+}
+
+fn get_raw_line_numbers(fh: &mut File, section: &SectionHeader) -> io::Result<Vec<CoffLineNumber>> {
+    let num_line_numbers = section.number_of_line_numbers;
+    let mut addr = section.pointer_to_line_numbers as u64;
+
+    let mut ret: Vec<CoffLineNumber> = Vec::with_capacity(num_line_numbers as usize);
+    for _ in 0..num_line_numbers {
+        let line_number: CoffLineNumber = unsafe { transmute(read_exact::<{ size_of::<CoffLineNumber>() }>(fh, addr)?) };
+        ret.push(line_number);
+        addr += size_of::<CoffLineNumber>() as u64;
+    }
+    Ok(ret)
+}
+
+fn group_line_numbers(raw_line_numbers: &[CoffLineNumber], symbols: Option<&SymbolTable>) -> Vec<FunctionLineNumbers> {
+    let mut ret: Vec<FunctionLineNumbers> = Vec::new();
+    for record in raw_line_numbers {
+        let first_field = record.symbol_table_index_or_virtual_address;
+        if record.line_number == 0 {
+            ret.push(FunctionLineNumbers {
+                symbol_table_index: first_field,
+                function_name: symbols.and_then(|table| table.name(first_field)),
+                lines: Vec::new(),
+            });
+        } else if let Some(function) = ret.last_mut() {
+            function.lines.push(LineNumber {
+                virtual_address: first_field,
+                line_number: record.line_number,
+            });
+        }
+        // line records before any function record have nothing to belong to, so they are dropped
+    }
+    ret
+}
@@ -0,0 +1,147 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::headers::DataDirectory;
+use super::internal::agnostic_fio::{read_exact, read_vec};
+use super::internal::rva::rva_to_addr;
+use super::traits::PEHeader;
+use bitflags::bitflags;
+use std::fs::File;
+use std::io;
+use std::mem::{size_of, transmute};
+
+/// Most debug directory entries that will be read, to bound malformed tables.
+const MAX_ENTRIES: usize = 0x100;
+
+/// A debug directory entry (`IMAGE_DEBUG_DIRECTORY`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DebugDirectory {
+    pub characteristics: u32,
+    pub time_date_stamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// One of the `IMAGE_DEBUG_TYPE_*` values, such as [`DebugDirectory::CODEVIEW`].
+    pub debug_type: u32,
+    pub size_of_data: u32,
+    /// RVA of the debug data, or 0 if it is not mapped.
+    pub address_of_raw_data: u32,
+    pub pointer_to_raw_data: u32,
+}
+
+bitflags! {
+    /// Extended DLL characteristic flags, given by an [`DebugDirectory::EX_DLLCHARACTERISTICS`] debug entry.
+    #[derive(Debug, Clone, Copy)]
+    pub struct ExDllCharacteristics: u32 {
+        const CetCompat = 0x0001;
+        const CetCompatStrictMode = 0x0002;
+        const CetSetContextIpValidationRelaxedMode = 0x0004;
+        const CetDynamicApisAllowInProc = 0x0008;
+        const ForwardCfiCompat = 0x0040;
+        const HotpatchCompatible = 0x0080;
+    }
+}
+
+impl DebugDirectory {
+    /// Offset of `TimeDateStamp` in an entry.
+    pub const TIME_DATE_STAMP_OFFSET: usize = 4;
+    /// Offset of `Type` in an entry.
+    pub const TYPE_OFFSET: usize = 12;
+    /// Offset of `SizeOfData` in an entry.
+    pub const SIZE_OF_DATA_OFFSET: usize = 16;
+    /// Offset of `PointerToRawData` in an entry.
+    pub const POINTER_TO_RAW_DATA_OFFSET: usize = 24;
+
+    pub const COFF: u32 = 1;
+    pub const CODEVIEW: u32 = 2;
+    pub const FPO: u32 = 3;
+    pub const MISC: u32 = 4;
+    pub const EXCEPTION: u32 = 5;
+    pub const FIXUP: u32 = 6;
+    pub const BORLAND: u32 = 9;
+    pub const CLSID: u32 = 11;
+    pub const VC_FEATURE: u32 = 12;
+    pub const POGO: u32 = 13;
+    pub const ILTCG: u32 = 14;
+    pub const MPX: u32 = 15;
+    pub const REPRO: u32 = 16;
+    pub const EMBEDDED_PORTABLE_PDB: u32 = 17;
+    pub const PDB_CHECKSUM: u32 = 19;
+    pub const EX_DLLCHARACTERISTICS: u32 = 20;
+
+    /// The name of the entry's debug type, such as `CodeView`, or `None` if it is not recognised.
+    pub fn type_name(&self) -> Option<&'static str> {
+        let name = match self.debug_type {
+            Self::COFF => "COFF",
+            Self::CODEVIEW => "CodeView",
+            Self::FPO => "FPO",
+            Self::MISC => "Misc",
+            Self::EXCEPTION => "Exception",
+            Self::FIXUP => "Fixup",
+            Self::BORLAND => "Borland",
+            Self::CLSID => "CLSID",
+            Self::VC_FEATURE => "VC Feature",
+            Self::POGO => "POGO",
+            Self::ILTCG => "ILTCG",
+            Self::MPX => "MPX",
+            Self::REPRO => "Repro",
+            Self::EMBEDDED_PORTABLE_PDB => "Embedded Portable PDB",
+            Self::PDB_CHECKSUM => "PDB Checksum",
+            Self::EX_DLLCHARACTERISTICS => "Extended DLL Characteristics",
+            _ => return None,
+        };
+        Some(name)
+    }
+}
+
+/// Reads the debug directory entries, or an empty list if the image has no debug directory.
+pub fn get_debug_directories(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Vec<DebugDirectory>, PEError> {
+    let debug_dir = match headers.data_directory(DataDirectory::DEBUG) {
+        Some(dir) => dir,
+        None => return Ok(Vec::new()),
+    };
+
+    match read_debug_directories(fh, debug_dir, section_table) {
+        Ok(entries) => Ok(entries),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads the data of a debug directory entry, which is always addressed by file offset.
+pub fn get_debug_data(fh: &mut File, entry: &DebugDirectory) -> Result<Vec<u8>, PEError> {
+    match read_vec(fh, entry.pointer_to_raw_data as u64, entry.size_of_data as usize) {
+        Ok(data) => Ok(data),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads the extended DLL characteristics, or `None` if the image has no such debug entry.
+pub fn get_ex_dll_characteristics(fh: &mut File, entries: &[DebugDirectory]) -> Result<Option<ExDllCharacteristics>, PEError> {
+    let entry = match entries.iter().find(|entry| entry.debug_type == DebugDirectory::EX_DLLCHARACTERISTICS) {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    if entry.size_of_data < 4 {
+        return Err(PEError::DeserialiseError(String::from("extended DLL characteristics entry is too short")));
+    }
+
+    match read_exact::<4>(fh, entry.pointer_to_raw_data as u64) {
+        Ok(bytes) => Ok(Some(ExDllCharacteristics::from_bits_retain(u32::from_le_bytes(bytes)))),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn read_debug_directories(fh: &mut File, debug_dir: &DataDirectory, section_table: &[SectionHeader]) -> io::Result<Vec<DebugDirectory>> {
+    let base_addr = rva_to_addr(section_table, debug_dir.virtual_address)?;
+    let num_entries = debug_dir.size as usize / size_of::<DebugDirectory>();
+    if num_entries > MAX_ENTRIES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "too many debug directory entries"));
+    }
+
+    let mut ret: Vec<DebugDirectory> = Vec::with_capacity(num_entries);
+    for i in 0..num_entries {
+        let addr = base_addr + (i * size_of::<DebugDirectory>()) as u64;
+        let entry: DebugDirectory = unsafe { transmute(read_exact::<{ size_of::<DebugDirectory>() }>(fh, addr)?) };
+        ret.push(entry);
+    }
+    Ok(ret)
+}
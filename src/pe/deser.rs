@@ -1,12 +1,21 @@
 use super::body::SectionHeader;
 use super::err::PEError;
-use super::headers::{CoffHeader, DataDirectory, HeadersPe32, HeadersPe32Plus, OptionalHeaderPe32, OptionalHeaderPe32Plus, PEType};
-use super::internal::agnostic_fio::read_exact;
+use super::file::{Gap, OptionalHeader, PeFile, Section, PE_SIGNATURE_LEN};
+use super::headers::{
+    CoffHeader, DataDirectory, DosHeader, HeadersPe32, HeadersPe32Plus, MachineType, OptionalHeaderPe32, OptionalHeaderPe32Plus, PEType, WindowsSubsystem,
+};
+use super::internal::agnostic_fio::{read_exact, read_vec};
 use super::traits::PEHeader;
 use std::fs::File;
 use std::io;
 use std::mem::{size_of, transmute};
 
+/// Most bytes that a section's data will be zero-filled by past its raw data.
+const MAX_ZERO_FILL: usize = 0x4000000;
+/// Most data directories that will be read. The loader ignores any past the sixteen that are defined, so a larger
+/// `number_of_rva_and_sizes` is only reported by the lints.
+const MAX_DATA_DIRECTORIES: u32 = 16;
+
 pub fn get_headers_from_file(fh: &mut File) -> Result<Box<dyn PEHeader>, PEError> {
     match do_get_headers_from_file(fh) {
         Ok(header) => Ok(header),
@@ -14,6 +23,11 @@ pub fn get_headers_from_file(fh: &mut File) -> Result<Box<dyn PEHeader>, PEError
     }
 }
 
+/// Checks whether a file starts with a DOS header whose header pointer leads to a PE signature, as an image does.
+pub fn is_image(fh: &mut File) -> bool {
+    matches!(get_coff_header_address(fh), Ok(addr) if addr != 0)
+}
+
 pub fn get_section_table(fh: &mut File, headers: &(impl PEHeader + ?Sized)) -> Result<Vec<SectionHeader>, PEError> {
     match get_section_headers(fh, headers.coff_header()) {
         Ok(section_table) => Ok(section_table),
@@ -21,13 +35,68 @@ pub fn get_section_table(fh: &mut File, headers: &(impl PEHeader + ?Sized)) -> R
     }
 }
 
+/// Reads a section's data, as it would be laid out in memory.
+///
+/// At most `size_of_raw_data` bytes are read from the file, and the data is zero-filled up to `virtual_size`, by at most
+/// 64 MiB.
+pub fn get_section_data(fh: &mut File, section: &SectionHeader) -> Result<Vec<u8>, PEError> {
+    match read_section_data(fh, section) {
+        Ok(data) => Ok(data),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads a section's raw data as stored in the file: `size_of_raw_data` bytes, or fewer if the file is truncated.
+///
+/// This is what section hashes and entropy are computed over, as the zero-filled tail of the in-memory layout is not
+/// in the file.
+pub fn get_raw_section_data(fh: &mut File, section: &SectionHeader) -> Result<Vec<u8>, PEError> {
+    match read_raw_section_data(fh, section) {
+        Ok(data) => Ok(data),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+pub fn get_dos_header(fh: &mut File) -> Result<DosHeader, PEError> {
+    match read_dos_header(fh) {
+        Ok(header) => Ok(header),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads a bare COFF header at the given offset, such as that of an object file embedded in an archive.
+pub fn get_coff_header_at(fh: &mut File, coff_addr: u32) -> Result<CoffHeader, PEError> {
+    match read_coff_header(fh, coff_addr) {
+        Ok(header) => Ok(header),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads the section table following a COFF header at the given offset.
+///
+/// Section data and relocation pointers in the returned headers are relative to the start of the COFF header.
+pub fn get_section_table_at(fh: &mut File, coff_addr: u32, coff_header: &CoffHeader) -> Result<Vec<SectionHeader>, PEError> {
+    match read_section_headers(fh, coff_addr, coff_header) {
+        Ok(section_table) => Ok(section_table),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads a whole image or object file into a `PeFile`, which `ser::write_pe_file` writes back out byte for byte.
+pub fn get_pe_file(fh: &mut File) -> Result<PeFile, PEError> {
+    match read_pe_file(fh) {
+        Ok(pe_file) => Ok(pe_file),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
 fn do_get_headers_from_file(fh: &mut File) -> io::Result<Box<dyn PEHeader>> {
     let coff_header_addr = get_coff_header_address(fh)?;
     let coff_header = get_coff_header(fh)?;
 
     if coff_header.size_of_optional_header == 0 {
         // no optional header, just return the COFF header
-        return Ok(Box::new(coff_header));
+        Ok(Box::new(coff_header))
     } else {
         // get optional headers, then return the full set of headers
         let magic_option = get_optional_headers_magic(fh, coff_header_addr, &coff_header)?;
@@ -36,21 +105,119 @@ fn do_get_headers_from_file(fh: &mut File) -> io::Result<Box<dyn PEHeader>> {
                 let optional_headers = get_optional_headers_pe32(fh, coff_header_addr, &coff_header)?;
                 let data_directories = get_data_directories_pe32(fh, coff_header_addr, &optional_headers)?;
                 let full_headers = HeadersPe32::new(coff_header, optional_headers, data_directories);
-                return Ok(Box::new(full_headers));
+                Ok(Box::new(full_headers))
             }
             Some(PEType::Pe32Plus) => {
                 let optional_headers = get_optional_headers_pe32plus(fh, coff_header_addr, &coff_header)?;
                 let data_directories = get_data_directories_pe32plus(fh, coff_header_addr, &optional_headers)?;
                 let full_headers = HeadersPe32Plus::new(coff_header, optional_headers, data_directories);
-                return Ok(Box::new(full_headers));
+                Ok(Box::new(full_headers))
             }
-            None => panic!("invalid/missing magic number for optional header!!"),
+            None => Err(io::Error::new(io::ErrorKind::InvalidData, "missing optional header")),
+        }
+    }
+}
+
+fn read_pe_file(fh: &mut File) -> io::Result<PeFile> {
+    let file_len = fh.metadata()?.len();
+    let coff_addr = get_coff_header_address(fh)?;
+    let (dos_header, dos_stub) = if coff_addr == 0 {
+        (None, Vec::new())
+    } else {
+        let dos_header = read_dos_header(fh)?;
+        let stub_len = (dos_header.address_of_new_header as usize).saturating_sub(size_of::<DosHeader>());
+        let dos_stub = read_vec(fh, size_of::<DosHeader>() as u64, stub_len)?;
+        (Some(dos_header), dos_stub)
+    };
+    let coff_header = read_coff_header(fh, coff_addr)?;
+    let (optional_header, data_directories) = match get_optional_headers_magic(fh, coff_addr, &coff_header)? {
+        Some(PEType::Pe32) => {
+            let optional_headers = get_optional_headers_pe32(fh, coff_addr, &coff_header)?;
+            let data_directories = get_data_directories_pe32(fh, coff_addr, &optional_headers)?;
+            (Some(OptionalHeader::Pe32(optional_headers)), data_directories)
+        }
+        Some(PEType::Pe32Plus) => {
+            let optional_headers = get_optional_headers_pe32plus(fh, coff_addr, &coff_header)?;
+            let data_directories = get_data_directories_pe32plus(fh, coff_addr, &optional_headers)?;
+            (Some(OptionalHeader::Pe32Plus(optional_headers)), data_directories)
         }
+        None => (None, Vec::new()),
+    };
+
+    let mut sections: Vec<Section> = Vec::new();
+    for header in read_section_headers(fh, coff_addr, &coff_header)? {
+        // truncated files are read up to EOF
+        let raw_addr = header.pointer_to_raw_data as u64;
+        let data = if raw_addr == 0 || raw_addr >= file_len {
+            Vec::new()
+        } else {
+            read_vec(fh, raw_addr, (header.size_of_raw_data as u64).min(file_len - raw_addr) as usize)?
+        };
+        sections.push(Section { header, data });
+    }
+
+    let mut pe_file = PeFile {
+        dos_header,
+        dos_stub,
+        coff_header,
+        optional_header,
+        data_directories,
+        sections,
+        gaps: Vec::new(),
+        overlay: Vec::new(),
+    };
+    let overlay_offset = pe_file.overlay_offset().min(file_len);
+    pe_file.overlay = read_vec(fh, overlay_offset, (file_len - overlay_offset) as usize)?;
+
+    // keep whatever the parts above do not cover, so that nothing is lost on a round trip
+    let mut covered: Vec<(u64, u64)> = vec![
+        (
+            0,
+            pe_file
+                .dos_header
+                .as_ref()
+                .map_or(0, |_| (size_of::<DosHeader>() + pe_file.dos_stub.len()) as u64),
+        ),
+        (
+            coff_addr as u64 - pe_file.dos_header.as_ref().map_or(0, |_| PE_SIGNATURE_LEN),
+            pe_file.optional_header_offset(),
+        ),
+        (
+            pe_file.section_table_offset(),
+            pe_file.section_table_offset() + (pe_file.sections.len() * size_of::<SectionHeader>()) as u64,
+        ),
+    ];
+    if let Some(optional_header) = &pe_file.optional_header {
+        let dirs_end = optional_header.size() + (pe_file.data_directories.len() * size_of::<DataDirectory>()) as u64;
+        covered.push((pe_file.optional_header_offset(), pe_file.optional_header_offset() + dirs_end));
     }
+    for section in pe_file.sections.iter().filter(|section| section.header.pointer_to_raw_data != 0) {
+        let start = section.header.pointer_to_raw_data as u64;
+        covered.push((start, start + section.data.len() as u64));
+    }
+    covered.sort();
+    let mut next = 0;
+    for (start, end) in covered {
+        let start = start.min(overlay_offset);
+        if start > next {
+            let data = read_vec(fh, next, (start - next) as usize)?;
+            pe_file.gaps.push(Gap { offset: next, data });
+        }
+        next = next.max(end);
+    }
+    if overlay_offset > next {
+        let data = read_vec(fh, next, (overlay_offset - next) as usize)?;
+        pe_file.gaps.push(Gap { offset: next, data });
+    }
+    Ok(pe_file)
 }
 
 fn get_section_headers(fh: &mut File, coff_header: &CoffHeader) -> io::Result<Vec<SectionHeader>> {
     let coff_addr = get_coff_header_address(fh)?;
+    read_section_headers(fh, coff_addr, coff_header)
+}
+
+fn read_section_headers(fh: &mut File, coff_addr: u32, coff_header: &CoffHeader) -> io::Result<Vec<SectionHeader>> {
     let section_base_addr = coff_addr + 20 + (coff_header.size_of_optional_header as u32);
     let num_sections = coff_header.number_of_sections;
 
@@ -64,6 +231,30 @@ fn get_section_headers(fh: &mut File, coff_header: &CoffHeader) -> io::Result<Ve
     Ok(ret)
 }
 
+fn read_section_data(fh: &mut File, section: &SectionHeader) -> io::Result<Vec<u8>> {
+    let mut data = read_raw_section_data(fh, section)?;
+
+    // the virtual size is untrusted, so bound how much will be zero-filled
+    let virtual_len = (section.virtual_size as usize).min(data.len() + MAX_ZERO_FILL);
+    if virtual_len > data.len() {
+        data.resize(virtual_len, 0);
+    }
+    Ok(data)
+}
+
+fn read_raw_section_data(fh: &mut File, section: &SectionHeader) -> io::Result<Vec<u8>> {
+    let file_len = fh.metadata()?.len();
+    let raw_addr = section.pointer_to_raw_data as u64;
+
+    // uninitialised sections have no raw data, and truncated files are read up to EOF
+    let raw_len = if raw_addr == 0 || raw_addr >= file_len {
+        0
+    } else {
+        (section.size_of_raw_data as u64).min(file_len - raw_addr) as usize
+    };
+    read_vec(fh, raw_addr, raw_len)
+}
+
 fn get_data_directories_pe32(fh: &mut File, coff_addr: u32, headers: &OptionalHeaderPe32) -> io::Result<Vec<DataDirectory>> {
     let num_directories = headers.windows_fields.number_of_rva_and_sizes;
     let base_addr = coff_addr + 20 + 96; // +20 for coff header, +96 for pe32 optional headers
@@ -77,6 +268,7 @@ fn get_data_directories_pe32plus(fh: &mut File, coff_addr: u32, headers: &Option
 }
 
 fn get_data_directories(fh: &mut File, base_addr: u32, num_directories: u32) -> io::Result<Vec<DataDirectory>> {
+    let num_directories = num_directories.min(MAX_DATA_DIRECTORIES);
     let mut ret: Vec<DataDirectory> = Vec::with_capacity(num_directories as usize);
     let mut addr = base_addr;
     for _ in 0..num_directories {
@@ -88,36 +280,57 @@ fn get_data_directories(fh: &mut File, base_addr: u32, num_directories: u32) ->
 }
 
 fn get_optional_headers_pe32(fh: &mut File, coff_addr: u32, coff_header: &CoffHeader) -> io::Result<OptionalHeaderPe32> {
-    let optional_addr = get_optional_headers_addr(coff_addr, &coff_header).unwrap();
-    let headers = unsafe { transmute(read_exact::<{ size_of::<OptionalHeaderPe32>() }>(fh, optional_addr as u64)?) };
+    let optional_addr = get_optional_headers_addr(coff_addr, coff_header).unwrap();
+    let bytes = read_exact::<{ size_of::<OptionalHeaderPe32>() }>(fh, optional_addr as u64)?;
+    check_subsystem(&bytes)?;
+    let headers: OptionalHeaderPe32 = unsafe { transmute(bytes) };
     Ok(headers)
 }
 
 fn get_optional_headers_pe32plus(fh: &mut File, coff_addr: u32, coff_header: &CoffHeader) -> io::Result<OptionalHeaderPe32Plus> {
-    let optional_addr = get_optional_headers_addr(coff_addr, &coff_header).unwrap();
-    let headers = unsafe { transmute(read_exact::<{ size_of::<OptionalHeaderPe32Plus>() }>(fh, optional_addr as u64)?) };
+    let optional_addr = get_optional_headers_addr(coff_addr, coff_header).unwrap();
+    let bytes = read_exact::<{ size_of::<OptionalHeaderPe32Plus>() }>(fh, optional_addr as u64)?;
+    check_subsystem(&bytes)?;
+    let headers: OptionalHeaderPe32Plus = unsafe { transmute(bytes) };
     Ok(headers)
 }
 
 fn get_coff_header(fh: &mut File) -> io::Result<CoffHeader> {
-    let metadata = fh.metadata()?;
-    let len = metadata.len();
+    let header_addr = get_coff_header_address(fh)?;
+    read_coff_header(fh, header_addr)
+}
 
-    // if len < 0x3f, then not PE -- 0x3c is where the dword header pointer is
-    if len < 0x3f {
-        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing header pointer"));
+fn read_coff_header(fh: &mut File, header_addr: u32) -> io::Result<CoffHeader> {
+    let bytes = read_exact::<{ size_of::<CoffHeader>() }>(fh, header_addr as u64)?;
+    // the machine type is an enum, so must be checked before transmuting
+    if let Err(machine) = MachineType::try_from(u16::from_le_bytes([bytes[0], bytes[1]])) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown machine type {:#06x}", machine)));
     }
+    let header: CoffHeader = unsafe { transmute(bytes) };
+    Ok(header)
+}
 
-    let header_addr = get_coff_header_address(fh)?;
-    let header: CoffHeader = unsafe { transmute(read_exact::<{ size_of::<CoffHeader>() }>(fh, header_addr as u64)?) };
+fn read_dos_header(fh: &mut File) -> io::Result<DosHeader> {
+    let header: DosHeader = unsafe { transmute(read_exact::<{ size_of::<DosHeader>() }>(fh, 0)?) };
+    if header.magic != *b"MZ" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing MZ signature"));
+    }
     Ok(header)
 }
 
+/// Checks the subsystem of a PE32 or PE32+ optional header, which is at the same offset in both.
+fn check_subsystem(optional_header: &[u8]) -> io::Result<()> {
+    match WindowsSubsystem::try_from(u16::from_le_bytes([optional_header[68], optional_header[69]])) {
+        Ok(_) => Ok(()),
+        Err(subsystem) => Err(io::Error::new(io::ErrorKind::InvalidData, format!("unknown subsystem {}", subsystem))),
+    }
+}
+
 fn get_optional_headers_addr(coff_addr: u32, coff_header: &CoffHeader) -> Option<u32> {
     if coff_header.size_of_optional_header == 0 {
-        return None;
+        None
     } else {
-        return Some(coff_addr + 20);
+        Some(coff_addr + 20)
     }
 }
 
@@ -125,16 +338,30 @@ fn get_optional_headers_magic(fh: &mut File, coff_addr: u32, coff_header: &CoffH
     let addr_option = get_optional_headers_addr(coff_addr, coff_header);
     if let Some(addr) = addr_option {
         let magic: u16 = u16::from_le_bytes(read_exact(fh, addr as u64)?);
-        return match magic {
+        match magic {
             0x10b => Ok(Some(PEType::Pe32)),
             0x20b => Ok(Some(PEType::Pe32Plus)),
             _ => Err(io::Error::new(io::ErrorKind::InvalidData, "magic number not recognised as PE32 or PE32+")),
-        };
+        }
     } else {
-        return Ok(None);
+        Ok(None)
     }
 }
 
 fn get_coff_header_address(fh: &mut File) -> io::Result<u32> {
-    Ok(u32::from_le_bytes(read_exact(fh, 0x3c)?) + 4)
+    // object files have no DOS stub, and start with the COFF header
+    if read_exact::<2>(fh, 0)? != *b"MZ" {
+        return Ok(0);
+    }
+
+    // if len < 0x40, then not PE -- 0x3c is where the dword header pointer is
+    if fh.metadata()?.len() < 0x40 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing header pointer"));
+    }
+
+    let signature_addr = u32::from_le_bytes(read_exact(fh, 0x3c)?);
+    if read_exact::<4>(fh, signature_addr as u64)? != *b"PE\0\0" {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "missing PE signature"));
+    }
+    Ok(signature_addr + 4)
 }
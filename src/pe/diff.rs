@@ -0,0 +1,389 @@
+use super::body::SectionHeader;
+use super::deser::{get_headers_from_file, get_raw_section_data, get_section_table};
+use super::err::PEError;
+use super::exports::{get_exports, Exports};
+use super::hashes::get_digests;
+use super::headers::{CoffHeader, DataDirectory};
+use super::imports::{get_imports, ImportedDll};
+use super::traits::PEHeader;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+use std::fs::File;
+
+/// The value of a compared field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum FieldValue {
+    Number(u64),
+    Text(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// A single difference between two files.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// What was compared: `coff_header`, `optional_header`, `data_directory`, `section`, `import` or `export`.
+    pub category: &'static str,
+    /// The section, data directory, import or export that changed, or `None` for header fields.
+    pub item: Option<String>,
+    /// The field that changed, or `None` if a whole item was added or removed.
+    pub field: Option<&'static str>,
+    pub old: Option<FieldValue>,
+    pub new: Option<FieldValue>,
+}
+
+/// The parts of a file that are compared.
+struct Snapshot {
+    headers: Box<dyn PEHeader>,
+    sections: Vec<(String, Vec<(&'static str, FieldValue)>)>,
+    imports: BTreeSet<String>,
+    exports: BTreeMap<String, FieldValue>,
+}
+
+impl Display for FieldValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldValue::Number(value) => write!(f, "{:#x}", value),
+            FieldValue::Text(text) => write!(f, "{}", text),
+        }
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let subject = match (&self.item, self.field) {
+            (Some(item), Some(field)) => format!("{} {} {}", self.category, item, field),
+            (Some(item), None) => format!("{} {}", self.category, item),
+            (None, Some(field)) => format!("{}.{}", self.category, field),
+            (None, None) => self.category.to_string(),
+        };
+        match (self.kind, &self.old, &self.new) {
+            (ChangeKind::Added, _, _) => write!(f, "{} added: {}", self.category, self.item.as_deref().unwrap_or_default()),
+            (ChangeKind::Removed, _, _) => write!(f, "{} removed: {}", self.category, self.item.as_deref().unwrap_or_default()),
+            // sizes are easier to read as a delta
+            (ChangeKind::Changed, Some(FieldValue::Number(old)), Some(FieldValue::Number(new))) if subject.contains("size") => {
+                if new > old {
+                    write!(f, "{} grew by {:#x} ({:#x} -> {:#x})", subject, new - old, old, new)
+                } else {
+                    write!(f, "{} shrank by {:#x} ({:#x} -> {:#x})", subject, old - new, old, new)
+                }
+            }
+            (ChangeKind::Changed, Some(old), Some(new)) => write!(f, "{} changed: {} -> {}", subject, old, new),
+            (ChangeKind::Changed, _, _) => write!(f, "{} changed", subject),
+        }
+    }
+}
+
+/// Compares the headers, data directories, sections, imports and exports of two images or object files.
+///
+/// Sections are matched by name, imports by DLL (ignoring case) and function name, and exports by name or ordinal.
+pub fn diff_files(old_fh: &mut File, new_fh: &mut File) -> Result<Vec<Change>, PEError> {
+    let old = get_snapshot(old_fh)?;
+    let new = get_snapshot(new_fh)?;
+
+    let mut changes: Vec<Change> = Vec::new();
+    diff_fields(
+        &mut changes,
+        "coff_header",
+        None,
+        &coff_fields(old.headers.coff_header()),
+        &coff_fields(new.headers.coff_header()),
+    );
+    diff_fields(
+        &mut changes,
+        "optional_header",
+        None,
+        &optional_fields(old.headers.as_ref()),
+        &optional_fields(new.headers.as_ref()),
+    );
+
+    let old_dirs = data_directory_items(old.headers.data_directories());
+    let new_dirs = data_directory_items(new.headers.data_directories());
+    diff_items(&mut changes, "data_directory", &old_dirs, &new_dirs);
+    diff_items(&mut changes, "section", &old.sections, &new.sections);
+
+    for name in old.imports.difference(&new.imports) {
+        changes.push(item_change(ChangeKind::Removed, "import", name));
+    }
+    for name in new.imports.difference(&old.imports) {
+        changes.push(item_change(ChangeKind::Added, "import", name));
+    }
+
+    for (name, old_rva) in &old.exports {
+        match new.exports.get(name) {
+            None => changes.push(item_change(ChangeKind::Removed, "export", name)),
+            Some(new_rva) if new_rva != old_rva => changes.push(Change {
+                kind: ChangeKind::Changed,
+                category: "export",
+                item: Some(name.clone()),
+                field: Some("rva"),
+                old: Some(old_rva.clone()),
+                new: Some(new_rva.clone()),
+            }),
+            Some(_) => {}
+        }
+    }
+    for name in new.exports.keys().filter(|name| !old.exports.contains_key(*name)) {
+        changes.push(item_change(ChangeKind::Added, "export", name));
+    }
+
+    Ok(changes)
+}
+
+fn get_snapshot(fh: &mut File) -> Result<Snapshot, PEError> {
+    let headers = get_headers_from_file(fh)?;
+    let section_table = get_section_table(fh, headers.as_ref())?;
+    let mut sections: Vec<(String, Vec<(&'static str, FieldValue)>)> = Vec::with_capacity(section_table.len());
+    for header in &section_table {
+        let data = get_raw_section_data(fh, header)?;
+        sections.push((unique_section_name(&sections, header), section_fields(header, &data)));
+    }
+    let imports = get_imports(fh, headers.as_ref(), &section_table)?;
+    let exports = get_exports(fh, headers.as_ref(), &section_table)?;
+
+    Ok(Snapshot {
+        imports: import_names(&imports),
+        exports: export_rvas(exports.as_ref()),
+        headers,
+        sections,
+    })
+}
+
+/// Names a section, suffixing repeated names with their occurrence so that each can still be matched.
+fn unique_section_name(previous: &[(String, Vec<(&'static str, FieldValue)>)], header: &SectionHeader) -> String {
+    let name = header.name_str();
+    let occurrence = previous
+        .iter()
+        .filter(|(other, _)| *other == name || other.starts_with(&format!("{}#", name)))
+        .count();
+    if occurrence == 0 {
+        name
+    } else {
+        format!("{}#{}", name, occurrence + 1)
+    }
+}
+
+fn import_names(imports: &[ImportedDll]) -> BTreeSet<String> {
+    imports
+        .iter()
+        .flat_map(|dll| {
+            dll.functions
+                .iter()
+                .map(move |function| format!("{}!{}", dll.dll_name.to_lowercase(), function.display_name()))
+        })
+        .collect()
+}
+
+fn export_rvas(exports: Option<&Exports>) -> BTreeMap<String, FieldValue> {
+    exports
+        .map(|exports| {
+            exports
+                .functions
+                .iter()
+                .map(|function| {
+                    let target = match &function.forwarder {
+                        Some(forwarder) => FieldValue::Text(forwarder.clone()),
+                        None => FieldValue::Number(function.rva as u64),
+                    };
+                    (function.display_name(), target)
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn coff_fields(header: &CoffHeader) -> Vec<(&'static str, FieldValue)> {
+    vec![
+        ("target_machine", FieldValue::Text(format!("{:?}", header.target_machine))),
+        ("number_of_sections", FieldValue::Number(header.number_of_sections as u64)),
+        ("time_date_stamp", FieldValue::Number(header.time_date_stamp as u64)),
+        ("pointer_to_symbol_table", FieldValue::Number(header.pointer_to_symbol_table as u64)),
+        ("number_of_symbols", FieldValue::Number(header.number_of_symbols as u64)),
+        ("size_of_optional_header", FieldValue::Number(header.size_of_optional_header as u64)),
+        ("characteristics", FieldValue::Text(format!("{:?}", header.characteristics))),
+    ]
+}
+
+fn optional_fields(headers: &dyn PEHeader) -> Vec<(&'static str, FieldValue)> {
+    let number = |value: u64| FieldValue::Number(value);
+    if let Some(header) = headers.optional_header_pe32() {
+        let (standard, windows) = (&header.standard_fields, &header.windows_fields);
+        return vec![
+            ("magic", number(standard.magic as u64)),
+            (
+                "linker_version",
+                FieldValue::Text(format!("{}.{}", standard.major_linker_version, standard.minor_linker_version)),
+            ),
+            ("size_of_code", number(standard.size_of_code as u64)),
+            ("size_of_initialised_data", number(standard.size_of_initialised_data as u64)),
+            ("size_of_uninitialised_data", number(standard.size_of_uninitialised_data as u64)),
+            ("address_of_entry_point", number(standard.address_of_entry_point as u64)),
+            ("base_of_code", number(standard.base_of_code as u64)),
+            ("base_of_data", number(standard.base_of_data as u64)),
+            ("image_base", number(windows.image_base as u64)),
+            ("section_alignment", number(windows.section_alignment as u64)),
+            ("file_alignment", number(windows.file_alignment as u64)),
+            (
+                "operating_system_version",
+                FieldValue::Text(format!("{}.{}", windows.major_operating_system_version, windows.minor_operating_system_version)),
+            ),
+            (
+                "image_version",
+                FieldValue::Text(format!("{}.{}", windows.major_image_version, windows.minor_image_version)),
+            ),
+            (
+                "subsystem_version",
+                FieldValue::Text(format!("{}.{}", windows.major_subsystem_version, windows.minor_subsystem_version)),
+            ),
+            ("size_of_image", number(windows.size_of_image as u64)),
+            ("size_of_headers", number(windows.size_of_headers as u64)),
+            ("checksum", number(windows.checksum as u64)),
+            ("subsystem", FieldValue::Text(format!("{:?}", windows.subsystem))),
+            ("dll_characteristics", FieldValue::Text(format!("{:?}", windows.dll_characteristics))),
+            ("size_of_stack_reserve", number(windows.size_of_stack_reserve as u64)),
+            ("size_of_stack_commit", number(windows.size_of_stack_commit as u64)),
+            ("size_of_heap_reserve", number(windows.size_of_heap_reserve as u64)),
+            ("size_of_heap_commit", number(windows.size_of_heap_commit as u64)),
+            ("number_of_rva_and_sizes", number(windows.number_of_rva_and_sizes as u64)),
+        ];
+    }
+    if let Some(header) = headers.optional_header_pe32plus() {
+        let (standard, windows) = (&header.standard_fields, &header.windows_fields);
+        return vec![
+            ("magic", number(standard.magic as u64)),
+            (
+                "linker_version",
+                FieldValue::Text(format!("{}.{}", standard.major_linker_version, standard.minor_linker_version)),
+            ),
+            ("size_of_code", number(standard.size_of_code as u64)),
+            ("size_of_initialised_data", number(standard.size_of_initialised_data as u64)),
+            ("size_of_uninitialised_data", number(standard.size_of_uninitialised_data as u64)),
+            ("address_of_entry_point", number(standard.address_of_entry_point as u64)),
+            ("base_of_code", number(standard.base_of_code as u64)),
+            ("image_base", number(windows.image_base)),
+            ("section_alignment", number(windows.section_alignment as u64)),
+            ("file_alignment", number(windows.file_alignment as u64)),
+            (
+                "operating_system_version",
+                FieldValue::Text(format!("{}.{}", windows.major_operating_system_version, windows.minor_operating_system_version)),
+            ),
+            (
+                "image_version",
+                FieldValue::Text(format!("{}.{}", windows.major_image_version, windows.minor_image_version)),
+            ),
+            (
+                "subsystem_version",
+                FieldValue::Text(format!("{}.{}", windows.major_subsystem_version, windows.minor_subsystem_version)),
+            ),
+            ("size_of_image", number(windows.size_of_image as u64)),
+            ("size_of_headers", number(windows.size_of_headers as u64)),
+            ("checksum", number(windows.checksum as u64)),
+            ("subsystem", FieldValue::Text(format!("{:?}", windows.subsystem))),
+            ("dll_characteristics", FieldValue::Text(format!("{:?}", windows.dll_characteristics))),
+            ("size_of_stack_reserve", number(windows.size_of_stack_reserve)),
+            ("size_of_stack_commit", number(windows.size_of_stack_commit)),
+            ("size_of_heap_reserve", number(windows.size_of_heap_reserve)),
+            ("size_of_heap_commit", number(windows.size_of_heap_commit)),
+            ("number_of_rva_and_sizes", number(windows.number_of_rva_and_sizes as u64)),
+        ];
+    }
+    Vec::new()
+}
+
+fn data_directory_items(dirs: Option<&Vec<DataDirectory>>) -> Vec<(String, Vec<(&'static str, FieldValue)>)> {
+    dirs.map(|dirs| {
+        dirs.iter()
+            .enumerate()
+            // empty directories are treated as absent, so that filling one in reads as an addition
+            .filter(|(_, dir)| dir.virtual_address != 0 || dir.size != 0)
+            .map(|(i, dir)| {
+                // the display names in snake case, such as base_relocation_table
+                let name = DataDirectory::DISPLAY_NAMES
+                    .get(i)
+                    .map_or_else(|| format!("directory_{}", i), |name| name.to_lowercase().replace(' ', "_"));
+                let fields = vec![
+                    ("virtual_address", FieldValue::Number(dir.virtual_address as u64)),
+                    ("size", FieldValue::Number(dir.size as u64)),
+                ];
+                (name, fields)
+            })
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+fn section_fields(header: &SectionHeader, data: &[u8]) -> Vec<(&'static str, FieldValue)> {
+    vec![
+        ("virtual_address", FieldValue::Number(header.virtual_address as u64)),
+        ("virtual_size", FieldValue::Number(header.virtual_size as u64)),
+        ("pointer_to_raw_data", FieldValue::Number(header.pointer_to_raw_data as u64)),
+        ("size_of_raw_data", FieldValue::Number(header.size_of_raw_data as u64)),
+        ("characteristics", FieldValue::Text(format!("{:?}", header.characteristics))),
+        ("md5", FieldValue::Text(get_digests(data).md5)),
+    ]
+}
+
+fn item_change(kind: ChangeKind, category: &'static str, item: &str) -> Change {
+    Change {
+        kind,
+        category,
+        item: Some(item.to_string()),
+        field: None,
+        old: None,
+        new: None,
+    }
+}
+
+fn diff_fields(changes: &mut Vec<Change>, category: &'static str, item: Option<&str>, old: &[(&'static str, FieldValue)], new: &[(&'static str, FieldValue)]) {
+    for (field, old_value) in old {
+        let new_value = new.iter().find(|(other, _)| other == field).map(|(_, value)| value);
+        if new_value != Some(old_value) {
+            changes.push(Change {
+                kind: ChangeKind::Changed,
+                category,
+                item: item.map(str::to_string),
+                field: Some(field),
+                old: Some(old_value.clone()),
+                new: new_value.cloned(),
+            });
+        }
+    }
+    for (field, new_value) in new.iter().filter(|(field, _)| !old.iter().any(|(other, _)| other == field)) {
+        changes.push(Change {
+            kind: ChangeKind::Changed,
+            category,
+            item: item.map(str::to_string),
+            field: Some(field),
+            old: None,
+            new: Some(new_value.clone()),
+        });
+    }
+}
+
+fn diff_items(
+    changes: &mut Vec<Change>,
+    category: &'static str,
+    old: &[(String, Vec<(&'static str, FieldValue)>)],
+    new: &[(String, Vec<(&'static str, FieldValue)>)],
+) {
+    for (name, old_fields) in old {
+        match new.iter().find(|(other, _)| other == name) {
+            Some((_, new_fields)) => diff_fields(changes, category, Some(name), old_fields, new_fields),
+            None => changes.push(item_change(ChangeKind::Removed, category, name)),
+        }
+    }
+    for (name, _) in new.iter().filter(|(name, _)| !old.iter().any(|(other, _)| other == name)) {
+        changes.push(item_change(ChangeKind::Added, category, name));
+    }
+}
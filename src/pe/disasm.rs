@@ -0,0 +1,244 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::exports::get_exports;
+use super::headers::MachineType;
+use super::imports::get_imports;
+use super::internal::agnostic_fio::read_up_to;
+use super::internal::rva::rva_to_addr;
+use super::tls::get_tls;
+use super::traits::PEHeader;
+#[cfg(feature = "disasm-arm64")]
+use capstone::arch::{self, BuildsCapstone};
+#[cfg(feature = "disasm-arm64")]
+use capstone::Capstone;
+use iced_x86::{Decoder, DecoderOptions, Formatter, IntelFormatter, OpKind, Register};
+use std::collections::HashMap;
+use std::fs::File;
+
+/// Longest x86 or x64 instruction, in bytes.
+const MAX_X86_INSTRUCTION_LEN: usize = 15;
+/// Length of every ARM64 instruction, in bytes.
+const ARM64_INSTRUCTION_LEN: usize = 4;
+
+/// One decoded instruction.
+#[derive(Debug, Clone)]
+pub struct Instruction {
+    pub rva: u32,
+    pub va: u64,
+    pub bytes: Vec<u8>,
+    /// The instruction in Intel syntax for x86 and x64, or in Capstone's syntax for ARM64.
+    pub text: String,
+    /// Name of the import, export or other symbol that the instruction calls, jumps to or reads, if known.
+    pub symbol: Option<String>,
+}
+
+/// Names for addresses in an image, keyed by VA.
+#[derive(Debug, Clone, Default)]
+pub struct Symbols {
+    names: HashMap<u64, String>,
+}
+
+impl Symbols {
+    pub fn get(&self, va: u64) -> Option<&str> {
+        self.names.get(&va).map(String::as_str)
+    }
+}
+
+/// Gathers names for the entry point, TLS callbacks, exports and import address table slots.
+///
+/// Any of these that cannot be read are left out, so that a malformed table does not stop disassembly.
+pub fn get_symbols(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Symbols {
+    let (image_base, entry_point) = match image_base_and_entry_point(headers) {
+        Some(fields) => fields,
+        None => return Symbols::default(),
+    };
+    let mut names: HashMap<u64, String> = HashMap::new();
+    if entry_point != 0 {
+        names.insert(image_base + entry_point as u64, String::from("entry"));
+    }
+    if let Ok(Some(tls)) = get_tls(fh, headers, section_table) {
+        for (i, callback) in tls.callbacks.iter().enumerate() {
+            names.insert(image_base + *callback as u64, format!("tls_callback_{}", i));
+        }
+    }
+    if let Ok(Some(exports)) = get_exports(fh, headers, section_table) {
+        for function in exports.functions.iter().filter(|function| function.forwarder.is_none()) {
+            names.insert(image_base + function.rva as u64, function.display_name());
+        }
+    }
+    for dll in get_imports(fh, headers, section_table).unwrap_or_default() {
+        for function in &dll.functions {
+            let function_name = match (&function.name, function.ordinal) {
+                (Some(name), _) => name.clone(),
+                (None, Some(ordinal)) => format!("#{}", ordinal),
+                (None, None) => continue,
+            };
+            names.insert(image_base + function.thunk_rva as u64, format!("{}!{}", dll.dll_name, function_name));
+        }
+    }
+    Symbols { names }
+}
+
+/// Disassembles up to `count` instructions starting at an RVA, stopping early at the end of the section's raw data.
+pub fn disassemble(
+    fh: &mut File,
+    headers: &(impl PEHeader + ?Sized),
+    section_table: &[SectionHeader],
+    rva: u32,
+    count: usize,
+    symbols: &Symbols,
+) -> Result<Vec<Instruction>, PEError> {
+    let (image_base, _) = match image_base_and_entry_point(headers) {
+        Some(fields) => fields,
+        None => return Err(PEError::DeserialiseError(String::from("object files cannot be disassembled"))),
+    };
+    let machine = headers.coff_header().target_machine;
+    let max_len = match machine {
+        MachineType::I386 | MachineType::Amd64 => count.saturating_mul(MAX_X86_INSTRUCTION_LEN),
+        MachineType::Arm64 => count.saturating_mul(ARM64_INSTRUCTION_LEN),
+        _ => return Err(PEError::DeserialiseError(format!("disassembly of {:?} code is not supported", machine))),
+    };
+
+    // only read up to the end of the section's raw data, not into whatever follows it in the file
+    let section = match section_table.iter().find(|section| section.contains_rva(rva)) {
+        Some(section) => section,
+        None => return Err(PEError::DeserialiseError(format!("RVA {:08X}h is not inside any section", rva))),
+    };
+    let raw_left = (section.size_of_raw_data as usize).saturating_sub((rva - section.virtual_address) as usize);
+    let code = match rva_to_addr(section_table, rva).and_then(|addr| read_up_to(fh, addr, max_len.min(raw_left))) {
+        Ok(code) => code,
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+
+    let va = image_base + rva as u64;
+    match machine {
+        MachineType::I386 => Ok(disassemble_x86(&code, 32, va, image_base, count, symbols)),
+        MachineType::Amd64 => Ok(disassemble_x86(&code, 64, va, image_base, count, symbols)),
+        _ => disassemble_arm64(&code, va, image_base, count, symbols),
+    }
+}
+
+fn disassemble_x86(code: &[u8], bitness: u32, va: u64, image_base: u64, count: usize, symbols: &Symbols) -> Vec<Instruction> {
+    let mut decoder = Decoder::with_ip(bitness, code, va, DecoderOptions::NONE);
+    let mut formatter = IntelFormatter::new();
+    let mut instruction = iced_x86::Instruction::default();
+    let mut ret: Vec<Instruction> = Vec::new();
+    while decoder.can_decode() && ret.len() < count {
+        let start = decoder.position();
+        decoder.decode_out(&mut instruction);
+        let mut text = String::new();
+        formatter.format(&instruction, &mut text);
+
+        let symbol = x86_referenced_address(&instruction).and_then(|address| symbols.get(address));
+        ret.push(Instruction {
+            rva: (instruction.ip() - image_base) as u32,
+            va: instruction.ip(),
+            bytes: code[start..start + instruction.len()].to_vec(),
+            text,
+            symbol: symbol.map(String::from),
+        });
+    }
+    ret
+}
+
+/// The address an instruction branches to, or reads from with an absolute or RIP-relative memory operand.
+fn x86_referenced_address(instruction: &iced_x86::Instruction) -> Option<u64> {
+    if instruction
+        .op_kinds()
+        .any(|kind| matches!(kind, OpKind::NearBranch16 | OpKind::NearBranch32 | OpKind::NearBranch64))
+    {
+        return Some(instruction.near_branch_target());
+    }
+    if !instruction.op_kinds().any(|kind| kind == OpKind::Memory) {
+        return None;
+    }
+    if instruction.is_ip_rel_memory_operand() {
+        Some(instruction.ip_rel_memory_address())
+    } else if instruction.memory_base() == Register::None && instruction.memory_index() == Register::None {
+        Some(instruction.memory_displacement64())
+    } else {
+        None
+    }
+}
+
+#[cfg(feature = "disasm-arm64")]
+fn disassemble_arm64(code: &[u8], va: u64, image_base: u64, count: usize, symbols: &Symbols) -> Result<Vec<Instruction>, PEError> {
+    let cs = match Capstone::new().arm64().mode(arch::arm64::ArchMode::Arm).build() {
+        Ok(cs) => cs,
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+
+    // imports are called through `adrp xN, page` followed by `ldr xM, [xN, #offset]` or `add xM, xN, #offset`, so track the pages loaded
+    let mut pages: HashMap<String, u64> = HashMap::new();
+    let mut ret: Vec<Instruction> = Vec::new();
+    // decode one word at a time, as Capstone stops at the first word that is not a valid instruction
+    for (i, word) in code.chunks_exact(ARM64_INSTRUCTION_LEN).take(count).enumerate() {
+        let address = va + (i * ARM64_INSTRUCTION_LEN) as u64;
+        let decoded = match cs.disasm_count(word, address, 1) {
+            Ok(decoded) => decoded,
+            Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+        };
+        let (mnemonic, op_str) = match decoded.iter().next() {
+            Some(insn) => (insn.mnemonic().unwrap_or_default().to_string(), insn.op_str().unwrap_or_default().to_string()),
+            None => (String::from(".inst"), format!("{:#010x}", u32::from_le_bytes(word.try_into().unwrap()))),
+        };
+        let operands: Vec<&str> = op_str.split(", ").collect();
+        let referenced = match mnemonic.as_str() {
+            "bl" | "b" => parse_immediate(&op_str),
+            "adrp" => {
+                if let (Some(register), Some(page)) = (operands.first(), operands.get(1).and_then(|page| parse_immediate(page))) {
+                    pages.insert(register.to_string(), page);
+                }
+                None
+            }
+            "add" if operands.len() == 3 => {
+                let offset = parse_immediate(operands[2]);
+                pages.get(operands[1]).zip(offset).map(|(page, offset)| page + offset)
+            }
+            "ldr" if operands.len() == 3 => {
+                let base = operands[1].trim_start_matches('[');
+                let offset = parse_immediate(operands[2].trim_end_matches(']'));
+                pages.get(base).zip(offset).map(|(page, offset)| page + offset)
+            }
+            _ => None,
+        };
+
+        let text = if op_str.is_empty() { mnemonic } else { format!("{} {}", mnemonic, op_str) };
+        ret.push(Instruction {
+            rva: (address - image_base) as u32,
+            va: address,
+            bytes: word.to_vec(),
+            text,
+            symbol: referenced.and_then(|referenced| symbols.get(referenced)).map(String::from),
+        });
+    }
+    Ok(ret)
+}
+
+#[cfg(not(feature = "disasm-arm64"))]
+fn disassemble_arm64(_code: &[u8], _va: u64, _image_base: u64, _count: usize, _symbols: &Symbols) -> Result<Vec<Instruction>, PEError> {
+    Err(PEError::DeserialiseError(String::from(
+        "pepeek was built without the disasm-arm64 feature, so ARM64 code cannot be disassembled",
+    )))
+}
+
+/// Parses a Capstone immediate such as `#0x140001000` or `#16`.
+#[cfg(feature = "disasm-arm64")]
+fn parse_immediate(operand: &str) -> Option<u64> {
+    let value = operand.strip_prefix('#')?;
+    match value.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => value.parse::<u64>().ok(),
+    }
+}
+
+fn image_base_and_entry_point(headers: &(impl PEHeader + ?Sized)) -> Option<(u64, u32)> {
+    match (headers.optional_header_pe32(), headers.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => Some((pe32_header.windows_fields.image_base as u64, pe32_header.standard_fields.address_of_entry_point)),
+        (_, Some(pe32plus_header)) => Some((
+            pe32plus_header.windows_fields.image_base,
+            pe32plus_header.standard_fields.address_of_entry_point,
+        )),
+        _ => None,
+    }
+}
@@ -1,4 +1,21 @@
+use std::fmt::Display;
+
 #[derive(Debug)]
 pub enum PEError {
     DeserialiseError(String),
+    SerialiseError(String),
+    /// A modification that cannot be made to the file, such as setting a field that it does not have.
+    InvalidEdit(String),
 }
+
+impl Display for PEError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::DeserialiseError(msg) => write!(f, "could not deserialise: {}", msg),
+            Self::SerialiseError(msg) => write!(f, "could not serialise: {}", msg),
+            Self::InvalidEdit(msg) => write!(f, "invalid edit: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PEError {}
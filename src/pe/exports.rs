@@ -0,0 +1,123 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::headers::DataDirectory;
+use super::internal::agnostic_fio::read_exact;
+use super::internal::rva::{offset_rva, read_name_at_rva, rva_to_addr};
+use super::traits::PEHeader;
+use std::fs::File;
+use std::io;
+use std::mem::{size_of, transmute};
+
+/// Most functions or names that will be read, to bound malformed tables.
+const MAX_EXPORTS: u32 = 0x10000;
+
+/// The export directory table (`IMAGE_EXPORT_DIRECTORY`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ExportDirectory {
+    pub export_flags: u32,
+    pub time_date_stamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    pub name_rva: u32,
+    /// Ordinal of the first entry in the export address table.
+    pub ordinal_base: u32,
+    pub number_of_functions: u32,
+    pub number_of_names: u32,
+    pub address_of_functions: u32,
+    pub address_of_names: u32,
+    pub address_of_name_ordinals: u32,
+}
+
+/// A single exported function or variable.
+#[derive(Debug, Clone)]
+pub struct ExportedFunction {
+    pub ordinal: u32,
+    /// RVA of the export, or of the forwarder string for forwarded exports.
+    pub rva: u32,
+    /// Name of the export, or `None` if it is only exported by ordinal.
+    pub name: Option<String>,
+    /// Target of a forwarded export, such as `NTDLL.RtlAllocateHeap`.
+    pub forwarder: Option<String>,
+}
+
+/// The exports of an image.
+#[derive(Debug, Clone)]
+pub struct Exports {
+    pub directory: ExportDirectory,
+    /// Name the image was built with, such as `KERNEL32.dll`.
+    pub dll_name: String,
+    /// Exports in ordinal order, skipping unused slots of the export address table.
+    pub functions: Vec<ExportedFunction>,
+}
+
+impl ExportedFunction {
+    /// The export's name, or `#<ordinal>` if it is only exported by ordinal.
+    pub fn display_name(&self) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!("#{}", self.ordinal),
+        }
+    }
+}
+
+/// Reads the export directory, or `None` if the image has no exports.
+pub fn get_exports(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Option<Exports>, PEError> {
+    let export_dir = match headers.data_directory(DataDirectory::EXPORT_TABLE) {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+
+    match do_get_exports(fh, export_dir, section_table) {
+        Ok(exports) => Ok(Some(exports)),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn do_get_exports(fh: &mut File, export_dir: &DataDirectory, section_table: &[SectionHeader]) -> io::Result<Exports> {
+    let directory_addr = rva_to_addr(section_table, export_dir.virtual_address)?;
+    let directory: ExportDirectory = unsafe { transmute(read_exact::<{ size_of::<ExportDirectory>() }>(fh, directory_addr)?) };
+    let dll_name = read_name_at_rva(fh, section_table, directory.name_rva)?;
+
+    // names map to indices into the address table through the ordinal table
+    let num_names = directory.number_of_names.min(MAX_EXPORTS);
+    let mut names: Vec<Option<String>> = vec![None; directory.number_of_functions.min(MAX_EXPORTS) as usize];
+    for i in 0..num_names {
+        let name_rva_addr = rva_to_addr(section_table, offset_rva(directory.address_of_names, i * 4)?)?;
+        let name_rva = u32::from_le_bytes(read_exact(fh, name_rva_addr)?);
+        let ordinal_addr = rva_to_addr(section_table, offset_rva(directory.address_of_name_ordinals, i * 2)?)?;
+        let index = u16::from_le_bytes(read_exact(fh, ordinal_addr)?) as usize;
+        if let Some(slot) = names.get_mut(index) {
+            *slot = Some(read_name_at_rva(fh, section_table, name_rva)?);
+        }
+    }
+
+    let export_dir_end = export_dir.virtual_address as u64 + export_dir.size as u64;
+    let mut functions: Vec<ExportedFunction> = Vec::new();
+    for (i, name) in names.into_iter().enumerate() {
+        let function_addr = rva_to_addr(section_table, offset_rva(directory.address_of_functions, i as u32 * 4)?)?;
+        let rva = u32::from_le_bytes(read_exact(fh, function_addr)?);
+        if rva == 0 {
+            continue;
+        }
+
+        // exports pointing back into the export directory are forwarders
+        let forwarder = if rva >= export_dir.virtual_address && (rva as u64) < export_dir_end {
+            Some(read_name_at_rva(fh, section_table, rva)?)
+        } else {
+            None
+        };
+        functions.push(ExportedFunction {
+            ordinal: directory.ordinal_base + i as u32,
+            rva,
+            name,
+            forwarder,
+        });
+    }
+
+    Ok(Exports {
+        directory,
+        dll_name,
+        functions,
+    })
+}
@@ -0,0 +1,142 @@
+use super::body::SectionHeader;
+use super::headers::{CoffHeader, DataDirectory, DosHeader, OptionalHeaderPe32, OptionalHeaderPe32Plus};
+use super::traits::PEHeader;
+use std::mem::size_of;
+
+/// Size of the PE signature, `PE\0\0`.
+pub const PE_SIGNATURE_LEN: u64 = 4;
+
+/// An optional header of either flavour.
+#[derive(Debug)]
+pub enum OptionalHeader {
+    Pe32(OptionalHeaderPe32),
+    Pe32Plus(OptionalHeaderPe32Plus),
+}
+
+/// A section's header along with its raw data.
+#[derive(Debug)]
+pub struct Section {
+    pub header: SectionHeader,
+    /// The raw data, as stored in the file at `header.pointer_to_raw_data`. This is normally `size_of_raw_data` bytes
+    /// long, but is shorter if the file was truncated.
+    pub data: Vec<u8>,
+}
+
+/// Bytes that no header or section accounts for, such as the padding after the section table or a COFF line number
+/// table, kept at their file offset so that they survive a round trip.
+#[derive(Debug, Clone)]
+pub struct Gap {
+    pub offset: u64,
+    pub data: Vec<u8>,
+}
+
+/// A whole image or object file, split into its parts so that they can be modified and written back out.
+///
+/// Where each part is written is taken from the headers: the PE signature is at `e_lfanew`, the section table follows
+/// the optional header, and each section's data is at its `pointer_to_raw_data`. The overlay follows whichever of
+/// these ends last.
+#[derive(Debug)]
+pub struct PeFile {
+    /// The MS-DOS header, or `None` for an object file.
+    pub dos_header: Option<DosHeader>,
+    /// Everything between the MS-DOS header and the PE signature, including the Rich header.
+    pub dos_stub: Vec<u8>,
+    pub coff_header: CoffHeader,
+    /// The optional header, or `None` for an object file.
+    pub optional_header: Option<OptionalHeader>,
+    pub data_directories: Vec<DataDirectory>,
+    pub sections: Vec<Section>,
+    pub gaps: Vec<Gap>,
+    /// Everything past the end of the headers and sections, including the certificate table and COFF symbol table.
+    pub overlay: Vec<u8>,
+}
+
+impl OptionalHeader {
+    /// Size of the fixed part of the header, before the data directories.
+    pub fn size(&self) -> u64 {
+        match self {
+            OptionalHeader::Pe32(_) => size_of::<OptionalHeaderPe32>() as u64,
+            OptionalHeader::Pe32Plus(_) => size_of::<OptionalHeaderPe32Plus>() as u64,
+        }
+    }
+}
+
+impl PeFile {
+    /// File offset of the COFF header.
+    pub fn coff_header_offset(&self) -> u64 {
+        match &self.dos_header {
+            Some(dos_header) => dos_header.address_of_new_header as u64 + PE_SIGNATURE_LEN,
+            // object files start with the COFF header
+            None => 0,
+        }
+    }
+
+    /// File offset of the optional header, which directly follows the COFF header.
+    pub fn optional_header_offset(&self) -> u64 {
+        self.coff_header_offset() + size_of::<CoffHeader>() as u64
+    }
+
+    /// File offset of the section table, which follows the optional header.
+    pub fn section_table_offset(&self) -> u64 {
+        self.optional_header_offset() + self.coff_header.size_of_optional_header as u64
+    }
+
+    /// File offset that the overlay starts at: the end of the section table or of the last section's data, whichever
+    /// is further into the file. Sections with no data, such as those whose data is past EOF, are not counted.
+    pub fn overlay_offset(&self) -> u64 {
+        let section_table_end = self.section_table_offset() + (self.sections.len() * size_of::<SectionHeader>()) as u64;
+        self.sections
+            .iter()
+            .filter(|section| section.header.pointer_to_raw_data != 0 && !section.data.is_empty())
+            .map(|section| section.header.pointer_to_raw_data as u64 + section.data.len() as u64)
+            .fold(section_table_end, u64::max)
+    }
+
+    /// Finds the section holding an RVA in its raw data, and the offset into that data.
+    pub fn find_rva(&self, rva: u32) -> Option<(usize, usize)> {
+        self.sections.iter().enumerate().find_map(|(i, section)| {
+            let offset = rva.checked_sub(section.header.virtual_address)? as usize;
+            (offset < section.data.len()).then_some((i, offset))
+        })
+    }
+
+    /// Finds the section whose raw data holds a file offset, and the offset into that data.
+    pub fn find_file_offset(&self, offset: u64) -> Option<(usize, usize)> {
+        self.sections.iter().enumerate().find_map(|(i, section)| {
+            if section.header.pointer_to_raw_data == 0 {
+                return None;
+            }
+            let offset = offset.checked_sub(section.header.pointer_to_raw_data as u64)? as usize;
+            (offset < section.data.len()).then_some((i, offset))
+        })
+    }
+
+    /// The section headers, in the same form as `deser::get_section_table` gives them.
+    pub fn section_table(&self) -> Vec<SectionHeader> {
+        self.sections.iter().map(|section| section.header.clone()).collect()
+    }
+}
+
+impl PEHeader for PeFile {
+    fn coff_header(&self) -> &CoffHeader {
+        &self.coff_header
+    }
+
+    fn optional_header_pe32(&self) -> Option<&OptionalHeaderPe32> {
+        match &self.optional_header {
+            Some(OptionalHeader::Pe32(optional_header)) => Some(optional_header),
+            _ => None,
+        }
+    }
+
+    fn optional_header_pe32plus(&self) -> Option<&OptionalHeaderPe32Plus> {
+        match &self.optional_header {
+            Some(OptionalHeader::Pe32Plus(optional_header)) => Some(optional_header),
+            _ => None,
+        }
+    }
+
+    fn data_directories(&self) -> Option<&Vec<DataDirectory>> {
+        self.optional_header.as_ref().map(|_| &self.data_directories)
+    }
+}
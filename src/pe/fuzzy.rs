@@ -0,0 +1,108 @@
+/// Pearson permutation table used to map byte triplets to buckets.
+const PEARSON_TABLE: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163, 14, 197, 213, 181, 161, 85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200, 110, 177,
+    104, 103, 141, 253, 255, 50, 77, 101, 81, 18, 45, 96, 31, 222, 25, 107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227, 149, 235, 97, 234, 57, 22,
+    60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248, 174, 169, 211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243, 132, 56, 148, 75, 128,
+    133, 158, 100, 130, 126, 91, 13, 153, 246, 216, 219, 119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92, 32, 136, 114, 52, 10, 138, 30, 48, 183, 156, 35, 61,
+    26, 143, 74, 251, 94, 129, 162, 63, 152, 170, 7, 115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131, 125, 173, 15, 238, 79, 95, 89, 16, 105,
+    137, 225, 224, 217, 160, 37, 123, 118, 73, 2, 157, 46, 116, 9, 145, 134, 228, 207, 212, 202, 215, 69, 229, 27, 188, 67, 124, 168, 252, 42, 4, 29, 108, 21,
+    247, 19, 205, 39, 203, 233, 40, 186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76, 140, 36, 210, 172, 41, 54, 159, 8, 185, 232, 113, 196,
+    231, 47, 146, 120, 51, 65, 28, 144, 254, 221, 93, 189, 194, 139, 112, 43, 71, 109, 184, 209,
+];
+
+const WINDOW_SIZE: usize = 5;
+const EFFECTIVE_BUCKETS: usize = 128;
+const CODE_SIZE: usize = EFFECTIVE_BUCKETS / 4;
+const MIN_DATA_LENGTH: usize = 50;
+
+/// Computes a TLSH-style locality sensitive hash of a buffer, in the 72 character `T1` hex format.
+///
+/// This follows the TLSH construction with 128 buckets and a one byte checksum: byte triplets from a five byte
+/// sliding window are counted into buckets, and each bucket is encoded by which quartile its count falls in, so
+/// that similar inputs give hashes a small distance apart. Returns `None` if the data is shorter than 50 bytes or
+/// too uniform to hash meaningfully.
+pub fn get_fuzzy_hash(data: &[u8]) -> Option<String> {
+    if data.len() < MIN_DATA_LENGTH {
+        return None;
+    }
+
+    let mut buckets = [0_u32; 256];
+    let mut checksum: u8 = 0;
+    for i in (WINDOW_SIZE - 1)..data.len() {
+        let (b0, b1, b2, b3, b4) = (data[i], data[i - 1], data[i - 2], data[i - 3], data[i - 4]);
+        checksum = pearson_hash(0, b0, b1, checksum);
+        buckets[pearson_hash(2, b0, b1, b2) as usize] += 1;
+        buckets[pearson_hash(3, b0, b1, b3) as usize] += 1;
+        buckets[pearson_hash(5, b0, b2, b3) as usize] += 1;
+        buckets[pearson_hash(7, b0, b2, b4) as usize] += 1;
+        buckets[pearson_hash(11, b0, b1, b4) as usize] += 1;
+        buckets[pearson_hash(13, b0, b3, b4) as usize] += 1;
+    }
+
+    let buckets = &buckets[..EFFECTIVE_BUCKETS];
+    let nonzero = buckets.iter().filter(|&&count| count > 0).count();
+    if nonzero <= EFFECTIVE_BUCKETS / 2 {
+        return None;
+    }
+
+    let mut sorted = buckets.to_vec();
+    sorted.sort_unstable();
+    let (q1, q2, q3) = (sorted[CODE_SIZE - 1], sorted[2 * CODE_SIZE - 1], sorted[3 * CODE_SIZE - 1]);
+    if q3 == 0 {
+        return None;
+    }
+
+    // each bucket becomes two bits, and the body is written out last bucket first
+    let mut code = [0_u8; CODE_SIZE];
+    for (i, chunk) in buckets.chunks_exact(4).enumerate() {
+        let mut h: u8 = 0;
+        for (j, &count) in chunk.iter().enumerate() {
+            let quartile = if count > q3 {
+                3
+            } else if count > q2 {
+                2
+            } else if count > q1 {
+                1
+            } else {
+                0
+            };
+            h |= quartile << (j * 2);
+        }
+        code[CODE_SIZE - 1 - i] = h;
+    }
+
+    let q1_ratio = ((q1 as u64 * 100 / q3 as u64) % 16) as u8;
+    let q2_ratio = ((q2 as u64 * 100 / q3 as u64) % 16) as u8;
+    let header = [
+        swap_nibbles(checksum),
+        swap_nibbles(length_code(data.len())),
+        swap_nibbles(q1_ratio | (q2_ratio << 4)),
+    ];
+
+    let hex: String = header.iter().chain(code.iter()).map(|byte| format!("{:02X}", byte)).collect();
+    Some(format!("T1{}", hex))
+}
+
+fn pearson_hash(salt: u8, a: u8, b: u8, c: u8) -> u8 {
+    let mut h = PEARSON_TABLE[salt as usize];
+    h = PEARSON_TABLE[(h ^ a) as usize];
+    h = PEARSON_TABLE[(h ^ b) as usize];
+    PEARSON_TABLE[(h ^ c) as usize]
+}
+
+/// Encodes the data length on a logarithmic scale, so that it fits in a byte.
+fn length_code(len: usize) -> u8 {
+    let len = len as f64;
+    let code = if len <= 656.0 {
+        (len.ln() / 1.5_f64.ln()).floor()
+    } else if len <= 3199.0 {
+        (len.ln() / 1.3_f64.ln() - 8.72777).floor()
+    } else {
+        (len.ln() / 1.1_f64.ln() - 62.5472).floor()
+    };
+    (code as u32 & 0xff) as u8
+}
+
+fn swap_nibbles(byte: u8) -> u8 {
+    byte.rotate_left(4)
+}
@@ -0,0 +1,221 @@
+use super::checksum::get_checksum_offset;
+use super::err::PEError;
+use super::exports::Exports;
+use super::imports::ImportedDll;
+use super::internal::ordinals::lookup_ordinal;
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+/// Size of the chunks that a file is read in by `get_file_digests`.
+const FILE_CHUNK_LEN: usize = 0x10000;
+
+/// Cryptographic digests of a buffer, as lowercase hex strings.
+#[derive(Debug, Clone)]
+pub struct Digests {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+/// Computes the MD5, SHA-1 and SHA-256 digests of a buffer.
+pub fn get_digests(data: &[u8]) -> Digests {
+    let whole = 0..data.len();
+    get_digests_of_ranges(data, std::slice::from_ref(&whole))
+}
+
+/// Computes the MD5, SHA-1 and SHA-256 digests of a whole file, reading it in chunks rather than all at once.
+pub fn get_file_digests(fh: &mut File) -> Result<Digests, PEError> {
+    match do_get_file_digests(fh) {
+        Ok(digests) => Ok(digests),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Computes the import hash, compatible with Mandiant's imphash and `pefile`.
+///
+/// Each import is written as `dll.function` in lowercase, with the `.dll`, `.ocx` or `.sys` extension removed and
+/// functions imported by ordinal named where the ordinal is well known, or as `ord<N>` otherwise. The MD5 of the
+/// comma-separated list, in import order, is the imphash. Returns `None` if the image has no imports.
+pub fn get_imphash(imports: &[ImportedDll]) -> Option<String> {
+    let mut entries: Vec<String> = Vec::new();
+    for dll in imports {
+        let dll_name = dll.dll_name.to_lowercase();
+        let lib_name = match dll_name.rsplit_once('.') {
+            Some((stem, "dll" | "ocx" | "sys")) => stem,
+            _ => dll_name.as_str(),
+        };
+
+        for function in &dll.functions {
+            let function_name = match (&function.name, function.ordinal) {
+                (Some(name), _) => name.clone(),
+                (None, Some(ordinal)) => lookup_ordinal(&dll_name, ordinal),
+                (None, None) => continue,
+            };
+            entries.push(format!("{}.{}", lib_name, function_name.to_lowercase()));
+        }
+    }
+
+    if entries.is_empty() {
+        return None;
+    }
+    Some(format!("{:x}", Md5::digest(entries.join(",").as_bytes())))
+}
+
+/// Computes the export hash, the export counterpart of the imphash.
+///
+/// This is the MD5 of the comma-separated, lowercased export names in ordinal order, with exports that have no
+/// name written as `ord<N>`. Returns `None` if the image has no exports.
+pub fn get_exphash(exports: &Exports) -> Option<String> {
+    if exports.functions.is_empty() {
+        return None;
+    }
+
+    let entries: Vec<String> = exports
+        .functions
+        .iter()
+        .map(|function| match &function.name {
+            Some(name) => name.to_lowercase(),
+            None => format!("ord{}", function.ordinal),
+        })
+        .collect();
+    Some(format!("{:x}", Md5::digest(entries.join(",").as_bytes())))
+}
+
+/// Computes the Authenticode digests of an image, as a signature over it would record them.
+///
+/// This covers the whole file except for the checksum, the certificate table data directory entry and the
+/// certificate table itself.
+pub fn get_authentihash(data: &[u8]) -> Result<Digests, PEError> {
+    let ranges = get_authenticode_ranges(data)?;
+    Ok(get_digests_of_ranges(data, &ranges))
+}
+
+fn get_authenticode_ranges(data: &[u8]) -> Result<Vec<Range<usize>>, PEError> {
+    let checksum_offset = get_checksum_offset(data)?;
+
+    // the optional header starts 64 bytes before the checksum, and its data directories follow the fixed fields
+    let optional_header_offset = checksum_offset - 64;
+    let (directories_offset, num_directories_offset) = match data.get(optional_header_offset..optional_header_offset + 2) {
+        Some([0x0b, 0x01]) => (optional_header_offset + 96, optional_header_offset + 92),
+        Some([0x0b, 0x02]) => (optional_header_offset + 112, optional_header_offset + 108),
+        _ => return Err(PEError::DeserialiseError(String::from("magic number not recognised as PE32 or PE32+"))),
+    };
+    let num_directories = match data.get(num_directories_offset..num_directories_offset + 4) {
+        Some(bytes) => u32::from_le_bytes(bytes.try_into().unwrap()),
+        None => return Err(PEError::DeserialiseError(String::from("image too small for optional header"))),
+    };
+
+    let cert_entry_offset = directories_offset + 4 * 8;
+    if num_directories <= 4 || cert_entry_offset + 8 > data.len() {
+        // no certificate table entry to skip
+        return Ok(vec![0..checksum_offset, checksum_offset + 4..data.len()]);
+    }
+
+    let cert_addr = u32::from_le_bytes(data[cert_entry_offset..cert_entry_offset + 4].try_into().unwrap()) as usize;
+    let cert_size = u32::from_le_bytes(data[cert_entry_offset + 4..cert_entry_offset + 8].try_into().unwrap()) as usize;
+    let mut ranges = vec![0..checksum_offset, checksum_offset + 4..cert_entry_offset];
+    if cert_addr == 0 || cert_size == 0 || cert_addr >= data.len() {
+        ranges.push(cert_entry_offset + 8..data.len());
+    } else {
+        let cert_end = (cert_addr + cert_size).min(data.len());
+        ranges.push(cert_entry_offset + 8..cert_addr.max(cert_entry_offset + 8));
+        ranges.push(cert_end..data.len());
+    }
+    Ok(ranges)
+}
+
+fn get_digests_of_ranges(data: &[u8], ranges: &[Range<usize>]) -> Digests {
+    let mut hashers = Hashers::default();
+    for range in ranges {
+        hashers.update(&data[range.clone()]);
+    }
+    hashers.finalize()
+}
+
+fn do_get_file_digests(fh: &mut File) -> io::Result<Digests> {
+    fh.seek(SeekFrom::Start(0))?;
+    let mut hashers = Hashers::default();
+    let mut chunk = vec![0u8; FILE_CHUNK_LEN];
+    loop {
+        match fh.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(len) => hashers.update(&chunk[..len]),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(hashers.finalize())
+}
+
+/// The hashers behind [`Digests`], fed the same data.
+#[derive(Default)]
+struct Hashers {
+    md5: Md5,
+    sha1: Sha1,
+    sha256: Sha256,
+}
+
+impl Hashers {
+    fn update(&mut self, data: &[u8]) {
+        self.md5.update(data);
+        self.sha1.update(data);
+        self.sha256.update(data);
+    }
+
+    fn finalize(self) -> Digests {
+        Digests {
+            md5: format!("{:x}", self.md5.finalize()),
+            sha1: format!("{:x}", self.sha1.finalize()),
+            sha256: format!("{:x}", self.sha256.finalize()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::imports::{ImportDescriptor, ImportedFunction};
+
+    fn dll(dll_name: &str, functions: &[(Option<&str>, Option<u16>)]) -> ImportedDll {
+        ImportedDll {
+            descriptor: ImportDescriptor {
+                import_lookup_table_rva: 0,
+                time_date_stamp: 0,
+                forwarder_chain: 0,
+                name_rva: 0,
+                import_address_table_rva: 0,
+            },
+            dll_name: String::from(dll_name),
+            functions: functions
+                .iter()
+                .map(|(name, ordinal)| ImportedFunction {
+                    name: name.map(String::from),
+                    ordinal: *ordinal,
+                    hint: None,
+                    thunk_rva: 0,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn imphash_names_ordinals_like_pefile() {
+        // pefile gives "kernel32.getprocaddress,ws2_32.wsastartup,ws2_32.getaddrinfo,ws2_32.ord9999,
+        // oleaut32.sysfreestring,oleaut32.safearraycreatevector,oleaut32.varui8fromdec" for these imports
+        let imports = [
+            dll("KERNEL32.dll", &[(Some("GetProcAddress"), None)]),
+            dll("WS2_32.dll", &[(None, Some(115)), (None, Some(98)), (None, Some(9999))]),
+            dll("OLEAUT32.dll", &[(None, Some(6)), (None, Some(411)), (None, Some(441))]),
+        ];
+        assert_eq!(get_imphash(&imports).as_deref(), Some("138526855e33b8172c89d75f03c9d032"));
+    }
+
+    #[test]
+    fn imphash_of_no_imports_is_none() {
+        assert_eq!(get_imphash(&[]), None);
+    }
+}
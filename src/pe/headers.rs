@@ -7,6 +7,7 @@ use super::traits::PEHeader;
 /// The two possible flavours of PE optional header.
 #[repr(u16)]
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum PEType {
     Pe32 = 0x10b,
     Pe32Plus = 0x20b,
@@ -15,6 +16,7 @@ pub enum PEType {
 /// Machine types that a PE can target.
 #[repr(u16)]
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum MachineType {
     Unknown = 0x0,
     Alpha = 0x184,
@@ -49,7 +51,8 @@ pub enum MachineType {
 
 /// Possible Windows subsystems that a PE can require.
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum WindowsSubsystem {
     Unknown = 0,
     Native = 1,
@@ -70,6 +73,8 @@ pub enum WindowsSubsystem {
 bitflags! {
     /// PE characteristic flags given in the COFF header.
     #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
     pub struct CoffCharacteristics: u16 {
         const RelocsStripped = 0x0001;
         const ExecutableImage = 0x0002;
@@ -92,6 +97,8 @@ bitflags! {
 bitflags! {
     /// PE DLL characteristic flags given in the Windows optional header.
     #[derive(Debug, Clone, Copy)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize))]
+    #[cfg_attr(feature = "serde", serde(transparent))]
     pub struct DllCharacteristics: u16 {
         const HighEntropyVa = 0x0020;
         const DynamicBase = 0x0040;
@@ -107,9 +114,38 @@ bitflags! {
     }
 }
 
+/// The MS-DOS header at the start of an image.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DosHeader {
+    /// Always `MZ`.
+    pub magic: [u8; 2],
+    pub bytes_on_last_page: u16,
+    pub pages_in_file: u16,
+    pub relocations: u16,
+    pub size_of_header_in_paragraphs: u16,
+    pub min_extra_paragraphs: u16,
+    pub max_extra_paragraphs: u16,
+    pub initial_ss: u16,
+    pub initial_sp: u16,
+    pub checksum: u16,
+    pub initial_ip: u16,
+    pub initial_cs: u16,
+    pub address_of_relocation_table: u16,
+    pub overlay_number: u16,
+    pub reserved: [u16; 4],
+    pub oem_id: u16,
+    pub oem_info: u16,
+    pub reserved2: [u16; 10],
+    /// File offset of the PE signature (`e_lfanew`).
+    pub address_of_new_header: u32,
+}
+
 /// A COFF header.
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct CoffHeader {
     /// Targeted machine architecture.
     pub target_machine: MachineType,
@@ -130,6 +166,7 @@ pub struct CoffHeader {
 /// Standard optional header for the PE32 flavour.
 #[repr(C)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OptionalHeaderStandardFieldsPe32 {
     pub magic: u16,
     pub major_linker_version: u8,
@@ -145,6 +182,7 @@ pub struct OptionalHeaderStandardFieldsPe32 {
 /// Standard optional header for the PE32+ flavour.
 #[repr(C)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OptionalHeaderStandardFieldsPe32Plus {
     pub magic: u16,
     pub major_linker_version: u8,
@@ -159,6 +197,7 @@ pub struct OptionalHeaderStandardFieldsPe32Plus {
 /// Windows optional header for the PE32 flavour.
 #[repr(C)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OptionalHeaderWindowsFieldsPe32 {
     pub image_base: u32,
     pub section_alignment: u32,
@@ -186,6 +225,7 @@ pub struct OptionalHeaderWindowsFieldsPe32 {
 /// Windows optional header for the PE32+ flavour.
 #[repr(C)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OptionalHeaderWindowsFieldsPe32Plus {
     pub image_base: u64,
     pub section_alignment: u32,
@@ -212,15 +252,55 @@ pub struct OptionalHeaderWindowsFieldsPe32Plus {
 
 /// A header data directory.
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DataDirectory {
     pub virtual_address: u32,
     pub size: u32,
 }
 
+impl DataDirectory {
+    pub const EXPORT_TABLE: usize = 0;
+    pub const IMPORT_TABLE: usize = 1;
+    pub const RESOURCE_TABLE: usize = 2;
+    pub const EXCEPTION_TABLE: usize = 3;
+    pub const CERTIFICATE_TABLE: usize = 4;
+    pub const BASE_RELOCATION_TABLE: usize = 5;
+    pub const DEBUG: usize = 6;
+    pub const ARCHITECTURE: usize = 7;
+    pub const GLOBAL_PTR: usize = 8;
+    pub const TLS_TABLE: usize = 9;
+    pub const LOAD_CONFIG_TABLE: usize = 10;
+    pub const BOUND_IMPORT: usize = 11;
+    pub const IAT: usize = 12;
+    pub const DELAY_IMPORT_DESCRIPTOR: usize = 13;
+    pub const CLR_RUNTIME_HEADER: usize = 14;
+
+    /// Names of the data directories, by index.
+    pub const DISPLAY_NAMES: [&'static str; 16] = [
+        "Export Table",
+        "Import Table",
+        "Resource Table",
+        "Exception Table",
+        "Certificate Table",
+        "Base Relocation Table",
+        "Debug",
+        "Architecture",
+        "Global Ptr",
+        "TLS Table",
+        "Load Config Table",
+        "Bound Import",
+        "IAT",
+        "Delay Import Descriptor",
+        "CLR Runtime Header",
+        "Reserved",
+    ];
+}
+
 /// A full optional header for the PE32 flavour.
 #[repr(C)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OptionalHeaderPe32 {
     pub standard_fields: OptionalHeaderStandardFieldsPe32,
     pub windows_fields: OptionalHeaderWindowsFieldsPe32,
@@ -229,6 +309,7 @@ pub struct OptionalHeaderPe32 {
 /// A full optional header for the PE32+ flavour.
 #[repr(C)]
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct OptionalHeaderPe32Plus {
     pub standard_fields: OptionalHeaderStandardFieldsPe32Plus,
     pub windows_fields: OptionalHeaderWindowsFieldsPe32Plus,
@@ -270,7 +351,7 @@ impl HeadersPe32Plus {
 
 impl PEHeader for CoffHeader {
     fn coff_header(&self) -> &CoffHeader {
-        &self
+        self
     }
 
     fn optional_header_pe32(&self) -> Option<&OptionalHeaderPe32> {
@@ -322,6 +403,69 @@ impl PEHeader for HeadersPe32Plus {
     }
 }
 
+impl TryFrom<u16> for MachineType {
+    type Error = u16;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0x0 => Ok(Self::Unknown),
+            0x184 => Ok(Self::Alpha),
+            0x284 => Ok(Self::Alpha64),
+            0x1d3 => Ok(Self::Am33),
+            0x8664 => Ok(Self::Amd64),
+            0x1c0 => Ok(Self::Arm),
+            0xaa64 => Ok(Self::Arm64),
+            0x1c4 => Ok(Self::ArmNT),
+            0xebc => Ok(Self::Ebc),
+            0x14c => Ok(Self::I386),
+            0x200 => Ok(Self::Ia64),
+            0x6232 => Ok(Self::LoongArch32),
+            0x6264 => Ok(Self::LoongArch64),
+            0x9041 => Ok(Self::M32R),
+            0x266 => Ok(Self::Mips16),
+            0x366 => Ok(Self::MipsFpu),
+            0x466 => Ok(Self::MipsFpu16),
+            0x1f0 => Ok(Self::PowerPc),
+            0x1f1 => Ok(Self::PowerPcFp),
+            0x166 => Ok(Self::R4000),
+            0x5032 => Ok(Self::RiscV32),
+            0x5064 => Ok(Self::RiscV64),
+            0x5128 => Ok(Self::RiscV128),
+            0x1a2 => Ok(Self::Sh3),
+            0x1a3 => Ok(Self::Sh3Dsp),
+            0x1a6 => Ok(Self::Sh4),
+            0x1a8 => Ok(Self::Sh5),
+            0x1c2 => Ok(Self::Thumb),
+            0x169 => Ok(Self::WceMipsV2),
+            _ => Err(value),
+        }
+    }
+}
+
+impl TryFrom<u16> for WindowsSubsystem {
+    type Error = u16;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Unknown),
+            1 => Ok(Self::Native),
+            2 => Ok(Self::WindowsGui),
+            3 => Ok(Self::WindowsCui),
+            5 => Ok(Self::Os2Cui),
+            7 => Ok(Self::PosixCui),
+            8 => Ok(Self::NativeWindows),
+            9 => Ok(Self::WindowsCeGui),
+            10 => Ok(Self::EfiApplication),
+            11 => Ok(Self::EfiBootServiceDriver),
+            12 => Ok(Self::EfiRuntimeDriver),
+            13 => Ok(Self::EfiRom),
+            14 => Ok(Self::Xbox),
+            16 => Ok(Self::WindowsBootApplication),
+            _ => Err(value),
+        }
+    }
+}
+
 impl Display for MachineType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match *self {
@@ -0,0 +1,324 @@
+use super::body::{rva_to_file_offset, SectionHeader};
+use super::err::PEError;
+use super::imports::get_imports;
+use super::internal::agnostic_fio::read_up_to;
+use super::rich::get_rich_header;
+use super::traits::PEHeader;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::path::Path;
+
+/// The built-in rules, in the same format as user rule files.
+const BUILTIN_RULES: &str = include_str!("signatures.toml");
+/// Most bytes read from the entry point, which bounds how long an entry point pattern can be.
+const MAX_ENTRY_POINT_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleKind {
+    /// Compresses or encrypts the image, unpacking it at run time.
+    Packer,
+    /// Obfuscates or virtualises code to resist analysis.
+    Protector,
+    /// A compiler, linker or language runtime.
+    Toolchain,
+}
+
+/// A rule as written in a rule file. See `signatures.toml` for the meaning of each field.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    name: String,
+    kind: RuleKind,
+    description: Option<String>,
+    #[serde(default)]
+    sections: Vec<String>,
+    entry_point: Option<String>,
+    #[serde(default)]
+    imports: Vec<String>,
+    #[serde(default)]
+    strings: Vec<String>,
+    rich: Option<bool>,
+    #[serde(default)]
+    rich_products: Vec<String>,
+    linker_min: Option<String>,
+    linker_max: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleFile {
+    #[serde(default)]
+    rule: Vec<RawRule>,
+}
+
+/// A checked identification rule. A rule matches when all of its conditions hold.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub kind: RuleKind,
+    pub description: Option<String>,
+    sections: Vec<String>,
+    /// Bytes at the entry point, with `None` matching any byte.
+    entry_point: Option<Vec<Option<u8>>>,
+    /// Lowercased DLL names, or `dll!function` pairs with the DLL lowercased.
+    imports: Vec<String>,
+    strings: Vec<String>,
+    rich: Option<bool>,
+    rich_products: Vec<String>,
+    linker_min: Option<(u8, u8)>,
+    linker_max: Option<(u8, u8)>,
+}
+
+/// A rule that matched, with the conditions that made it match.
+#[derive(Debug, Clone, Serialize)]
+pub struct Detection {
+    pub name: String,
+    pub kind: RuleKind,
+    pub description: Option<String>,
+    pub reasons: Vec<String>,
+}
+
+/// What the rules look at, gathered once per file.
+struct Facts {
+    section_names: HashSet<String>,
+    entry_point_bytes: Vec<u8>,
+    imports: HashSet<String>,
+    data: Vec<u8>,
+    rich_products: Option<Vec<&'static str>>,
+    linker_version: (u8, u8),
+}
+
+impl Display for RuleKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleKind::Packer => f.pad("packer"),
+            RuleKind::Protector => f.pad("protector"),
+            RuleKind::Toolchain => f.pad("toolchain"),
+        }
+    }
+}
+
+impl TryFrom<RawRule> for Rule {
+    type Error = String;
+
+    fn try_from(raw: RawRule) -> Result<Self, Self::Error> {
+        let fail = |msg: String| format!("rule {}: {}", raw.name, msg);
+        let entry_point = raw.entry_point.as_deref().map(parse_pattern).transpose().map_err(fail)?;
+        let linker_min = raw.linker_min.as_deref().map(parse_version).transpose().map_err(fail)?;
+        let linker_max = raw.linker_max.as_deref().map(parse_version).transpose().map_err(fail)?;
+        let has_condition = !raw.sections.is_empty()
+            || entry_point.is_some()
+            || !raw.imports.is_empty()
+            || !raw.strings.is_empty()
+            || raw.rich.is_some()
+            || !raw.rich_products.is_empty()
+            || linker_min.is_some()
+            || linker_max.is_some();
+        if !has_condition {
+            return Err(fail(String::from("has no conditions, so would match everything")));
+        }
+
+        Ok(Rule {
+            entry_point,
+            imports: raw.imports.iter().map(|import| normalise_import(import)).collect(),
+            linker_min,
+            linker_max,
+            name: raw.name,
+            kind: raw.kind,
+            description: raw.description,
+            sections: raw.sections,
+            strings: raw.strings,
+            rich: raw.rich,
+            rich_products: raw.rich_products,
+        })
+    }
+}
+
+impl Rule {
+    /// Checks the rule against a file, returning why it matched or `None` if it did not.
+    fn check(&self, facts: &Facts) -> Option<Vec<String>> {
+        let mut reasons: Vec<String> = Vec::new();
+        if !self.sections.is_empty() {
+            if !self.sections.iter().all(|name| facts.section_names.contains(name)) {
+                return None;
+            }
+            reasons.push(format!("sections {}", self.sections.join(", ")));
+        }
+        if let Some(pattern) = &self.entry_point {
+            let matches = pattern.len() <= facts.entry_point_bytes.len()
+                && pattern
+                    .iter()
+                    .zip(&facts.entry_point_bytes)
+                    .all(|(expected, actual)| expected.is_none_or(|b| b == *actual));
+            if !matches {
+                return None;
+            }
+            reasons.push(String::from("entry point bytes"));
+        }
+        if !self.imports.is_empty() {
+            if !self.imports.iter().all(|import| facts.imports.contains(import)) {
+                return None;
+            }
+            reasons.push(format!("imports {}", self.imports.join(", ")));
+        }
+        if !self.strings.is_empty() {
+            if !self.strings.iter().all(|string| contains_bytes(&facts.data, string.as_bytes())) {
+                return None;
+            }
+            reasons.push(format!(
+                "strings {}",
+                self.strings.iter().map(|string| format!("{:?}", string)).collect::<Vec<_>>().join(", ")
+            ));
+        }
+        if let Some(rich) = self.rich {
+            if rich != facts.rich_products.is_some() {
+                return None;
+            }
+            reasons.push(String::from(if rich { "Rich header" } else { "no Rich header" }));
+        }
+        if !self.rich_products.is_empty() {
+            let products = facts.rich_products.as_deref().unwrap_or_default();
+            if !self
+                .rich_products
+                .iter()
+                .all(|prefix| products.iter().any(|product| product.starts_with(prefix.as_str())))
+            {
+                return None;
+            }
+            reasons.push(format!("Rich header lists {}", self.rich_products.join(", ")));
+        }
+        if self.linker_min.is_some() || self.linker_max.is_some() {
+            let version = facts.linker_version;
+            if self.linker_min.is_some_and(|min| version < min) || self.linker_max.is_some_and(|max| version > max) {
+                return None;
+            }
+            reasons.push(format!("linker version {}.{}", version.0, version.1));
+        }
+        Some(reasons)
+    }
+}
+
+/// Parses the built-in rules.
+pub fn builtin_rules() -> Result<Vec<Rule>, PEError> {
+    parse_rules(BUILTIN_RULES).map_err(|err| PEError::DeserialiseError(format!("built-in rules: {}", err)))
+}
+
+/// Reads and parses a rule file.
+pub fn load_rules(path: &Path) -> Result<Vec<Rule>, PEError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) => return Err(PEError::DeserialiseError(format!("{}: {}", path.display(), err))),
+    };
+    parse_rules(&text).map_err(|err| PEError::DeserialiseError(format!("{}: {}", path.display(), err)))
+}
+
+/// Runs the rules against an image, returning the ones that matched in rule order.
+///
+/// Packed images often have malformed import tables and Rich headers, so these are treated as absent if they cannot be
+/// read.
+pub fn identify(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader], rules: &[Rule]) -> Result<Vec<Detection>, PEError> {
+    let (entry_point, linker_version) = match (headers.optional_header_pe32(), headers.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => (
+            pe32_header.standard_fields.address_of_entry_point,
+            (
+                pe32_header.standard_fields.major_linker_version,
+                pe32_header.standard_fields.minor_linker_version,
+            ),
+        ),
+        (_, Some(pe32plus_header)) => (
+            pe32plus_header.standard_fields.address_of_entry_point,
+            (
+                pe32plus_header.standard_fields.major_linker_version,
+                pe32plus_header.standard_fields.minor_linker_version,
+            ),
+        ),
+        _ => return Err(PEError::DeserialiseError(String::from("object files cannot be identified"))),
+    };
+    let entry_point_bytes = match rva_to_file_offset(section_table, entry_point).filter(|_| entry_point != 0) {
+        Some(offset) => read_up_to(fh, offset as u64, MAX_ENTRY_POINT_LEN).map_err(|err| PEError::DeserialiseError(err.to_string()))?,
+        None => Vec::new(),
+    };
+    let mut imports: HashSet<String> = HashSet::new();
+    for dll in get_imports(fh, headers, section_table).unwrap_or_default() {
+        let dll_name = dll.dll_name.to_lowercase();
+        for function in dll.functions.iter().filter_map(|function| function.name.as_ref()) {
+            imports.insert(format!("{}!{}", dll_name, function));
+        }
+        imports.insert(dll_name);
+    }
+    let rich_products = get_rich_header(fh)
+        .ok()
+        .flatten()
+        .map(|rich_header| rich_header.entries.iter().filter_map(|entry| entry.product_name()).collect());
+    let data = match read_up_to(fh, 0, usize::MAX) {
+        Ok(data) => data,
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+
+    let facts = Facts {
+        section_names: section_table.iter().map(|section| section.name_str()).collect(),
+        entry_point_bytes,
+        imports,
+        data,
+        rich_products,
+        linker_version,
+    };
+    Ok(rules
+        .iter()
+        .filter_map(|rule| {
+            rule.check(&facts).map(|reasons| Detection {
+                name: rule.name.clone(),
+                kind: rule.kind,
+                description: rule.description.clone(),
+                reasons,
+            })
+        })
+        .collect())
+}
+
+fn parse_rules(text: &str) -> Result<Vec<Rule>, String> {
+    let file: RuleFile = toml::from_str(text).map_err(|err| err.to_string())?;
+    file.rule.into_iter().map(Rule::try_from).collect()
+}
+
+/// Parses a pattern of hex bytes separated by whitespace, such as `60 BE ?? ??`.
+fn parse_pattern(pattern: &str) -> Result<Vec<Option<u8>>, String> {
+    let bytes = pattern
+        .split_whitespace()
+        .map(|byte| match byte {
+            "??" => Ok(None),
+            _ if byte.len() == 2 => u8::from_str_radix(byte, 16).map(Some).map_err(|_| format!("bad byte {:?} in pattern", byte)),
+            _ => Err(format!("bad byte {:?} in pattern", byte)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if bytes.is_empty() || bytes.len() > MAX_ENTRY_POINT_LEN {
+        return Err(format!("entry point pattern must be 1 to {} bytes", MAX_ENTRY_POINT_LEN));
+    }
+    Ok(bytes)
+}
+
+/// Parses a linker version such as `14.20`.
+fn parse_version(version: &str) -> Result<(u8, u8), String> {
+    let (major, minor) = version
+        .split_once('.')
+        .ok_or_else(|| format!("linker version {:?} is not major.minor", version))?;
+    match (major.parse::<u8>(), minor.parse::<u8>()) {
+        (Ok(major), Ok(minor)) => Ok((major, minor)),
+        _ => Err(format!("linker version {:?} is not major.minor", version)),
+    }
+}
+
+/// Lowercases the DLL part of an import condition, leaving the function name as it is.
+fn normalise_import(import: &str) -> String {
+    match import.split_once('!') {
+        Some((dll, function)) => format!("{}!{}", dll.to_lowercase(), function),
+        None => import.to_lowercase(),
+    }
+}
+
+fn contains_bytes(haystack: &[u8], needle: &[u8]) -> bool {
+    needle.is_empty() || haystack.windows(needle.len()).any(|window| window == needle)
+}
@@ -0,0 +1,196 @@
+use super::body::SectionFlags;
+use super::err::PEError;
+use super::file::PeFile;
+use super::headers::DataDirectory;
+use super::imports::{ImportDescriptor, ImportedDll, ImportedFunction, MAX_DESCRIPTORS};
+use super::section_edit::add_section;
+use super::traits::PEHeader;
+use std::mem::{size_of, transmute};
+
+/// Name of the section that a rebuilt import directory is put in.
+pub const IMPORT_SECTION_NAME: &str = ".idata2";
+
+/// Adds an import of named functions from a DLL, returning the new import with the RVA of each function's slot in
+/// the import address table.
+///
+/// The import directory is rebuilt in a new section, holding a copy of the existing descriptors followed by the new
+/// one, and the new DLL's lookup table, address table and names. The existing descriptors still point at their
+/// original lookup and address tables, so code that calls through those is unaffected. Those address tables are
+/// usually in a read-only section that the loader only makes writable while binding because the IAT data directory
+/// covers them, and a directory can only cover one range, so the directory is cleared to have the loader unprotect each
+/// descriptor's address table in turn instead. Bound imports are dropped, as binding would skip resolving the new import.
+pub fn add_import(pe_file: &mut PeFile, dll_name: &str, functions: &[String]) -> Result<ImportedDll, PEError> {
+    if pe_file.optional_header.is_none() {
+        return Err(PEError::InvalidEdit(String::from("imports can only be added to images, not object files")));
+    }
+    if pe_file.data_directories.len() <= DataDirectory::IAT {
+        return Err(PEError::InvalidEdit(String::from("the image has no IAT data directory")));
+    }
+    if dll_name.is_empty() || functions.is_empty() || functions.iter().any(|function| function.is_empty()) {
+        return Err(PEError::InvalidEdit(String::from("an import needs a DLL name and at least one function name")));
+    }
+    let existing = get_import_descriptors(pe_file)?;
+    if existing.iter().any(|(_, existing_name)| existing_name.eq_ignore_ascii_case(dll_name)) {
+        return Err(PEError::InvalidEdit(format!("the image already imports {}", dll_name)));
+    }
+
+    // the descriptors, including the new one and the terminator, then the lookup and address tables, then the names
+    let thunk_size = if pe_file.is_pe32plus() { 8 } else { 4 };
+    let descriptors_len = (existing.len() + 2) * size_of::<ImportDescriptor>();
+    let lookup_table_offset = descriptors_len.next_multiple_of(thunk_size);
+    let address_table_offset = lookup_table_offset + (functions.len() + 1) * thunk_size;
+    let mut hint_name_offset = address_table_offset + (functions.len() + 1) * thunk_size;
+    let mut hint_name_offsets: Vec<usize> = Vec::with_capacity(functions.len());
+    for function in functions {
+        hint_name_offsets.push(hint_name_offset);
+        // a two byte hint, then the null-terminated name, padded to an even length
+        hint_name_offset += (2 + function.len() + 1).next_multiple_of(2);
+    }
+    let dll_name_offset = hint_name_offset;
+    let len = dll_name_offset + dll_name.len() + 1;
+
+    let characteristics = SectionFlags::CntInitialisedData | SectionFlags::MemRead | SectionFlags::MemWrite;
+    let index = add_section(pe_file, IMPORT_SECTION_NAME, characteristics, &vec![0; len])?;
+    let base_rva = pe_file.sections[index].header.virtual_address;
+    let rva = |offset: usize| base_rva + offset as u32;
+
+    let descriptor = ImportDescriptor {
+        import_lookup_table_rva: rva(lookup_table_offset),
+        time_date_stamp: 0,
+        forwarder_chain: 0,
+        name_rva: rva(dll_name_offset),
+        import_address_table_rva: rva(address_table_offset),
+    };
+    let data = &mut pe_file.sections[index].data;
+    for (i, (descriptor, _)) in existing.iter().enumerate() {
+        // bound descriptors have their time stamp set, and would otherwise keep the addresses they were bound to
+        let descriptor = ImportDescriptor {
+            time_date_stamp: 0,
+            ..*descriptor
+        };
+        put_descriptor(data, i * size_of::<ImportDescriptor>(), &descriptor);
+    }
+    put_descriptor(data, existing.len() * size_of::<ImportDescriptor>(), &descriptor);
+    let mut imported_functions: Vec<ImportedFunction> = Vec::with_capacity(functions.len());
+    for (i, (function, &offset)) in functions.iter().zip(&hint_name_offsets).enumerate() {
+        // the loader overwrites the address table, but until then it is the same as the lookup table
+        let thunk = &rva(offset).to_le_bytes();
+        data[lookup_table_offset + i * thunk_size..][..4].copy_from_slice(thunk);
+        data[address_table_offset + i * thunk_size..][..4].copy_from_slice(thunk);
+        data[offset + 2..][..function.len()].copy_from_slice(function.as_bytes());
+        imported_functions.push(ImportedFunction {
+            name: Some(function.clone()),
+            ordinal: None,
+            hint: Some(0),
+            thunk_rva: rva(address_table_offset + i * thunk_size),
+        });
+    }
+    data[dll_name_offset..][..dll_name.len()].copy_from_slice(dll_name.as_bytes());
+
+    pe_file.data_directories[DataDirectory::IMPORT_TABLE] = DataDirectory {
+        virtual_address: base_rva,
+        size: descriptors_len as u32,
+    };
+    pe_file.data_directories[DataDirectory::BOUND_IMPORT] = DataDirectory { virtual_address: 0, size: 0 };
+    pe_file.data_directories[DataDirectory::IAT] = DataDirectory { virtual_address: 0, size: 0 };
+
+    Ok(ImportedDll {
+        descriptor,
+        dll_name: String::from(dll_name),
+        functions: imported_functions,
+    })
+}
+
+/// Reads the import descriptors, up to the all-zero one that ends them, along with the name of each one's DLL.
+fn get_import_descriptors(pe_file: &PeFile) -> Result<Vec<(ImportDescriptor, String)>, PEError> {
+    let Some(import_dir) = pe_file.data_directory(DataDirectory::IMPORT_TABLE).copied() else {
+        return Ok(Vec::new());
+    };
+    let mut descriptors: Vec<(ImportDescriptor, String)> = Vec::new();
+    let mut descriptor_rva = import_dir.virtual_address;
+    for _ in 0..MAX_DESCRIPTORS {
+        let bytes = data_at_rva(pe_file, descriptor_rva, size_of::<ImportDescriptor>())?;
+        let descriptor: ImportDescriptor = unsafe { transmute::<[u8; 20], ImportDescriptor>(bytes[..20].try_into().unwrap()) };
+        // the table is terminated by an all-zero descriptor
+        if descriptor.name_rva == 0 && descriptor.import_address_table_rva == 0 {
+            break;
+        }
+        let name = data_at_rva(pe_file, descriptor.name_rva, 1)?;
+        let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+        descriptors.push((descriptor, String::from_utf8_lossy(name).into_owned()));
+        descriptor_rva = descriptor_rva.wrapping_add(size_of::<ImportDescriptor>() as u32);
+    }
+    Ok(descriptors)
+}
+
+/// The section data from an RVA to the end of its section, which must hold at least `len` bytes.
+fn data_at_rva(pe_file: &PeFile, rva: u32, len: usize) -> Result<&[u8], PEError> {
+    match pe_file.find_rva(rva) {
+        Some((index, start)) if pe_file.sections[index].data.len() - start >= len => Ok(&pe_file.sections[index].data[start..]),
+        _ => Err(PEError::DeserialiseError(format!(
+            "import data at RVA {:08X}h is not in any section's raw data",
+            rva
+        ))),
+    }
+}
+
+fn put_descriptor(data: &mut [u8], offset: usize, descriptor: &ImportDescriptor) {
+    let fields = [
+        descriptor.import_lookup_table_rva,
+        descriptor.time_date_stamp,
+        descriptor.forwarder_chain,
+        descriptor.name_rva,
+        descriptor.import_address_table_rva,
+    ];
+    for (i, field) in fields.iter().enumerate() {
+        data[offset + i * 4..][..4].copy_from_slice(&field.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::imports::get_imports;
+    use crate::pe::internal::testdata;
+
+    /// The name, hint and IAT slot of each function a DLL imports.
+    fn functions(dll: &ImportedDll) -> Vec<(Option<String>, Option<u16>, u32)> {
+        dll.functions
+            .iter()
+            .map(|function| (function.name.clone(), function.hint, function.thunk_rva))
+            .collect()
+    }
+
+    #[test]
+    fn added_import_is_read_back() {
+        for (name, thunk_size) in [("test-x64.dll", 8), ("test-x86.dll", 4)] {
+            let mut pe_file = testdata::read_pe_file(name);
+            let (mut fh, headers, section_table) = testdata::reparse(&pe_file);
+            let existing = get_imports(&mut fh, headers.as_ref(), &section_table).unwrap();
+            let new_functions = [String::from("MessageBoxA"), String::from("MessageBoxW")];
+            let added = add_import(&mut pe_file, "USER32.dll", &new_functions).unwrap();
+            assert!(add_import(&mut pe_file, "kernel32.dll", &new_functions).is_err());
+            assert!(add_import(&mut pe_file, "user32.dll", &new_functions).is_err());
+
+            let (mut fh, headers, section_table) = testdata::reparse(&pe_file);
+            let imports = get_imports(&mut fh, headers.as_ref(), &section_table).unwrap();
+            assert_eq!(imports.len(), 2, "{}", name);
+            assert_eq!(imports[0].dll_name, existing[0].dll_name);
+            assert_eq!(functions(&imports[0]), functions(&existing[0]));
+            assert_eq!(imports[1].dll_name, "USER32.dll");
+            assert_eq!(functions(&imports[1]), functions(&added));
+            let names: Vec<Option<String>> = imports[1].functions.iter().map(|function| function.name.clone()).collect();
+            assert_eq!(names, [Some(String::from("MessageBoxA")), Some(String::from("MessageBoxW"))]);
+
+            let section = &section_table[section_table.len() - 1];
+            assert_eq!(section.name_str(), IMPORT_SECTION_NAME);
+            let import_dir = headers.data_directories().unwrap()[DataDirectory::IMPORT_TABLE];
+            assert_eq!(import_dir.virtual_address, section.virtual_address);
+            assert_eq!(import_dir.size as usize, 3 * size_of::<ImportDescriptor>());
+            // the original address tables are in read-only sections, so the loader must unprotect each one in turn
+            let iat_dir = headers.data_directories().unwrap()[DataDirectory::IAT];
+            assert_eq!((iat_dir.virtual_address, iat_dir.size), (0, 0));
+            assert_eq!(added.functions[1].thunk_rva - added.functions[0].thunk_rva, thunk_size);
+        }
+    }
+}
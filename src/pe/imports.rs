@@ -0,0 +1,151 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::headers::DataDirectory;
+use super::internal::agnostic_fio::{read_cstring, read_exact};
+use super::internal::rva::{offset_rva, read_name_at_rva, rva_to_addr};
+use super::traits::PEHeader;
+use std::fs::File;
+use std::io;
+use std::mem::{size_of, transmute};
+
+/// Longest function name that will be read.
+const MAX_NAME_LEN: usize = 4096;
+/// Most thunks that will be read for a single DLL, to bound malformed tables.
+const MAX_THUNKS: usize = 0x10000;
+/// Most descriptors that will be read, to bound malformed tables.
+pub(crate) const MAX_DESCRIPTORS: usize = 0x1000;
+
+/// An entry in the import directory table (`IMAGE_IMPORT_DESCRIPTOR`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ImportDescriptor {
+    /// RVA of the import lookup table, which is left untouched by the loader.
+    pub import_lookup_table_rva: u32,
+    /// Zero until bound, then the timestamp of the DLL the imports were bound against.
+    pub time_date_stamp: u32,
+    pub forwarder_chain: u32,
+    pub name_rva: u32,
+    /// RVA of the import address table, which the loader overwrites with the imported addresses.
+    pub import_address_table_rva: u32,
+}
+
+/// A single function imported from a DLL.
+#[derive(Debug, Clone)]
+pub struct ImportedFunction {
+    /// Name of the function, or `None` if it is imported by ordinal.
+    pub name: Option<String>,
+    /// Ordinal of the function, if it is imported by ordinal.
+    pub ordinal: Option<u16>,
+    /// Hint into the DLL's export name table, if it is imported by name.
+    pub hint: Option<u16>,
+    /// RVA of the function's slot in the import address table.
+    pub thunk_rva: u32,
+}
+
+/// The functions imported from one DLL.
+#[derive(Debug, Clone)]
+pub struct ImportedDll {
+    pub descriptor: ImportDescriptor,
+    pub dll_name: String,
+    pub functions: Vec<ImportedFunction>,
+}
+
+impl ImportedFunction {
+    /// The function's name, or `#<ordinal>` if it is imported by ordinal.
+    pub fn display_name(&self) -> String {
+        match (&self.name, self.ordinal) {
+            (Some(name), _) => name.clone(),
+            (None, Some(ordinal)) => format!("#{}", ordinal),
+            (None, None) => String::from("?"),
+        }
+    }
+}
+
+/// Reads the import directory, returning an empty list if the image has no imports.
+pub fn get_imports(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Vec<ImportedDll>, PEError> {
+    let import_dir = match headers.data_directory(DataDirectory::IMPORT_TABLE) {
+        Some(dir) => dir,
+        None => return Ok(Vec::new()),
+    };
+
+    match do_get_imports(fh, import_dir.virtual_address, headers.is_pe32plus(), section_table) {
+        Ok(imports) => Ok(imports),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn do_get_imports(fh: &mut File, import_dir_rva: u32, is_pe32plus: bool, section_table: &[SectionHeader]) -> io::Result<Vec<ImportedDll>> {
+    let mut ret: Vec<ImportedDll> = Vec::new();
+    let mut descriptor_rva = import_dir_rva;
+    for _ in 0..MAX_DESCRIPTORS {
+        let descriptor_addr = rva_to_addr(section_table, descriptor_rva)?;
+        let descriptor: ImportDescriptor = unsafe { transmute(read_exact::<{ size_of::<ImportDescriptor>() }>(fh, descriptor_addr)?) };
+
+        // the table is terminated by an all-zero descriptor
+        if descriptor.name_rva == 0 && descriptor.import_address_table_rva == 0 {
+            break;
+        }
+
+        let dll_name = read_name_at_rva(fh, section_table, descriptor.name_rva)?;
+        let functions = get_imported_functions(fh, &descriptor, is_pe32plus, section_table)?;
+        ret.push(ImportedDll {
+            descriptor,
+            dll_name,
+            functions,
+        });
+        descriptor_rva = offset_rva(descriptor_rva, size_of::<ImportDescriptor>() as u32)?;
+    }
+    Ok(ret)
+}
+
+fn get_imported_functions(
+    fh: &mut File,
+    descriptor: &ImportDescriptor,
+    is_pe32plus: bool,
+    section_table: &[SectionHeader],
+) -> io::Result<Vec<ImportedFunction>> {
+    // prefer the lookup table, since the address table may have been bound
+    let table_rva = if descriptor.import_lookup_table_rva != 0 {
+        descriptor.import_lookup_table_rva
+    } else {
+        descriptor.import_address_table_rva
+    };
+    let thunk_size: u32 = if is_pe32plus { 8 } else { 4 };
+
+    let mut ret: Vec<ImportedFunction> = Vec::new();
+    for i in 0..MAX_THUNKS as u32 {
+        let thunk_addr = rva_to_addr(section_table, offset_rva(table_rva, i * thunk_size)?)?;
+        let (thunk, by_ordinal) = if is_pe32plus {
+            let thunk = u64::from_le_bytes(read_exact(fh, thunk_addr)?);
+            (thunk & 0x7fffffff_ffffffff, thunk & 0x80000000_00000000 != 0)
+        } else {
+            let thunk = u32::from_le_bytes(read_exact(fh, thunk_addr)?) as u64;
+            (thunk & 0x7fffffff, thunk & 0x80000000 != 0)
+        };
+        if thunk == 0 && !by_ordinal {
+            break;
+        }
+
+        let thunk_rva = offset_rva(descriptor.import_address_table_rva, i * thunk_size)?;
+        if by_ordinal {
+            ret.push(ImportedFunction {
+                name: None,
+                ordinal: Some(thunk as u16),
+                hint: None,
+                thunk_rva,
+            });
+        } else {
+            // a hint/name table entry: a two byte hint, then the name
+            let hint_name_addr = rva_to_addr(section_table, thunk as u32)?;
+            let hint = u16::from_le_bytes(read_exact(fh, hint_name_addr)?);
+            let name = read_cstring(fh, hint_name_addr + 2, MAX_NAME_LEN)?;
+            ret.push(ImportedFunction {
+                name: Some(String::from_utf8_lossy(&name).into_owned()),
+                ordinal: None,
+                hint: Some(hint),
+                thunk_rva,
+            });
+        }
+    }
+    Ok(ret)
+}
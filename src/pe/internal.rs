@@ -1 +1,5 @@
 pub(crate) mod agnostic_fio;
+pub(crate) mod ordinals;
+pub(crate) mod rva;
+#[cfg(test)]
+pub(crate) mod testdata;
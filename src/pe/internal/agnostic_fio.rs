@@ -30,3 +30,79 @@ pub fn read_exact<const N: usize>(fh: &mut File, offset: u64) -> io::Result<[u8;
 
     Ok(buf)
 }
+
+#[cfg(windows)]
+pub fn read_vec(fh: &mut File, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    check_range(fh, offset, len as u64)?;
+    let mut buf: Vec<u8> = vec![0_u8; len];
+    let bytes_read = fh.seek_read(&mut buf, offset)?;
+    if bytes_read != len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "reached EOF early in read_vec"));
+    }
+    Ok(buf)
+}
+
+#[cfg(unix)]
+pub fn read_vec(fh: &mut File, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    check_range(fh, offset, len as u64)?;
+    let mut buf: Vec<u8> = vec![0_u8; len];
+    let seek_pos = fh.seek(SeekFrom::Start(offset))?;
+    if seek_pos != offset {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Seek failed in read_vec"));
+    }
+
+    fh.read_exact(&mut buf)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Reached EOF early in read_vec"))?;
+    Ok(buf)
+}
+
+#[cfg(windows)]
+pub fn read_up_to(fh: &mut File, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let len = len.min(fh.metadata()?.len().saturating_sub(offset) as usize);
+    let mut buf: Vec<u8> = vec![0_u8; len];
+    let mut filled = 0;
+    while filled < len {
+        let bytes_read = fh.seek_read(&mut buf[filled..], offset + filled as u64)?;
+        if bytes_read == 0 {
+            break;
+        }
+        filled += bytes_read;
+    }
+    buf.truncate(filled);
+    Ok(buf)
+}
+
+#[cfg(unix)]
+pub fn read_up_to(fh: &mut File, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut buf: Vec<u8> = Vec::with_capacity(len.min(fh.metadata()?.len().saturating_sub(offset) as usize));
+    fh.seek(SeekFrom::Start(offset))?;
+    fh.take(len as u64).read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads a null-terminated string of at most `max_len` bytes, not including the terminator.
+pub fn read_cstring(fh: &mut File, offset: u64, max_len: usize) -> io::Result<Vec<u8>> {
+    let mut ret: Vec<u8> = Vec::new();
+    let mut addr = offset;
+    while ret.len() < max_len {
+        let chunk = read_up_to(fh, addr, 64.min(max_len - ret.len()))?;
+        if chunk.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "reached EOF early in read_cstring"));
+        }
+        if let Some(end) = chunk.iter().position(|&b| b == 0) {
+            ret.extend_from_slice(&chunk[..end]);
+            return Ok(ret);
+        }
+        ret.extend_from_slice(&chunk);
+        addr += chunk.len() as u64;
+    }
+    Ok(ret)
+}
+
+/// Checks that `len` bytes from `offset` are within the file, so that untrusted sizes can be checked before allocating.
+pub fn check_range(fh: &mut File, offset: u64, len: u64) -> io::Result<()> {
+    match offset.checked_add(len) {
+        Some(end) if end <= fh.metadata()?.len() => Ok(()),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "range runs past the end of the file")),
+    }
+}
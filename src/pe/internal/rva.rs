@@ -0,0 +1,37 @@
+use super::agnostic_fio::read_cstring;
+use crate::pe::body::{rva_to_file_offset, SectionHeader};
+use std::fs::File;
+use std::io;
+
+/// Longest name that will be read from a table.
+const MAX_NAME_LEN: usize = 4096;
+
+/// Maps an RVA to a file offset, failing if it is not backed by data in the file.
+pub fn rva_to_addr(section_table: &[SectionHeader], rva: u32) -> io::Result<u64> {
+    match rva_to_file_offset(section_table, rva) {
+        Some(offset) => Ok(offset as u64),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("RVA {:08X}h is not backed by file data", rva),
+        )),
+    }
+}
+
+/// Adds an offset to an RVA, such as that of an entry in a table, failing if a crafted table RVA pushes it past the
+/// end of the address space.
+pub fn offset_rva(rva: u32, offset: u32) -> io::Result<u32> {
+    match rva.checked_add(offset) {
+        Some(rva) => Ok(rva),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("RVA {:08X}h plus {:X}h is past the end of the address space", rva, offset),
+        )),
+    }
+}
+
+/// Reads a null-terminated name at an RVA.
+pub fn read_name_at_rva(fh: &mut File, section_table: &[SectionHeader], rva: u32) -> io::Result<String> {
+    let addr = rva_to_addr(section_table, rva)?;
+    let name = read_cstring(fh, addr, MAX_NAME_LEN)?;
+    Ok(String::from_utf8_lossy(&name).into_owned())
+}
@@ -0,0 +1,45 @@
+//! Helpers for tests that read the images in `testdata`.
+
+use crate::pe::body::SectionHeader;
+use crate::pe::deser::{get_headers_from_file, get_pe_file, get_section_table};
+use crate::pe::file::PeFile;
+use crate::pe::ser::pe_file_to_bytes;
+use crate::pe::traits::PEHeader;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the temporary files written, to give each a unique name.
+static TEMP_FILES: AtomicUsize = AtomicUsize::new(0);
+
+pub(crate) fn path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("testdata").join(name)
+}
+
+pub(crate) fn read(name: &str) -> Vec<u8> {
+    fs::read(path(name)).unwrap()
+}
+
+pub(crate) fn read_pe_file(name: &str) -> PeFile {
+    get_pe_file(&mut File::open(path(name)).unwrap()).unwrap()
+}
+
+/// Writes bytes to a temporary file and opens it, so that they can be read back with the `deser` functions.
+pub(crate) fn open_bytes(bytes: &[u8]) -> File {
+    let id = TEMP_FILES.fetch_add(1, Ordering::Relaxed);
+    let temp_path = std::env::temp_dir().join(format!("pepeek-test-{}-{}", process::id(), id));
+    fs::write(&temp_path, bytes).unwrap();
+    let fh = File::open(&temp_path).unwrap();
+    // an open file can't be removed on Windows, which only leaves it behind in the temporary directory
+    let _ = fs::remove_file(&temp_path);
+    fh
+}
+
+/// Serialises a `PeFile` and parses its headers and section table again, returning the file to read the rest from.
+pub(crate) fn reparse(pe_file: &PeFile) -> (File, Box<dyn PEHeader>, Vec<SectionHeader>) {
+    let mut fh = open_bytes(&pe_file_to_bytes(pe_file).unwrap());
+    let headers = get_headers_from_file(&mut fh).unwrap();
+    let section_table = get_section_table(&mut fh, headers.as_ref()).unwrap();
+    (fh, headers, section_table)
+}
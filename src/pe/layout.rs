@@ -0,0 +1,136 @@
+use super::body::{rva_to_file_offset, SectionHeader};
+use super::coff::CoffSymbol;
+use super::err::PEError;
+use super::headers::{CoffHeader, DataDirectory};
+use super::internal::agnostic_fio::read_exact;
+use super::overlay::get_overlay;
+use super::rich::get_rich_header;
+use super::traits::PEHeader;
+use std::fs::File;
+use std::io;
+use std::mem::size_of;
+
+/// Size of a COFF relocation record.
+const RELOCATION_SIZE: u64 = 10;
+/// Size of a COFF line number record.
+const LINE_NUMBER_SIZE: u64 = 6;
+
+/// A named range of a file, such as a header, a section's raw data or a data directory.
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub name: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl Region {
+    /// Checks whether the region overlaps the range `start..end`.
+    pub fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.size > 0 && self.offset < end && start < self.offset + self.size
+    }
+}
+
+/// Maps out where the headers, sections, data directories, COFF tables and overlay lie in a file.
+///
+/// Regions are sorted by offset, with enclosing regions before the regions they contain. Data directories that are not
+/// backed by data in the file are left out.
+pub fn get_layout(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Vec<Region>, PEError> {
+    match read_layout(fh, headers, section_table) {
+        Ok(regions) => Ok(regions),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn read_layout(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> io::Result<Vec<Region>> {
+    let coff_header = headers.coff_header();
+    let mut regions: Vec<Region> = Vec::new();
+    let mut push = |name: String, offset: u64, size: u64| regions.push(Region { name, offset, size });
+
+    // object files have no DOS header, and start with the COFF header
+    let coff_addr = if read_exact::<2>(fh, 0)? == *b"MZ" {
+        let signature_addr = u32::from_le_bytes(read_exact(fh, 0x3c)?) as u64;
+        push(String::from("DOS header"), 0, 0x40);
+        push(String::from("DOS stub"), 0x40, signature_addr.saturating_sub(0x40));
+        if let Ok(Some(rich_header)) = get_rich_header(fh) {
+            // the clear data runs from the `DanS` marker, and is followed by the `Rich` marker and key
+            push(String::from("Rich header"), rich_header.offset as u64, rich_header.clear_data.len() as u64 + 8);
+        }
+        push(String::from("PE signature"), signature_addr, 4);
+        signature_addr + 4
+    } else {
+        0
+    };
+    push(String::from("COFF header"), coff_addr, size_of::<CoffHeader>() as u64);
+    let optional_header_addr = coff_addr + size_of::<CoffHeader>() as u64;
+    push(
+        String::from("Optional header"),
+        optional_header_addr,
+        coff_header.size_of_optional_header as u64,
+    );
+    if let Some(dirs) = headers.data_directories() {
+        // the directories follow the standard and Windows-specific fields
+        let dirs_addr = optional_header_addr + if headers.is_pe32plus() { 112 } else { 96 };
+        push(
+            String::from("Data directories"),
+            dirs_addr,
+            dirs.len() as u64 * size_of::<DataDirectory>() as u64,
+        );
+    }
+    let section_table_addr = optional_header_addr + coff_header.size_of_optional_header as u64;
+    push(
+        String::from("Section table"),
+        section_table_addr,
+        section_table.len() as u64 * size_of::<SectionHeader>() as u64,
+    );
+
+    for section in section_table {
+        let name = section.name_str();
+        if section.pointer_to_raw_data != 0 {
+            push(format!("section {}", name), section.pointer_to_raw_data as u64, section.size_of_raw_data as u64);
+        }
+        if section.pointer_to_relocations != 0 {
+            push(
+                format!("relocations of {}", name),
+                section.pointer_to_relocations as u64,
+                section.number_of_relocations as u64 * RELOCATION_SIZE,
+            );
+        }
+        if section.pointer_to_line_numbers != 0 {
+            push(
+                format!("line numbers of {}", name),
+                section.pointer_to_line_numbers as u64,
+                section.number_of_line_numbers as u64 * LINE_NUMBER_SIZE,
+            );
+        }
+    }
+
+    for (i, dir) in headers.data_directories().into_iter().flatten().enumerate() {
+        if dir.virtual_address == 0 || dir.size == 0 {
+            continue;
+        }
+        let name = DataDirectory::DISPLAY_NAMES.get(i).copied().unwrap_or("Unknown directory").to_string();
+        if i == DataDirectory::CERTIFICATE_TABLE {
+            // unlike other data directories, the certificate table's address is a file offset
+            push(name, dir.virtual_address as u64, dir.size as u64);
+        } else if let Some(offset) = rva_to_file_offset(section_table, dir.virtual_address) {
+            push(name, offset as u64, dir.size as u64);
+        }
+    }
+
+    if coff_header.pointer_to_symbol_table != 0 {
+        let symbols_addr = coff_header.pointer_to_symbol_table as u64;
+        let symbols_size = coff_header.number_of_symbols as u64 * size_of::<CoffSymbol>() as u64;
+        push(String::from("Symbol table"), symbols_addr, symbols_size);
+        // the string table starts with its own size, including the size field
+        if let Ok(size) = read_exact::<4>(fh, symbols_addr + symbols_size) {
+            push(String::from("String table"), symbols_addr + symbols_size, u32::from_le_bytes(size) as u64);
+        }
+    }
+
+    if let Ok(Some(overlay)) = get_overlay(fh, headers, section_table) {
+        push(String::from("Overlay"), overlay.offset, overlay.size);
+    }
+
+    regions.sort_by(|a, b| a.offset.cmp(&b.offset).then(b.size.cmp(&a.size)));
+    Ok(regions)
+}
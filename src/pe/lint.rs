@@ -0,0 +1,347 @@
+use super::body::{SectionFlags, SectionHeader};
+use super::err::PEError;
+use super::traits::PEHeader;
+use chrono::Utc;
+use std::fmt::Display;
+use std::fs::File;
+
+/// Earliest plausible link time, 1993-01-01, when the PE format first shipped.
+const EARLIEST_TIMESTAMP: u32 = 725846400;
+/// How far in the future a link time may be before it is reported, to allow for clock skew.
+const TIMESTAMP_SLACK_SECS: i64 = 86400;
+/// Number of data directories defined by the PE format.
+const STANDARD_DATA_DIRECTORIES: u32 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Severity {
+    /// Unusual, but common in legitimate files.
+    Info,
+    /// Unusual enough to be worth a look, but the file will still load.
+    Warning,
+    /// The file is malformed, or will not load as its headers describe.
+    Error,
+}
+
+/// A lint that can be reported, with its ID and severity.
+#[derive(Debug, Clone, Copy)]
+pub struct Lint {
+    /// Short ID for suppressing the lint, such as `wx-section`.
+    pub id: &'static str,
+    pub severity: Severity,
+    pub description: &'static str,
+}
+
+/// One instance of a lint found in a file.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Anomaly {
+    pub id: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+pub const OVERLAPPING_SECTIONS: Lint = Lint {
+    id: "overlapping-sections",
+    severity: Severity::Error,
+    description: "two sections overlap in the file or in memory",
+};
+pub const RAW_DATA_BEYOND_EOF: Lint = Lint {
+    id: "raw-data-beyond-eof",
+    severity: Severity::Error,
+    description: "a section's raw data extends past the end of the file",
+};
+pub const BAD_FILE_ALIGNMENT: Lint = Lint {
+    id: "bad-file-alignment",
+    severity: Severity::Warning,
+    description: "file_alignment is not a power of two from 512 to 64K",
+};
+pub const BAD_SECTION_ALIGNMENT: Lint = Lint {
+    id: "bad-section-alignment",
+    severity: Severity::Warning,
+    description: "section_alignment is not a power of two, or is less than file_alignment",
+};
+pub const MISALIGNED_SECTION: Lint = Lint {
+    id: "misaligned-section",
+    severity: Severity::Info,
+    description: "a section's raw data or address is not a multiple of the alignment",
+};
+pub const ENTRY_POINT_OUTSIDE_SECTIONS: Lint = Lint {
+    id: "entry-point-outside-sections",
+    severity: Severity::Error,
+    description: "the entry point is not inside any section",
+};
+pub const ENTRY_POINT_WRITABLE: Lint = Lint {
+    id: "entry-point-writable",
+    severity: Severity::Warning,
+    description: "the entry point is in a writable section",
+};
+pub const ENTRY_POINT_NOT_EXECUTABLE: Lint = Lint {
+    id: "entry-point-not-executable",
+    severity: Severity::Warning,
+    description: "the entry point is in a section that is not executable",
+};
+pub const TOO_MANY_DATA_DIRECTORIES: Lint = Lint {
+    id: "too-many-data-directories",
+    severity: Severity::Warning,
+    description: "number_of_rva_and_sizes is greater than 16",
+};
+pub const SIZE_OF_IMAGE_MISMATCH: Lint = Lint {
+    id: "size-of-image-mismatch",
+    severity: Severity::Warning,
+    description: "size_of_image does not match the end of the last section",
+};
+pub const EMPTY_CODE_SECTION: Lint = Lint {
+    id: "empty-code-section",
+    severity: Severity::Warning,
+    description: "a section is marked as code but has no data",
+};
+pub const SUSPICIOUS_TIMESTAMP: Lint = Lint {
+    id: "suspicious-timestamp",
+    severity: Severity::Info,
+    description: "the link time is zero, before 1993 or in the future, as with reproducible builds",
+};
+pub const WX_SECTION: Lint = Lint {
+    id: "wx-section",
+    severity: Severity::Warning,
+    description: "a section is both writable and executable",
+};
+
+/// Every lint, in the order they are checked.
+pub const LINTS: [Lint; 13] = [
+    OVERLAPPING_SECTIONS,
+    RAW_DATA_BEYOND_EOF,
+    BAD_FILE_ALIGNMENT,
+    BAD_SECTION_ALIGNMENT,
+    MISALIGNED_SECTION,
+    ENTRY_POINT_OUTSIDE_SECTIONS,
+    ENTRY_POINT_WRITABLE,
+    ENTRY_POINT_NOT_EXECUTABLE,
+    TOO_MANY_DATA_DIRECTORIES,
+    SIZE_OF_IMAGE_MISMATCH,
+    EMPTY_CODE_SECTION,
+    SUSPICIOUS_TIMESTAMP,
+    WX_SECTION,
+];
+
+/// Optional header fields that lints look at, which are at different offsets in PE32 and PE32+.
+struct ImageLayout {
+    entry_point: u32,
+    section_alignment: u32,
+    file_alignment: u32,
+    size_of_image: u32,
+    number_of_rva_and_sizes: u32,
+}
+
+impl Lint {
+    fn report(&self, message: String) -> Anomaly {
+        Anomaly {
+            id: self.id,
+            severity: self.severity,
+            message,
+        }
+    }
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => f.pad("info"),
+            Severity::Warning => f.pad("warning"),
+            Severity::Error => f.pad("error"),
+        }
+    }
+}
+
+impl Display for Anomaly {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.id, self.message)
+    }
+}
+
+/// Checks an image or object file for anomalies, in the order of [`LINTS`].
+///
+/// Only the headers and section table are looked at, so this works on files that are too malformed to read further.
+pub fn find_anomalies(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Vec<Anomaly>, PEError> {
+    let file_len = match fh.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+    let layout = match (headers.optional_header_pe32(), headers.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => Some(ImageLayout {
+            entry_point: pe32_header.standard_fields.address_of_entry_point,
+            section_alignment: pe32_header.windows_fields.section_alignment,
+            file_alignment: pe32_header.windows_fields.file_alignment,
+            size_of_image: pe32_header.windows_fields.size_of_image,
+            number_of_rva_and_sizes: pe32_header.windows_fields.number_of_rva_and_sizes,
+        }),
+        (_, Some(pe32plus_header)) => Some(ImageLayout {
+            entry_point: pe32plus_header.standard_fields.address_of_entry_point,
+            section_alignment: pe32plus_header.windows_fields.section_alignment,
+            file_alignment: pe32plus_header.windows_fields.file_alignment,
+            size_of_image: pe32plus_header.windows_fields.size_of_image,
+            number_of_rva_and_sizes: pe32plus_header.windows_fields.number_of_rva_and_sizes,
+        }),
+        _ => None,
+    };
+
+    let mut ret: Vec<Anomaly> = Vec::new();
+    check_overlaps(section_table, layout.is_some(), &mut ret);
+    for section in section_table {
+        let raw_end = section.pointer_to_raw_data as u64 + section.size_of_raw_data as u64;
+        if raw_range(section).1 > 0 && raw_end > file_len {
+            ret.push(RAW_DATA_BEYOND_EOF.report(format!(
+                "section {} ends at offset {:#x}, but the file is {:#x} bytes",
+                section.name_str(),
+                raw_end,
+                file_len
+            )));
+        }
+    }
+    if let Some(layout) = &layout {
+        check_alignment(layout, section_table, &mut ret);
+        check_entry_point(layout, section_table, &mut ret);
+        if layout.number_of_rva_and_sizes > STANDARD_DATA_DIRECTORIES {
+            ret.push(TOO_MANY_DATA_DIRECTORIES.report(format!(
+                "number_of_rva_and_sizes is {}, but only {} are defined",
+                layout.number_of_rva_and_sizes, STANDARD_DATA_DIRECTORIES
+            )));
+        }
+        check_size_of_image(layout, section_table, &mut ret);
+    }
+    for section in section_table {
+        if section.characteristics.contains(SectionFlags::CntCode) && section.size_of_raw_data == 0 && section.virtual_size == 0 {
+            ret.push(EMPTY_CODE_SECTION.report(format!("section {} contains code but is empty", section.name_str())));
+        }
+    }
+    check_timestamp(headers.coff_header().time_date_stamp, &mut ret);
+    for section in section_table {
+        if section.characteristics.contains(SectionFlags::MemWrite | SectionFlags::MemExecute) {
+            ret.push(WX_SECTION.report(format!("section {} is writable and executable", section.name_str())));
+        }
+    }
+    Ok(ret)
+}
+
+fn check_overlaps(section_table: &[SectionHeader], is_image: bool, ret: &mut Vec<Anomaly>) {
+    for (i, first) in section_table.iter().enumerate() {
+        for second in &section_table[i + 1..] {
+            let raw_overlap = overlaps(raw_range(first), raw_range(second));
+            // object file sections are not laid out in memory, so all have an address of zero
+            let virtual_overlap = is_image && overlaps((first.virtual_address, first.mapped_size()), (second.virtual_address, second.mapped_size()));
+            let place = match (raw_overlap, virtual_overlap) {
+                (true, true) => "in the file and in memory",
+                (true, false) => "in the file",
+                (false, true) => "in memory",
+                (false, false) => continue,
+            };
+            ret.push(OVERLAPPING_SECTIONS.report(format!("sections {} and {} overlap {}", first.name_str(), second.name_str(), place)));
+        }
+    }
+}
+
+fn check_alignment(layout: &ImageLayout, section_table: &[SectionHeader], ret: &mut Vec<Anomaly>) {
+    // images with sections smaller than a page may use the same, smaller alignment for both
+    let small_alignment = layout.section_alignment < 0x1000 && layout.file_alignment == layout.section_alignment;
+    let file_alignment_ok = layout.file_alignment.is_power_of_two() && (small_alignment || (512..=0x10000).contains(&layout.file_alignment));
+    if !file_alignment_ok {
+        ret.push(BAD_FILE_ALIGNMENT.report(format!("file_alignment is {:#x}", layout.file_alignment)));
+    }
+    if !layout.section_alignment.is_power_of_two() || layout.section_alignment < layout.file_alignment {
+        ret.push(BAD_SECTION_ALIGNMENT.report(format!(
+            "section_alignment is {:#x}, with a file_alignment of {:#x}",
+            layout.section_alignment, layout.file_alignment
+        )));
+    }
+
+    for section in section_table {
+        if layout.file_alignment.is_power_of_two() && section.size_of_raw_data > 0 && section.pointer_to_raw_data % layout.file_alignment != 0 {
+            ret.push(MISALIGNED_SECTION.report(format!(
+                "section {} raw data at {:#x} is not a multiple of file_alignment {:#x}",
+                section.name_str(),
+                section.pointer_to_raw_data,
+                layout.file_alignment
+            )));
+        }
+        if layout.section_alignment.is_power_of_two() && section.virtual_address % layout.section_alignment != 0 {
+            ret.push(MISALIGNED_SECTION.report(format!(
+                "section {} address {:#x} is not a multiple of section_alignment {:#x}",
+                section.name_str(),
+                section.virtual_address,
+                layout.section_alignment
+            )));
+        }
+    }
+}
+
+fn check_entry_point(layout: &ImageLayout, section_table: &[SectionHeader], ret: &mut Vec<Anomaly>) {
+    // DLLs without an entry point leave it as zero
+    if layout.entry_point == 0 {
+        return;
+    }
+    let section = match section_table.iter().find(|section| section.contains_rva(layout.entry_point)) {
+        Some(section) => section,
+        None => {
+            ret.push(ENTRY_POINT_OUTSIDE_SECTIONS.report(format!("entry point {:#x} is not inside any section", layout.entry_point)));
+            return;
+        }
+    };
+    if section.characteristics.contains(SectionFlags::MemWrite) {
+        ret.push(ENTRY_POINT_WRITABLE.report(format!("entry point {:#x} is in writable section {}", layout.entry_point, section.name_str())));
+    }
+    if !section.characteristics.contains(SectionFlags::MemExecute) {
+        ret.push(ENTRY_POINT_NOT_EXECUTABLE.report(format!(
+            "entry point {:#x} is in non-executable section {}",
+            layout.entry_point,
+            section.name_str()
+        )));
+    }
+}
+
+fn check_size_of_image(layout: &ImageLayout, section_table: &[SectionHeader], ret: &mut Vec<Anomaly>) {
+    if !layout.section_alignment.is_power_of_two() {
+        return;
+    }
+    let end = match section_table
+        .iter()
+        .map(|section| section.virtual_address as u64 + section.mapped_size() as u64)
+        .max()
+    {
+        Some(end) => end,
+        None => return,
+    };
+    let expected = end.next_multiple_of(layout.section_alignment as u64);
+    if layout.size_of_image as u64 != expected {
+        ret.push(SIZE_OF_IMAGE_MISMATCH.report(format!("size_of_image is {:#x}, but the sections end at {:#x}", layout.size_of_image, expected)));
+    }
+}
+
+fn check_timestamp(time_date_stamp: u32, ret: &mut Vec<Anomaly>) {
+    let reason = if time_date_stamp == 0 {
+        "is zero"
+    } else if time_date_stamp < EARLIEST_TIMESTAMP {
+        "is before 1993"
+    } else if time_date_stamp as i64 > Utc::now().timestamp() + TIMESTAMP_SLACK_SECS {
+        "is in the future"
+    } else {
+        return;
+    };
+    ret.push(SUSPICIOUS_TIMESTAMP.report(format!("link time {:#010x} {}", time_date_stamp, reason)));
+}
+
+/// The range of the file holding a section's data. Uninitialised sections have no raw data, whatever their size says.
+fn raw_range(section: &SectionHeader) -> (u32, u32) {
+    if section.pointer_to_raw_data == 0 {
+        (0, 0)
+    } else {
+        (section.pointer_to_raw_data, section.size_of_raw_data)
+    }
+}
+
+/// Checks whether two non-empty `(start, length)` ranges overlap.
+fn overlaps(first: (u32, u32), second: (u32, u32)) -> bool {
+    let (first_start, first_len) = (first.0 as u64, first.1 as u64);
+    let (second_start, second_len) = (second.0 as u64, second.1 as u64);
+    first_len > 0 && second_len > 0 && first_start < second_start + second_len && second_start < first_start + first_len
+}
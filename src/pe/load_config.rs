@@ -0,0 +1,110 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::headers::DataDirectory;
+use super::internal::agnostic_fio::{read_exact, read_up_to};
+use super::internal::rva::rva_to_addr;
+use super::traits::PEHeader;
+use bitflags::bitflags;
+use std::fs::File;
+use std::io;
+
+/// Bytes of a PE32 load config directory that are read, up to and including `GuardFlags`.
+const LOAD_CONFIG_LEN_PE32: usize = 0x5C;
+/// Bytes of a PE32+ load config directory that are read, up to and including `GuardFlags`.
+const LOAD_CONFIG_LEN_PE32PLUS: usize = 0x94;
+
+bitflags! {
+    /// Control flow guard flags given in the load config directory. The top four bits hold the stride of the guard CF
+    /// function table, and are not flags.
+    #[derive(Debug, Clone, Copy)]
+    pub struct GuardFlags: u32 {
+        const CfInstrumented = 0x00000100;
+        const CfwInstrumented = 0x00000200;
+        const CfFunctionTablePresent = 0x00000400;
+        const SecurityCookieUnused = 0x00000800;
+        const ProtectDelayloadIat = 0x00001000;
+        const DelayloadIatInItsOwnSection = 0x00002000;
+        const CfExportSuppressionInfoPresent = 0x00004000;
+        const CfEnableExportSuppression = 0x00008000;
+        const CfLongjumpTablePresent = 0x00010000;
+        const RfInstrumented = 0x00020000;
+        const RfEnable = 0x00040000;
+        const RfStrict = 0x00080000;
+        const RetpolinePresent = 0x00100000;
+        const EhContinuationTablePresent = 0x00400000;
+        const XfgEnabled = 0x00800000;
+        const CastguardPresent = 0x01000000;
+        const MemcpyPresent = 0x02000000;
+    }
+}
+
+/// The security-related fields of the load config directory (`IMAGE_LOAD_CONFIG_DIRECTORY32/64`), widened to 64 bits.
+///
+/// The directory has grown over time, and its `size` says how much of it is present. Fields past the end of an older,
+/// shorter directory are read as zero.
+#[derive(Debug, Clone, Copy)]
+pub struct LoadConfig {
+    pub size: u32,
+    /// VA of the `/GS` security cookie.
+    pub security_cookie: u64,
+    /// VA of the table of safe exception handler RVAs. Only used by x86 images.
+    pub se_handler_table: u64,
+    pub se_handler_count: u64,
+    pub guard_cf_check_function_pointer: u64,
+    pub guard_cf_dispatch_function_pointer: u64,
+    /// VA of the table of valid indirect call targets.
+    pub guard_cf_function_table: u64,
+    pub guard_cf_function_count: u64,
+    pub guard_flags: GuardFlags,
+}
+
+/// Reads the load config directory, or `None` if the image has none.
+pub fn get_load_config(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Option<LoadConfig>, PEError> {
+    let load_config_dir = match headers.data_directory(DataDirectory::LOAD_CONFIG_TABLE) {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+
+    match read_load_config(fh, load_config_dir, section_table, headers.is_pe32plus()) {
+        Ok(load_config) => Ok(Some(load_config)),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn read_load_config(fh: &mut File, load_config_dir: &DataDirectory, section_table: &[SectionHeader], is_pe32plus: bool) -> io::Result<LoadConfig> {
+    let addr = rva_to_addr(section_table, load_config_dir.virtual_address)?;
+    let size = u32::from_le_bytes(read_exact(fh, addr)?);
+    let max_len = if is_pe32plus { LOAD_CONFIG_LEN_PE32PLUS } else { LOAD_CONFIG_LEN_PE32 };
+
+    // only the fields the directory claims to have are read, and the rest are left zeroed
+    let mut bytes = read_up_to(fh, addr, (size as usize).min(max_len))?;
+    bytes.resize(max_len, 0);
+    let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+    if is_pe32plus {
+        Ok(LoadConfig {
+            size,
+            security_cookie: u64_at(0x58),
+            se_handler_table: u64_at(0x60),
+            se_handler_count: u64_at(0x68),
+            guard_cf_check_function_pointer: u64_at(0x70),
+            guard_cf_dispatch_function_pointer: u64_at(0x78),
+            guard_cf_function_table: u64_at(0x80),
+            guard_cf_function_count: u64_at(0x88),
+            guard_flags: GuardFlags::from_bits_retain(u32_at(0x90)),
+        })
+    } else {
+        Ok(LoadConfig {
+            size,
+            security_cookie: u32_at(0x3C) as u64,
+            se_handler_table: u32_at(0x40) as u64,
+            se_handler_count: u32_at(0x44) as u64,
+            guard_cf_check_function_pointer: u32_at(0x48) as u64,
+            guard_cf_dispatch_function_pointer: u32_at(0x4C) as u64,
+            guard_cf_function_table: u32_at(0x50) as u64,
+            guard_cf_function_count: u32_at(0x54) as u64,
+            guard_flags: GuardFlags::from_bits_retain(u32_at(0x58)),
+        })
+    }
+}
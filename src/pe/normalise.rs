@@ -0,0 +1,238 @@
+use super::debug::DebugDirectory;
+use super::err::PEError;
+use super::file::PeFile;
+use super::headers::{DataDirectory, DosHeader};
+use super::rich::RichHeader;
+use super::traits::PEHeader;
+use std::mem::size_of;
+
+/// Offset of `TimeDateStamp` in the export directory.
+const EXPORT_TIME_DATE_STAMP_OFFSET: usize = 4;
+/// Signature of a PDB 7.0 CodeView record, which holds a GUID and age before the path.
+const RSDS_SIGNATURE: &[u8; 4] = b"RSDS";
+/// Offset of the GUID in an `RSDS` record, which is followed by the age, together identifying the PDB.
+const RSDS_GUID_OFFSET: usize = 4;
+/// Offset of the PDB path in an `RSDS` record.
+const RSDS_PATH_OFFSET: usize = 24;
+/// Signature of a PDB 2.0 CodeView record, which holds an offset, time stamp and age before the path.
+const NB10_SIGNATURE: &[u8; 4] = b"NB10";
+/// Offset of the time stamp in an `NB10` record.
+const NB10_TIME_DATE_STAMP_OFFSET: usize = 8;
+/// Offset of the PDB path in an `NB10` record.
+const NB10_PATH_OFFSET: usize = 16;
+/// Offset of the hash in a repro debug entry's data, which follows the hash's length.
+const REPRO_HASH_OFFSET: usize = 4;
+
+/// What `normalise` changed.
+#[derive(Debug, Default)]
+pub struct NormaliseReport {
+    /// Number of time stamps that were set to zero.
+    pub time_stamps_cleared: usize,
+    pub rich_header_cleared: bool,
+    /// The PDB paths that were stripped, along with the file names they were stripped to.
+    pub pdb_paths_stripped: Vec<(String, String)>,
+    /// Number of PDB GUIDs and ages that were set to zero.
+    pub pdb_ids_cleared: usize,
+    /// Whether the build hash in a repro debug entry was set to zero.
+    pub repro_hash_cleared: bool,
+}
+
+/// Removes the parts of an image that differ between builds of the same source, so that reproducible builds can be
+/// compared byte for byte: the time stamps in the COFF header and the export and debug directories, the Rich header,
+/// the PDB GUID and age and the directory part of the PDB path in CodeView debug entries, and the build hash in a
+/// repro debug entry.
+///
+/// The Rich header is zeroed rather than removed, so that nothing after it moves. The PDB paths are likewise cut down
+/// to their file names in place, leaving the debug entries' sizes as they are. The checksum is left for the caller to
+/// update.
+pub fn normalise(pe_file: &mut PeFile, rich_header: Option<&RichHeader>) -> Result<NormaliseReport, PEError> {
+    if pe_file.optional_header.is_none() {
+        return Err(PEError::InvalidEdit(String::from("only images can be normalised, not object files")));
+    }
+    let mut report = NormaliseReport::default();
+    if pe_file.coff_header.time_date_stamp != 0 {
+        pe_file.coff_header.time_date_stamp = 0;
+        report.time_stamps_cleared += 1;
+    }
+
+    if let Some(export_dir) = pe_file.data_directory(DataDirectory::EXPORT_TABLE).copied() {
+        if let Some((index, start)) = pe_file.find_rva(export_dir.virtual_address) {
+            report.time_stamps_cleared += clear_u32(&mut pe_file.sections[index].data, start + EXPORT_TIME_DATE_STAMP_OFFSET) as usize;
+        }
+    }
+
+    // the type, file offset and size of each CodeView and repro entry's data
+    let mut debug_records: Vec<(u32, u32, u32)> = Vec::new();
+    if let Some(debug_dir) = pe_file.data_directory(DataDirectory::DEBUG).copied() {
+        if let Some((index, start)) = pe_file.find_rva(debug_dir.virtual_address) {
+            let data = &mut pe_file.sections[index].data;
+            let end = (start + debug_dir.size as usize).min(data.len());
+            for entry in data[start..end].chunks_exact_mut(size_of::<DebugDirectory>()) {
+                report.time_stamps_cleared += clear_u32(entry, DebugDirectory::TIME_DATE_STAMP_OFFSET) as usize;
+                let debug_type = read_u32(entry, DebugDirectory::TYPE_OFFSET);
+                if debug_type == DebugDirectory::CODEVIEW || debug_type == DebugDirectory::REPRO {
+                    debug_records.push((
+                        debug_type,
+                        read_u32(entry, DebugDirectory::POINTER_TO_RAW_DATA_OFFSET),
+                        read_u32(entry, DebugDirectory::SIZE_OF_DATA_OFFSET),
+                    ));
+                }
+            }
+        }
+    }
+    for (debug_type, pointer, size) in debug_records {
+        // debug data is addressed by file offset, and is usually in a section but can be in the overlay
+        let record = match pe_file.find_file_offset(pointer as u64) {
+            Some((index, start)) => {
+                let data = &mut pe_file.sections[index].data;
+                let end = (start + size as usize).min(data.len());
+                &mut data[start..end]
+            }
+            None => {
+                let Some(start) = (pointer as u64).checked_sub(pe_file.overlay_offset()) else {
+                    continue;
+                };
+                let start = (start as usize).min(pe_file.overlay.len());
+                let end = (start + size as usize).min(pe_file.overlay.len());
+                &mut pe_file.overlay[start..end]
+            }
+        };
+        if debug_type == DebugDirectory::REPRO {
+            // the hash is only there if the linker was given /Brepro without a time stamp
+            let hash_len = read_u32(record, 0) as usize;
+            if let Some(hash) = record.get_mut(REPRO_HASH_OFFSET..).filter(|_| hash_len > 0) {
+                let hash_len = hash_len.min(hash.len());
+                report.repro_hash_cleared |= hash[..hash_len].iter().any(|&b| b != 0);
+                hash[..hash_len].fill(0);
+            }
+            continue;
+        }
+        let path_offset = match record.get(..4) {
+            Some(signature) if signature == RSDS_SIGNATURE => {
+                if let Some(id) = record.get_mut(RSDS_GUID_OFFSET..RSDS_PATH_OFFSET).filter(|id| id.iter().any(|&b| b != 0)) {
+                    id.fill(0);
+                    report.pdb_ids_cleared += 1;
+                }
+                RSDS_PATH_OFFSET
+            }
+            Some(signature) if signature == NB10_SIGNATURE => {
+                report.time_stamps_cleared += clear_u32(record, NB10_TIME_DATE_STAMP_OFFSET) as usize;
+                NB10_PATH_OFFSET
+            }
+            _ => continue,
+        };
+        if let Some(paths) = strip_pdb_path(record, path_offset) {
+            report.pdb_paths_stripped.push(paths);
+        }
+    }
+
+    if let Some(rich_header) = rich_header {
+        // the header runs from the DanS marker to the key that follows the Rich marker
+        let start = (rich_header.offset as usize).saturating_sub(size_of::<DosHeader>());
+        let end = (start + rich_header.clear_data.len() + 8).min(pe_file.dos_stub.len());
+        if start < end {
+            pe_file.dos_stub[start..end].fill(0);
+            report.rich_header_cleared = true;
+        }
+    }
+    Ok(report)
+}
+
+/// Cuts the null-terminated path at `path_offset` down to its file name, zero-filling the rest, and returns the old
+/// path and the new one if it had a directory part.
+fn strip_pdb_path(record: &mut [u8], path_offset: usize) -> Option<(String, String)> {
+    let path = record.get_mut(path_offset..)?;
+    let len = path.iter().position(|&b| b == 0).unwrap_or(path.len());
+    let name_start = path[..len].iter().rposition(|&b| b == b'\\' || b == b'/')? + 1;
+    let old_path = String::from_utf8_lossy(&path[..len]).into_owned();
+    let new_path = String::from_utf8_lossy(&path[name_start..len]).into_owned();
+    path.copy_within(name_start..len, 0);
+    path[len - name_start..len].fill(0);
+    Some((old_path, new_path))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    data.get(offset..offset + 4).map_or(0, |bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Sets a little-endian `u32` to zero, returning whether it was set before.
+fn clear_u32(data: &mut [u8], offset: usize) -> bool {
+    match data.get_mut(offset..offset + 4) {
+        Some(field) if field != [0; 4] => {
+            field.fill(0);
+            true
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::debug::{get_debug_data, get_debug_directories};
+    use crate::pe::deser::get_pe_file;
+    use crate::pe::internal::testdata;
+    use crate::pe::ser::pe_file_to_bytes;
+
+    #[test]
+    fn normalising_twice_changes_nothing_more() {
+        for name in ["test-x64.dll", "test-x86.dll"] {
+            let mut pe_file = testdata::read_pe_file(name);
+            let report = normalise(&mut pe_file, None).unwrap();
+            assert_eq!(
+                report.pdb_paths_stripped,
+                [(String::from("C:\\build\\test.pdb"), String::from("test.pdb"))],
+                "{}",
+                name
+            );
+            assert_eq!(report.pdb_ids_cleared, 1);
+            assert!(!report.rich_header_cleared && !report.repro_hash_cleared);
+            let normalised = pe_file_to_bytes(&pe_file).unwrap();
+
+            let mut pe_file = get_pe_file(&mut testdata::open_bytes(&normalised)).unwrap();
+            let report = normalise(&mut pe_file, None).unwrap();
+            assert_eq!(report.time_stamps_cleared, 0, "{}", name);
+            assert!(report.pdb_paths_stripped.is_empty());
+            assert_eq!(report.pdb_ids_cleared, 0);
+            assert_eq!(pe_file_to_bytes(&pe_file).unwrap(), normalised);
+
+            let (mut fh, headers, section_table) = testdata::reparse(&pe_file);
+            assert_eq!(headers.coff_header().time_date_stamp, 0);
+            let entries = get_debug_directories(&mut fh, headers.as_ref(), &section_table).unwrap();
+            assert!(entries.iter().all(|entry| entry.time_date_stamp == 0));
+            let codeview = entries.iter().find(|entry| entry.debug_type == DebugDirectory::CODEVIEW).unwrap();
+            let record = get_debug_data(&mut fh, codeview).unwrap();
+            assert_eq!(&record[..4], RSDS_SIGNATURE);
+            assert_eq!(record[RSDS_GUID_OFFSET..RSDS_PATH_OFFSET], [0; 20]);
+            assert!(record[RSDS_PATH_OFFSET..].starts_with(b"test.pdb\0"));
+            assert!(record[RSDS_PATH_OFFSET + 9..].iter().all(|&b| b == 0));
+        }
+    }
+
+    #[test]
+    fn repro_hash_is_cleared() {
+        // turn the CodeView entry into a repro one, taking the RSDS GUID and age as a 20 byte hash
+        let mut pe_file = testdata::read_pe_file("test-x64.dll");
+        let debug_dir = *pe_file.data_directory(DataDirectory::DEBUG).unwrap();
+        let (index, start) = pe_file.find_rva(debug_dir.virtual_address).unwrap();
+        let entry = &mut pe_file.sections[index].data[start..start + size_of::<DebugDirectory>()];
+        assert_eq!(read_u32(entry, DebugDirectory::TYPE_OFFSET), DebugDirectory::CODEVIEW);
+        entry[DebugDirectory::TYPE_OFFSET..DebugDirectory::TYPE_OFFSET + 4].copy_from_slice(&DebugDirectory::REPRO.to_le_bytes());
+        let pointer = read_u32(entry, DebugDirectory::POINTER_TO_RAW_DATA_OFFSET);
+        let (index, start) = pe_file.find_file_offset(pointer as u64).unwrap();
+        let record = &mut pe_file.sections[index].data[start..];
+        record[..4].copy_from_slice(&20u32.to_le_bytes());
+        let path = record[RSDS_PATH_OFFSET..RSDS_PATH_OFFSET + 8].to_vec();
+
+        let report = normalise(&mut pe_file, None).unwrap();
+        assert!(report.repro_hash_cleared);
+        assert_eq!(report.pdb_ids_cleared, 0);
+        assert!(report.pdb_paths_stripped.is_empty());
+        let record = &pe_file.sections[index].data[start..];
+        assert_eq!(record[..4], 20u32.to_le_bytes());
+        assert_eq!(record[REPRO_HASH_OFFSET..REPRO_HASH_OFFSET + 20], [0; 20]);
+        // the hash's length bounds what is cleared
+        assert_eq!(record[RSDS_PATH_OFFSET..RSDS_PATH_OFFSET + 8], path);
+        assert!(!normalise(&mut pe_file, None).unwrap().repro_hash_cleared);
+    }
+}
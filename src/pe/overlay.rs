@@ -0,0 +1,62 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::headers::DataDirectory;
+use super::internal::agnostic_fio::read_vec;
+use super::traits::PEHeader;
+use std::fs::File;
+
+/// Data appended to an image past the end of its sections, which the loader does not map.
+#[derive(Debug, Clone, Copy)]
+pub struct Overlay {
+    /// File offset of the start of the overlay.
+    pub offset: u64,
+    pub size: u64,
+    /// File offset and size of the certificate table, if it is within the overlay.
+    pub certificate_table: Option<(u64, u64)>,
+}
+
+/// Finds the overlay of an image, or `None` if there is no data past the sections.
+///
+/// The overlay runs from the end of the last section to the end of the file. This includes the certificate table of a
+/// signed image, which is reported separately, along with any padding before it and any data appended after it.
+pub fn get_overlay(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Option<Overlay>, PEError> {
+    let file_len = match fh.metadata() {
+        Ok(metadata) => metadata.len(),
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+
+    let size_of_headers = match (headers.optional_header_pe32(), headers.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => pe32_header.windows_fields.size_of_headers,
+        (_, Some(pe32plus_header)) => pe32plus_header.windows_fields.size_of_headers,
+        // object files are not loaded as images, so have no overlay
+        _ => return Ok(None),
+    };
+    let start = section_table
+        .iter()
+        .filter(|section| section.size_of_raw_data > 0)
+        .map(|section| section.pointer_to_raw_data as u64 + section.size_of_raw_data as u64)
+        .fold(size_of_headers as u64, u64::max);
+    if start >= file_len {
+        return Ok(None);
+    }
+
+    // unlike other data directories, the certificate table's address is a file offset
+    let certificate_table = headers
+        .data_directory(DataDirectory::CERTIFICATE_TABLE)
+        .filter(|cert| cert.size > 0)
+        .map(|cert| (cert.virtual_address as u64, cert.size as u64))
+        .filter(|&(cert_start, cert_size)| cert_start >= start && cert_start + cert_size <= file_len);
+    Ok(Some(Overlay {
+        offset: start,
+        size: file_len - start,
+        certificate_table,
+    }))
+}
+
+/// Reads the contents of an overlay.
+pub fn get_overlay_data(fh: &mut File, overlay: &Overlay) -> Result<Vec<u8>, PEError> {
+    match read_vec(fh, overlay.offset, overlay.size as usize) {
+        Ok(data) => Ok(data),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
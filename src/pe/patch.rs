@@ -0,0 +1,169 @@
+use super::checksum::compute_checksum;
+use super::err::PEError;
+use super::file::{OptionalHeader, PeFile};
+use super::headers::{DllCharacteristics, OptionalHeaderWindowsFieldsPe32, OptionalHeaderWindowsFieldsPe32Plus, WindowsSubsystem};
+use super::ser::pe_file_to_bytes;
+
+/// A change to one header field.
+#[derive(Debug, Clone, Copy)]
+pub enum FieldPatch {
+    /// Sets the COFF header's time stamp, which object files have too.
+    TimeDateStamp(u32),
+    Subsystem(WindowsSubsystem),
+    /// Sets DLL characteristics bits, leaving the others as they are.
+    SetDllCharacteristics(DllCharacteristics),
+    /// Clears DLL characteristics bits, leaving the others as they are.
+    ClearDllCharacteristics(DllCharacteristics),
+    OperatingSystemVersion(u16, u16),
+    ImageVersion(u16, u16),
+    SubsystemVersion(u16, u16),
+    StackReserve(u64),
+    StackCommit(u64),
+    HeapReserve(u64),
+    HeapCommit(u64),
+}
+
+/// Applies a change to a header field.
+///
+/// Fails if the file does not have the field, such as an object file's subsystem, or if the value does not fit, such
+/// as a stack size over 4 GiB in a PE32 image.
+pub fn apply_patch(pe_file: &mut PeFile, patch: FieldPatch) -> Result<(), PEError> {
+    if let FieldPatch::TimeDateStamp(time_date_stamp) = patch {
+        pe_file.coff_header.time_date_stamp = time_date_stamp;
+        return Ok(());
+    }
+    match &mut pe_file.optional_header {
+        Some(OptionalHeader::Pe32(optional_header)) => patch_pe32(&mut optional_header.windows_fields, patch),
+        Some(OptionalHeader::Pe32Plus(optional_header)) => {
+            patch_pe32plus(&mut optional_header.windows_fields, patch);
+            Ok(())
+        }
+        None => Err(PEError::InvalidEdit(String::from(
+            "object files have no optional header, so only the time stamp can be patched",
+        ))),
+    }
+}
+
+/// Recomputes the optional header's checksum, returning the new value. Object files have no checksum, so are left as
+/// they are.
+pub fn update_checksum(pe_file: &mut PeFile) -> Result<Option<u32>, PEError> {
+    if pe_file.optional_header.is_none() {
+        return Ok(None);
+    }
+    let checksum = compute_checksum(&pe_file_to_bytes(pe_file)?)?;
+    match &mut pe_file.optional_header {
+        Some(OptionalHeader::Pe32(optional_header)) => optional_header.windows_fields.checksum = checksum,
+        Some(OptionalHeader::Pe32Plus(optional_header)) => optional_header.windows_fields.checksum = checksum,
+        None => (),
+    }
+    Ok(Some(checksum))
+}
+
+fn patch_pe32(windows_fields: &mut OptionalHeaderWindowsFieldsPe32, patch: FieldPatch) -> Result<(), PEError> {
+    let narrow = |value: u64| match u32::try_from(value) {
+        Ok(value) => Ok(value),
+        Err(_) => Err(PEError::InvalidEdit(format!("{:X}h does not fit in a PE32 image's 32-bit field", value))),
+    };
+    match patch {
+        FieldPatch::TimeDateStamp(_) => (),
+        FieldPatch::Subsystem(subsystem) => windows_fields.subsystem = subsystem,
+        FieldPatch::SetDllCharacteristics(flags) => windows_fields.dll_characteristics.insert(flags),
+        FieldPatch::ClearDllCharacteristics(flags) => windows_fields.dll_characteristics.remove(flags),
+        FieldPatch::OperatingSystemVersion(major, minor) => {
+            windows_fields.major_operating_system_version = major;
+            windows_fields.minor_operating_system_version = minor;
+        }
+        FieldPatch::ImageVersion(major, minor) => {
+            windows_fields.major_image_version = major;
+            windows_fields.minor_image_version = minor;
+        }
+        FieldPatch::SubsystemVersion(major, minor) => {
+            windows_fields.major_subsystem_version = major;
+            windows_fields.minor_subsystem_version = minor;
+        }
+        FieldPatch::StackReserve(size) => windows_fields.size_of_stack_reserve = narrow(size)?,
+        FieldPatch::StackCommit(size) => windows_fields.size_of_stack_commit = narrow(size)?,
+        FieldPatch::HeapReserve(size) => windows_fields.size_of_heap_reserve = narrow(size)?,
+        FieldPatch::HeapCommit(size) => windows_fields.size_of_heap_commit = narrow(size)?,
+    }
+    Ok(())
+}
+
+fn patch_pe32plus(windows_fields: &mut OptionalHeaderWindowsFieldsPe32Plus, patch: FieldPatch) {
+    match patch {
+        FieldPatch::TimeDateStamp(_) => (),
+        FieldPatch::Subsystem(subsystem) => windows_fields.subsystem = subsystem,
+        FieldPatch::SetDllCharacteristics(flags) => windows_fields.dll_characteristics.insert(flags),
+        FieldPatch::ClearDllCharacteristics(flags) => windows_fields.dll_characteristics.remove(flags),
+        FieldPatch::OperatingSystemVersion(major, minor) => {
+            windows_fields.major_operating_system_version = major;
+            windows_fields.minor_operating_system_version = minor;
+        }
+        FieldPatch::ImageVersion(major, minor) => {
+            windows_fields.major_image_version = major;
+            windows_fields.minor_image_version = minor;
+        }
+        FieldPatch::SubsystemVersion(major, minor) => {
+            windows_fields.major_subsystem_version = major;
+            windows_fields.minor_subsystem_version = minor;
+        }
+        FieldPatch::StackReserve(size) => windows_fields.size_of_stack_reserve = size,
+        FieldPatch::StackCommit(size) => windows_fields.size_of_stack_commit = size,
+        FieldPatch::HeapReserve(size) => windows_fields.size_of_heap_reserve = size,
+        FieldPatch::HeapCommit(size) => windows_fields.size_of_heap_commit = size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::checksum::get_stored_checksum;
+    use crate::pe::internal::testdata;
+
+    #[test]
+    fn update_checksum_matches_known_value() {
+        // computed with the CheckSumMappedFile algorithm, as the linker leaves the checksums at zero
+        for (name, expected) in [("test-x64.dll", 0x251F), ("test-x86.dll", 0xDC81)] {
+            let mut pe_file = testdata::read_pe_file(name);
+            assert_eq!(update_checksum(&mut pe_file).unwrap(), Some(expected), "{}", name);
+            assert_eq!(get_stored_checksum(&pe_file_to_bytes(&pe_file).unwrap()).unwrap(), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn patches_change_only_their_fields() {
+        let mut pe_file = testdata::read_pe_file("test-x64.dll");
+        let original = pe_file_to_bytes(&pe_file).unwrap();
+        apply_patch(&mut pe_file, FieldPatch::Subsystem(WindowsSubsystem::WindowsGui)).unwrap();
+        apply_patch(&mut pe_file, FieldPatch::ClearDllCharacteristics(DllCharacteristics::DynamicBase)).unwrap();
+        apply_patch(&mut pe_file, FieldPatch::StackReserve(0x1_0000_0000)).unwrap();
+        let Some(OptionalHeader::Pe32Plus(optional_header)) = &pe_file.optional_header else {
+            panic!("test-x64.dll is not PE32+");
+        };
+        assert!(matches!(optional_header.windows_fields.subsystem, WindowsSubsystem::WindowsGui));
+        assert!(!optional_header.windows_fields.dll_characteristics.contains(DllCharacteristics::DynamicBase));
+        assert!(optional_header.windows_fields.dll_characteristics.contains(DllCharacteristics::NxCompat));
+        assert_eq!(optional_header.windows_fields.size_of_stack_reserve, 0x1_0000_0000);
+        let patched = pe_file_to_bytes(&pe_file).unwrap();
+        assert_eq!(patched.len(), original.len());
+        // the subsystem, DLL characteristics and stack reserve are next to each other in the optional header
+        let changed: Vec<usize> = (0..original.len()).filter(|&i| original[i] != patched[i]).collect();
+        assert!(changed.last().unwrap() - changed.first().unwrap() < 16, "{:X?}", changed);
+    }
+
+    #[test]
+    fn pe32_stack_reserve_must_fit_in_32_bits() {
+        let mut pe_file = testdata::read_pe_file("test-x86.dll");
+        assert!(apply_patch(&mut pe_file, FieldPatch::StackReserve(0x1_0000_0000)).is_err());
+        assert!(apply_patch(&mut pe_file, FieldPatch::StackReserve(0x20_0000)).is_ok());
+    }
+
+    #[test]
+    fn object_files_only_have_a_time_stamp() {
+        let mut pe_file = testdata::read_pe_file("test-x64.obj");
+        assert!(apply_patch(&mut pe_file, FieldPatch::Subsystem(WindowsSubsystem::WindowsCui)).is_err());
+        apply_patch(&mut pe_file, FieldPatch::TimeDateStamp(0x12345678)).unwrap();
+        assert_eq!(pe_file.coff_header.time_date_stamp, 0x12345678);
+        assert_eq!(update_checksum(&mut pe_file).unwrap(), None);
+    }
+}
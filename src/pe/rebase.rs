@@ -0,0 +1,234 @@
+use super::err::PEError;
+use super::file::{OptionalHeader, PeFile};
+use super::headers::{CoffCharacteristics, DataDirectory};
+use super::traits::PEHeader;
+
+/// Granularity that the loader maps images at, which image bases must be a multiple of.
+const IMAGE_BASE_ALIGNMENT: u64 = 0x10000;
+/// Size of the page RVA and block size fields that start each relocation block.
+const BLOCK_HEADER_LEN: usize = 8;
+
+/// Base relocation type of padding that brings a block to a multiple of four bytes, and is skipped.
+const IMAGE_REL_BASED_ABSOLUTE: u16 = 0;
+/// Base relocation type that adds the high 16 bits of the difference to a 16-bit field.
+const IMAGE_REL_BASED_HIGH: u16 = 1;
+/// Base relocation type that adds the low 16 bits of the difference to a 16-bit field.
+const IMAGE_REL_BASED_LOW: u16 = 2;
+/// Base relocation type that adds the difference to a 32-bit field.
+const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+/// Base relocation type that adds the difference to a 64-bit field.
+const IMAGE_REL_BASED_DIR64: u16 = 10;
+
+/// What `rebase` changed.
+#[derive(Debug)]
+pub struct RebaseReport {
+    pub old_image_base: u64,
+    pub new_image_base: u64,
+    /// Number of fixups that were applied to section data.
+    pub fixups_applied: usize,
+    /// RVAs of fixups that point outside the sections' raw data, such as into uninitialised data, and so could not be
+    /// applied.
+    pub fixups_skipped: Vec<u32>,
+}
+
+/// Moves an image to a new image base, applying each fixup in the base relocation table to the section data and
+/// setting the optional header's `image_base`, so that the file matches how the loader would have mapped it there.
+///
+/// Only the `HIGHLOW` and `DIR64` fixups used by x86, x64 and ARM64 images, along with `HIGH` and `LOW`, are supported.
+/// Any other type, such as the ARM `MOV32` pairs, or a malformed block fails the rebase before anything is changed. The
+/// relocation table itself is left as it is, so the rebased image can be rebased again.
+pub fn rebase(pe_file: &mut PeFile, new_image_base: u64) -> Result<RebaseReport, PEError> {
+    let old_image_base = match &pe_file.optional_header {
+        Some(OptionalHeader::Pe32(optional_header)) => {
+            if new_image_base > u32::MAX as u64 {
+                return Err(PEError::InvalidEdit(format!(
+                    "{:X}h does not fit in a PE32 image's 32-bit image base",
+                    new_image_base
+                )));
+            }
+            optional_header.windows_fields.image_base as u64
+        }
+        Some(OptionalHeader::Pe32Plus(optional_header)) => optional_header.windows_fields.image_base,
+        None => return Err(PEError::InvalidEdit(String::from("only images can be rebased, not object files"))),
+    };
+    if !new_image_base.is_multiple_of(IMAGE_BASE_ALIGNMENT) {
+        return Err(PEError::InvalidEdit(format!(
+            "image base {:X}h is not a multiple of {:X}h",
+            new_image_base, IMAGE_BASE_ALIGNMENT
+        )));
+    }
+    let mut report = RebaseReport {
+        old_image_base,
+        new_image_base,
+        fixups_applied: 0,
+        fixups_skipped: Vec::new(),
+    };
+    if new_image_base == old_image_base {
+        return Ok(report);
+    }
+
+    let relocations = match pe_file.data_directory(DataDirectory::BASE_RELOCATION_TABLE).copied() {
+        Some(dir) if !pe_file.coff_header.characteristics.contains(CoffCharacteristics::RelocsStripped) => {
+            let Some((index, start)) = pe_file.find_rva(dir.virtual_address) else {
+                return Err(PEError::DeserialiseError(format!(
+                    "base relocation table at RVA {:08X}h is not in any section's raw data",
+                    dir.virtual_address
+                )));
+            };
+            let data = &pe_file.sections[index].data;
+            data[start..(start + dir.size as usize).min(data.len())].to_vec()
+        }
+        _ => return Err(PEError::InvalidEdit(String::from("the image has no base relocations, so cannot be moved"))),
+    };
+
+    // check every block and entry before changing anything, so that a bad one later on leaves the image untouched
+    let mut fixups: Vec<(u16, u32)> = Vec::new();
+    let mut pos = 0;
+    while pos + BLOCK_HEADER_LEN <= relocations.len() {
+        let page_rva = u32::from_le_bytes(relocations[pos..pos + 4].try_into().unwrap());
+        let block_size = u32::from_le_bytes(relocations[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        if block_size < BLOCK_HEADER_LEN || pos + block_size > relocations.len() {
+            return Err(PEError::DeserialiseError(format!(
+                "base relocation block for page {:08X}h has a bad size of {:X}h",
+                page_rva, block_size
+            )));
+        }
+        for entry in relocations[pos + BLOCK_HEADER_LEN..pos + block_size].chunks_exact(2) {
+            let entry = u16::from_le_bytes([entry[0], entry[1]]);
+            let relocation_type = entry >> 12;
+            let rva = page_rva.wrapping_add((entry & 0xFFF) as u32);
+            match relocation_type {
+                IMAGE_REL_BASED_ABSOLUTE => (),
+                IMAGE_REL_BASED_HIGH | IMAGE_REL_BASED_LOW | IMAGE_REL_BASED_HIGHLOW | IMAGE_REL_BASED_DIR64 => fixups.push((relocation_type, rva)),
+                _ => {
+                    return Err(PEError::InvalidEdit(format!(
+                        "base relocation type {} at RVA {:08X}h is not supported",
+                        relocation_type, rva
+                    )))
+                }
+            }
+        }
+        pos += block_size;
+    }
+
+    let delta = new_image_base.wrapping_sub(old_image_base);
+    for (relocation_type, rva) in fixups {
+        let len = match relocation_type {
+            IMAGE_REL_BASED_HIGH | IMAGE_REL_BASED_LOW => 2,
+            IMAGE_REL_BASED_HIGHLOW => 4,
+            _ => 8,
+        };
+        let field = match pe_file.find_rva(rva) {
+            Some((index, offset)) => pe_file.sections[index].data.get_mut(offset..offset + len),
+            None => None,
+        };
+        let Some(field) = field else {
+            report.fixups_skipped.push(rva);
+            continue;
+        };
+        match relocation_type {
+            IMAGE_REL_BASED_HIGH => {
+                let value = u16::from_le_bytes(field.try_into().unwrap()).wrapping_add((delta >> 16) as u16);
+                field.copy_from_slice(&value.to_le_bytes());
+            }
+            IMAGE_REL_BASED_LOW => {
+                let value = u16::from_le_bytes(field.try_into().unwrap()).wrapping_add(delta as u16);
+                field.copy_from_slice(&value.to_le_bytes());
+            }
+            IMAGE_REL_BASED_HIGHLOW => {
+                let value = u32::from_le_bytes(field.try_into().unwrap()).wrapping_add(delta as u32);
+                field.copy_from_slice(&value.to_le_bytes());
+            }
+            _ => {
+                let value = u64::from_le_bytes(field.try_into().unwrap()).wrapping_add(delta);
+                field.copy_from_slice(&value.to_le_bytes());
+            }
+        }
+        report.fixups_applied += 1;
+    }
+
+    match &mut pe_file.optional_header {
+        Some(OptionalHeader::Pe32(optional_header)) => optional_header.windows_fields.image_base = new_image_base as u32,
+        Some(OptionalHeader::Pe32Plus(optional_header)) => optional_header.windows_fields.image_base = new_image_base,
+        None => (),
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::internal::testdata;
+    use crate::pe::ser::pe_file_to_bytes;
+
+    fn image_base(pe_file: &PeFile) -> u64 {
+        match &pe_file.optional_header {
+            Some(OptionalHeader::Pe32(optional_header)) => optional_header.windows_fields.image_base as u64,
+            Some(OptionalHeader::Pe32Plus(optional_header)) => optional_header.windows_fields.image_base,
+            None => unreachable!(),
+        }
+    }
+
+    fn read_at(pe_file: &PeFile, rva: u32, len: usize) -> u64 {
+        let (index, offset) = pe_file.find_rva(rva).unwrap();
+        let mut bytes = [0; 8];
+        bytes[..len].copy_from_slice(&pe_file.sections[index].data[offset..offset + len]);
+        u64::from_le_bytes(bytes)
+    }
+
+    /// Rebases a test image, checking the number of fixups and the rebased value of a few of them, given by their RVA,
+    /// size and value, and then rebases it back.
+    fn check_rebase(name: &str, new_image_base: u64, fixups: usize, values: &[(u32, usize, u64)]) {
+        let original = testdata::read(name);
+        let mut pe_file = testdata::read_pe_file(name);
+        let old_image_base = image_base(&pe_file);
+        let report = rebase(&mut pe_file, new_image_base).unwrap();
+        assert_eq!((report.old_image_base, report.new_image_base), (old_image_base, new_image_base), "{}", name);
+        assert_eq!(report.fixups_applied, fixups);
+        assert!(report.fixups_skipped.is_empty());
+        assert_eq!(image_base(&pe_file), new_image_base);
+        for &(rva, len, value) in values {
+            assert_eq!(read_at(&pe_file, rva, len), value, "{} {:08X}h", name, rva);
+        }
+
+        // the relocations are left in place, so rebasing back restores the original file
+        assert_eq!(rebase(&mut pe_file, old_image_base).unwrap().fixups_applied, fixups);
+        assert_eq!(pe_file_to_bytes(&pe_file).unwrap(), original);
+    }
+
+    #[test]
+    fn rebase_applies_known_fixups() {
+        check_rebase(
+            "test-x64.dll",
+            0x1_4000_0000,
+            3,
+            &[(0x3000, 8, 0x1_4000_3018), (0x3008, 8, 0x1_4000_3000), (0x3010, 8, 0x1_4000_3018)],
+        );
+        check_rebase(
+            "test-x86.dll",
+            0x2000_0000,
+            6,
+            &[(0x103F, 4, 0x2000_20F4), (0x3000, 4, 0x2000_300C), (0x3004, 4, 0x2000_3000)],
+        );
+    }
+
+    #[test]
+    fn bad_relocation_changes_nothing() {
+        let mut pe_file = testdata::read_pe_file("test-x86.dll");
+        assert!(rebase(&mut pe_file, 0x2000_8000).is_err());
+        assert!(rebase(&mut pe_file, 0x1_0000_0000).is_err());
+
+        // give the last entry of the last block a type that isn't supported, after the first block's good fixups
+        let dir = *pe_file.data_directory(DataDirectory::BASE_RELOCATION_TABLE).unwrap();
+        let (index, start) = pe_file.find_rva(dir.virtual_address + dir.size - 2).unwrap();
+        pe_file.sections[index].data[start + 1] |= 0x50;
+        let original = pe_file_to_bytes(&pe_file).unwrap();
+        let err = rebase(&mut pe_file, 0x2000_0000).unwrap_err();
+        assert!(
+            matches!(err, PEError::InvalidEdit(ref message) if message.contains("not supported")),
+            "{:?}",
+            err
+        );
+        assert_eq!(pe_file_to_bytes(&pe_file).unwrap(), original);
+    }
+}
@@ -0,0 +1,568 @@
+use super::body::{SectionFlags, SectionHeader};
+use super::err::PEError;
+use super::file::PeFile;
+use super::headers::DataDirectory;
+use super::resources::{
+    get_resource_data, get_resources, ResourceDirectory, ResourceDirectoryTable, ResourceId, ResourceNode, NAME_IS_STRING, OFFSET_IS_DIRECTORY,
+};
+use super::section_edit::{add_section, can_resize_in_place, remove_section, resize_section};
+use super::traits::PEHeader;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs::File;
+use std::mem::size_of;
+
+/// Language that resources are added with when none is given (`LANG_NEUTRAL`).
+pub const LANGUAGE_NEUTRAL: ResourceId = ResourceId::Id(0);
+/// Size of a directory entry (`IMAGE_RESOURCE_DIRECTORY_ENTRY`).
+const DIRECTORY_ENTRY_LEN: usize = 8;
+/// Size of a data entry (`IMAGE_RESOURCE_DATA_ENTRY`).
+const DATA_ENTRY_LEN: usize = 16;
+/// Alignment of each resource's data within the section.
+const DATA_ALIGNMENT: usize = 8;
+/// Size of the header of an `.ico` file and of an icon group (`ICONDIR` and `GRPICONDIR`).
+const ICON_DIR_LEN: usize = 6;
+/// Size of an image entry in an `.ico` file (`ICONDIRENTRY`).
+const ICO_ENTRY_LEN: usize = 16;
+/// Size of an image entry in an icon group (`GRPICONDIRENTRY`).
+const GROUP_ICON_ENTRY_LEN: usize = 14;
+/// Leading part of an image entry that `.ico` files and icon groups share: the dimensions, colour count, planes, bit
+/// count and size.
+const ICON_ENTRY_COMMON_LEN: usize = 12;
+
+/// A resource with its data, ready to be written into a rebuilt resource section.
+#[derive(Debug, Clone)]
+pub struct ResourceItem {
+    pub resource_type: ResourceId,
+    pub name: ResourceId,
+    pub language: ResourceId,
+    pub codepage: u32,
+    pub data: Vec<u8>,
+}
+
+/// An editable resource tree, flattened to the usual three levels of type, name and language.
+#[derive(Debug, Clone)]
+pub struct ResourceTree {
+    /// The root directory's header. Its characteristics, time stamp and version are given to every directory when the
+    /// tree is written.
+    pub table: ResourceDirectoryTable,
+    pub resources: Vec<ResourceItem>,
+}
+
+/// An image in an `.ico` file, with its directory entry.
+struct IcoImage<'a> {
+    entry: &'a [u8],
+    data: &'a [u8],
+}
+
+/// Where in the tree a directory entry points: another directory, by index, or a resource's data entry, by index.
+enum EntryTarget {
+    Directory(usize),
+    Data(usize),
+}
+
+impl Default for ResourceTree {
+    fn default() -> Self {
+        ResourceTree {
+            table: ResourceDirectoryTable {
+                characteristics: 0,
+                time_date_stamp: 0,
+                major_version: 0,
+                minor_version: 0,
+                number_of_name_entries: 0,
+                number_of_id_entries: 0,
+            },
+            resources: Vec::new(),
+        }
+    }
+}
+
+impl ResourceTree {
+    /// Finds the resources with a type and name, in every language.
+    pub fn find<'a>(&'a self, resource_type: &'a ResourceId, name: &'a ResourceId) -> impl Iterator<Item = &'a ResourceItem> + 'a {
+        self.resources
+            .iter()
+            .filter(move |item| item.resource_type == *resource_type && item.name == *name)
+    }
+
+    /// The first name of a type in the order the tree is written, such as the icon group that Explorer shows.
+    pub fn first_name(&self, resource_type: &ResourceId) -> Option<&ResourceId> {
+        self.resources
+            .iter()
+            .filter(|item| item.resource_type == *resource_type)
+            .map(|item| &item.name)
+            .min_by(|a, b| compare_ids(a, b))
+    }
+
+    /// Adds a resource, or replaces the data of an existing one, returning how many were replaced.
+    ///
+    /// With no language, every language of an existing resource is replaced, and a new resource is language neutral.
+    pub fn set(&mut self, resource_type: &ResourceId, name: &ResourceId, language: Option<&ResourceId>, data: &[u8]) -> usize {
+        let mut replaced = 0;
+        for item in self
+            .resources
+            .iter_mut()
+            .filter(|item| item.resource_type == *resource_type && item.name == *name && language.is_none_or(|language| item.language == *language))
+        {
+            item.data = data.to_vec();
+            replaced += 1;
+        }
+        if replaced == 0 {
+            self.resources.push(ResourceItem {
+                resource_type: resource_type.clone(),
+                name: name.clone(),
+                language: language.cloned().unwrap_or(LANGUAGE_NEUTRAL),
+                codepage: 0,
+                data: data.to_vec(),
+            });
+        }
+        replaced
+    }
+
+    /// Removes the resources of a type, or only those with a name and language if given, returning how many were
+    /// removed.
+    pub fn remove(&mut self, resource_type: &ResourceId, name: Option<&ResourceId>, language: Option<&ResourceId>) -> usize {
+        let before = self.resources.len();
+        self.resources.retain(|item| {
+            item.resource_type != *resource_type || !name.is_none_or(|name| item.name == *name) || !language.is_none_or(|language| item.language == *language)
+        });
+        before - self.resources.len()
+    }
+}
+
+/// Reads the resource tree along with each resource's data, or `None` if the image has no resources.
+///
+/// Fails if the tree has leaves anywhere other than the usual third level, as these could not be written back.
+pub fn get_resource_tree(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Option<ResourceTree>, PEError> {
+    let Some(root) = get_resources(fh, headers, section_table)? else {
+        return Ok(None);
+    };
+    let resources = root.resources();
+    if count_leaves(&root) != resources.len() {
+        return Err(PEError::DeserialiseError(String::from(
+            "resource tree has leaves that are not three levels deep",
+        )));
+    }
+    let mut items: Vec<ResourceItem> = Vec::with_capacity(resources.len());
+    for resource in resources {
+        items.push(ResourceItem {
+            data: get_resource_data(fh, section_table, &resource.data)?,
+            resource_type: resource.resource_type,
+            name: resource.name,
+            language: resource.language,
+            codepage: resource.data.codepage,
+        });
+    }
+    Ok(Some(ResourceTree {
+        table: root.table,
+        resources: items,
+    }))
+}
+
+/// Serialises a tree into the contents of a resource section loaded at `base_rva`.
+///
+/// The layout is the one resource compilers use: all the directory tables, then the data entries, then the name
+/// strings, then each resource's data. Within each directory, named entries come first in case-insensitive order,
+/// followed by ID entries in ascending order.
+pub fn resource_tree_to_bytes(tree: &ResourceTree, base_rva: u32) -> Result<Vec<u8>, PEError> {
+    let mut order: Vec<&ResourceItem> = tree.resources.iter().collect();
+    order.sort_by(|a, b| {
+        compare_ids(&a.resource_type, &b.resource_type)
+            .then_with(|| compare_ids(&a.name, &b.name))
+            .then_with(|| compare_ids(&a.language, &b.language))
+    });
+    if let Some(pair) = order
+        .windows(2)
+        .find(|pair| pair[0].resource_type == pair[1].resource_type && pair[0].name == pair[1].name && pair[0].language == pair[1].language)
+    {
+        return Err(PEError::SerialiseError(format!(
+            "resource {} / {} / {} appears more than once",
+            pair[0].resource_type, pair[0].name, pair[0].language
+        )));
+    }
+
+    // the root lists the types, which each have a directory of names, which each have a directory of languages
+    let mut directories: Vec<Vec<(&ResourceId, EntryTarget)>> = vec![Vec::new()];
+    let types: Vec<&[&ResourceItem]> = order.chunk_by(|a, b| a.resource_type == b.resource_type).collect();
+    for items in &types {
+        let target = EntryTarget::Directory(directories.len());
+        directories[0].push((&items[0].resource_type, target));
+        directories.push(Vec::new());
+    }
+    let mut data_index = 0;
+    for (i, items) in types.iter().enumerate() {
+        for items in items.chunk_by(|a, b| a.name == b.name) {
+            let target = EntryTarget::Directory(directories.len());
+            directories[i + 1].push((&items[0].name, target));
+            directories.push(
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(j, item)| (&item.language, EntryTarget::Data(data_index + j)))
+                    .collect(),
+            );
+            data_index += items.len();
+        }
+    }
+
+    let mut offset = 0;
+    let mut directory_offsets: Vec<usize> = Vec::with_capacity(directories.len());
+    for entries in &directories {
+        directory_offsets.push(offset);
+        offset += size_of::<ResourceDirectoryTable>() + entries.len() * DIRECTORY_ENTRY_LEN;
+    }
+    let data_entries_offset = offset;
+    offset += order.len() * DATA_ENTRY_LEN;
+    let mut string_offsets: HashMap<&str, usize> = HashMap::new();
+    for (id, _) in directories.iter().flatten() {
+        if let ResourceId::Name(name) = id {
+            if !string_offsets.contains_key(name.as_str()) {
+                string_offsets.insert(name, offset);
+                offset += 2 + name.encode_utf16().count() * 2;
+            }
+        }
+    }
+    let mut data_offsets: Vec<usize> = Vec::with_capacity(order.len());
+    for item in &order {
+        offset = offset.next_multiple_of(DATA_ALIGNMENT);
+        data_offsets.push(offset);
+        offset += item.data.len();
+    }
+    if offset as u64 >= OFFSET_IS_DIRECTORY as u64 || base_rva as u64 + offset as u64 > u32::MAX as u64 {
+        return Err(PEError::SerialiseError(String::from("resources are larger than a resource section can hold")));
+    }
+
+    let mut out = vec![0u8; offset];
+    for (entries, &table_offset) in directories.iter().zip(&directory_offsets) {
+        let number_of_name_entries = entries.iter().filter(|(id, _)| matches!(id, ResourceId::Name(_))).count();
+        put_u32(&mut out, table_offset, tree.table.characteristics);
+        put_u32(&mut out, table_offset + 4, tree.table.time_date_stamp);
+        put_u16(&mut out, table_offset + 8, tree.table.major_version);
+        put_u16(&mut out, table_offset + 10, tree.table.minor_version);
+        put_u16(&mut out, table_offset + 12, number_of_name_entries as u16);
+        put_u16(&mut out, table_offset + 14, (entries.len() - number_of_name_entries) as u16);
+        for (i, (id, target)) in entries.iter().enumerate() {
+            let entry_offset = table_offset + size_of::<ResourceDirectoryTable>() + i * DIRECTORY_ENTRY_LEN;
+            let name_or_id = match id {
+                ResourceId::Name(name) => NAME_IS_STRING | string_offsets[name.as_str()] as u32,
+                ResourceId::Id(id) if id & NAME_IS_STRING == 0 => *id,
+                ResourceId::Id(id) => return Err(PEError::SerialiseError(format!("resource ID {:X}h is too large", id))),
+            };
+            let target_offset = match target {
+                EntryTarget::Directory(index) => OFFSET_IS_DIRECTORY | directory_offsets[*index] as u32,
+                EntryTarget::Data(index) => (data_entries_offset + index * DATA_ENTRY_LEN) as u32,
+            };
+            put_u32(&mut out, entry_offset, name_or_id);
+            put_u32(&mut out, entry_offset + 4, target_offset);
+        }
+    }
+    for (name, &string_offset) in &string_offsets {
+        let units: Vec<u16> = name.encode_utf16().collect();
+        if units.len() > u16::MAX as usize {
+            return Err(PEError::SerialiseError(format!("resource name {:?} is too long", name)));
+        }
+        put_u16(&mut out, string_offset, units.len() as u16);
+        for (i, unit) in units.iter().enumerate() {
+            put_u16(&mut out, string_offset + 2 + i * 2, *unit);
+        }
+    }
+    for (i, item) in order.iter().enumerate() {
+        let entry_offset = data_entries_offset + i * DATA_ENTRY_LEN;
+        put_u32(&mut out, entry_offset, base_rva + data_offsets[i] as u32);
+        put_u32(&mut out, entry_offset + 4, item.data.len() as u32);
+        put_u32(&mut out, entry_offset + 8, item.codepage);
+        out[data_offsets[i]..data_offsets[i] + item.data.len()].copy_from_slice(&item.data);
+    }
+    Ok(out)
+}
+
+/// Writes a resource tree into an image and points the resource table data directory at it, returning the index of
+/// the section that now holds the resources.
+///
+/// The resources are rebuilt in place when they start a section that holds nothing else and they still fit, growing
+/// the section if nothing follows it in memory. Otherwise they are moved to a new `.rsrc` section at the end of the
+/// image, and the old section is removed if it held nothing else.
+pub fn write_resource_tree(pe_file: &mut PeFile, tree: &ResourceTree) -> Result<usize, PEError> {
+    if pe_file.optional_header.is_none() {
+        return Err(PEError::InvalidEdit(String::from("resources can only be edited in images, not object files")));
+    }
+    if pe_file.data_directories.len() <= DataDirectory::RESOURCE_TABLE {
+        return Err(PEError::InvalidEdit(String::from("the image has no resource table data directory")));
+    }
+    // the size does not depend on where the resources are loaded
+    let len = resource_tree_to_bytes(tree, 0)?.len();
+    let size = len as u32;
+
+    let own_section = pe_file.data_directory(DataDirectory::RESOURCE_TABLE).and_then(|dir| {
+        pe_file
+            .sections
+            .iter()
+            .position(|section| section.header.virtual_address == dir.virtual_address)
+            .filter(|&index| !holds_other_directories(pe_file, index))
+    });
+    let index = match own_section {
+        Some(index) if fits_in_section(&pe_file.sections[index].header, pe_file.sections[index].data.len(), size) => index,
+        Some(index) if can_resize_in_place(pe_file, index, size) => {
+            // so that resizing does not refuse to cut off the old resources
+            pe_file.data_directories[DataDirectory::RESOURCE_TABLE].size = size;
+            resize_section(pe_file, index, size)?;
+            index
+        }
+        _ => {
+            let characteristics = match own_section {
+                Some(index) => pe_file.sections[index].header.characteristics,
+                None => SectionFlags::CntInitialisedData | SectionFlags::MemRead,
+            };
+            if let Some(index) = own_section {
+                // this fails without changing anything if, say, the section is the first in memory, in which case it
+                // is left in place, unused
+                remove_section(pe_file, index).ok();
+            }
+            add_section(pe_file, ".rsrc", characteristics, &vec![0; len])?
+        }
+    };
+
+    let section = &mut pe_file.sections[index];
+    let virtual_address = section.header.virtual_address;
+    let bytes = resource_tree_to_bytes(tree, virtual_address)?;
+    section.data[..len].copy_from_slice(&bytes);
+    section.data[len..].fill(0);
+    pe_file.data_directories[DataDirectory::RESOURCE_TABLE] = DataDirectory { virtual_address, size };
+    Ok(index)
+}
+
+/// Replaces an icon group with the images in an `.ico` file, returning how many images there are.
+///
+/// The old group's icons are removed, and the new ones numbered after the highest remaining icon ID. The group keeps
+/// the languages it had, or is language neutral if it is new.
+pub fn set_icon_group(tree: &mut ResourceTree, name: &ResourceId, ico: &[u8]) -> Result<usize, PEError> {
+    let images = parse_ico(ico)?;
+    let mut languages: Vec<ResourceId> = Vec::new();
+    let old_groups: Vec<ResourceItem> = tree.find(&ResourceId::GROUP_ICON, name).cloned().collect();
+    for group in &old_groups {
+        for id in group_icon_ids(&group.data) {
+            tree.remove(&ResourceId::ICON, Some(&ResourceId::Id(id as u32)), Some(&group.language));
+        }
+        languages.push(group.language.clone());
+    }
+    if languages.is_empty() {
+        languages.push(LANGUAGE_NEUTRAL);
+    }
+
+    let first_id = tree
+        .resources
+        .iter()
+        .filter(|item| item.resource_type == ResourceId::ICON)
+        .filter_map(|item| match item.name {
+            ResourceId::Id(id) => Some(id + 1),
+            ResourceId::Name(_) => None,
+        })
+        .max()
+        .unwrap_or(1);
+    if first_id as u64 + images.len() as u64 > u16::MAX as u64 + 1 {
+        return Err(PEError::InvalidEdit(String::from("there are no icon IDs left for the new images")));
+    }
+
+    let mut group = ico[..ICON_DIR_LEN].to_vec();
+    for (i, image) in images.iter().enumerate() {
+        let id = first_id + i as u32;
+        group.extend_from_slice(&image.entry[..ICON_ENTRY_COMMON_LEN]);
+        group.extend_from_slice(&(id as u16).to_le_bytes());
+        for language in &languages {
+            tree.set(&ResourceId::ICON, &ResourceId::Id(id), Some(language), image.data);
+        }
+    }
+    for language in &languages {
+        tree.set(&ResourceId::GROUP_ICON, name, Some(language), &group);
+    }
+    Ok(images.len())
+}
+
+/// Splits an `.ico` file into each image's directory entry and data.
+fn parse_ico(ico: &[u8]) -> Result<Vec<IcoImage<'_>>, PEError> {
+    let invalid = |msg: &str| PEError::DeserialiseError(format!("not an icon file: {}", msg));
+    let header = ico.get(..ICON_DIR_LEN).ok_or_else(|| invalid("too short"))?;
+    if header[..4] != [0, 0, 1, 0] {
+        return Err(invalid("bad header"));
+    }
+    let count = u16::from_le_bytes([header[4], header[5]]) as usize;
+    if count == 0 {
+        return Err(invalid("no images"));
+    }
+
+    let mut images = Vec::with_capacity(count);
+    for i in 0..count {
+        let entry_offset = ICON_DIR_LEN + i * ICO_ENTRY_LEN;
+        let entry = ico
+            .get(entry_offset..entry_offset + ICO_ENTRY_LEN)
+            .ok_or_else(|| invalid("truncated directory"))?;
+        let size = u32::from_le_bytes(entry[8..12].try_into().unwrap()) as usize;
+        let offset = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as usize;
+        let image = ico
+            .get(offset..offset.saturating_add(size))
+            .ok_or_else(|| invalid("image runs past the end of the file"))?;
+        images.push(IcoImage { entry, data: image });
+    }
+    Ok(images)
+}
+
+/// The icon IDs an icon group refers to, skipping any entries that are cut off.
+fn group_icon_ids(group: &[u8]) -> Vec<u16> {
+    let count = match group.get(4..6) {
+        Some(count) => u16::from_le_bytes([count[0], count[1]]) as usize,
+        None => return Vec::new(),
+    };
+    group
+        .get(ICON_DIR_LEN..)
+        .unwrap_or_default()
+        .chunks_exact(GROUP_ICON_ENTRY_LEN)
+        .take(count)
+        .map(|entry| u16::from_le_bytes([entry[12], entry[13]]))
+        .collect()
+}
+
+fn count_leaves(directory: &ResourceDirectory) -> usize {
+    directory
+        .entries
+        .iter()
+        .map(|entry| match &entry.node {
+            ResourceNode::Directory(directory) => count_leaves(directory),
+            ResourceNode::Data(_) => 1,
+        })
+        .sum()
+}
+
+/// Orders entries the way the loader expects to search them: names first, compared case-insensitively, then IDs.
+fn compare_ids(a: &ResourceId, b: &ResourceId) -> Ordering {
+    match (a, b) {
+        // names that differ only in case are still kept apart
+        (ResourceId::Name(a), ResourceId::Name(b)) => a.to_uppercase().encode_utf16().cmp(b.to_uppercase().encode_utf16()).then_with(|| a.cmp(b)),
+        (ResourceId::Name(_), ResourceId::Id(_)) => Ordering::Less,
+        (ResourceId::Id(_), ResourceId::Name(_)) => Ordering::Greater,
+        (ResourceId::Id(a), ResourceId::Id(b)) => a.cmp(b),
+    }
+}
+
+/// Checks whether a section holds any data directory besides the resources, which rebuilding it would overwrite.
+fn holds_other_directories(pe_file: &PeFile, index: usize) -> bool {
+    let header = &pe_file.sections[index].header;
+    pe_file.data_directories.iter().enumerate().any(|(i, dir)| {
+        i != DataDirectory::RESOURCE_TABLE && i != DataDirectory::CERTIFICATE_TABLE && dir.virtual_address != 0 && header.contains_rva(dir.virtual_address)
+    })
+}
+
+/// Checks whether `size` bytes of resources fit in a section's raw data and in its virtual size.
+fn fits_in_section(header: &SectionHeader, data_len: usize, size: u32) -> bool {
+    size as usize <= data_len && size <= header.mapped_size()
+}
+
+fn put_u16(out: &mut [u8], offset: usize, value: u16) {
+    out[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut [u8], offset: usize, value: u32) {
+    out[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::deser::get_pe_file;
+    use crate::pe::imports::get_imports;
+    use crate::pe::internal::testdata;
+    use crate::pe::ser::pe_file_to_bytes;
+    use crate::pe::version_info::{parse_version_info, version_info_to_bytes, VersionNode};
+
+    fn node(key: &str, value: &[u8], children: Vec<VersionNode>) -> VersionNode {
+        VersionNode {
+            key: String::from(key),
+            is_text: false,
+            value: value.to_vec(),
+            children,
+        }
+    }
+
+    fn text(key: &str, value: &str) -> VersionNode {
+        VersionNode {
+            key: String::from(key),
+            is_text: true,
+            value: value.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect(),
+            children: Vec::new(),
+        }
+    }
+
+    /// A version resource for version 1.0.0.0, as a resource compiler would write it.
+    fn version_resource() -> Vec<u8> {
+        let mut fixed_file_info = [0u8; 52];
+        fixed_file_info[..4].copy_from_slice(&0xFEEF04BDu32.to_le_bytes());
+        fixed_file_info[4..8].copy_from_slice(&0x10000u32.to_le_bytes());
+        fixed_file_info[8..12].copy_from_slice(&0x10000u32.to_le_bytes());
+        fixed_file_info[16..20].copy_from_slice(&0x10000u32.to_le_bytes());
+        let root = node(
+            "VS_VERSION_INFO",
+            &fixed_file_info,
+            vec![
+                node(
+                    "StringFileInfo",
+                    &[],
+                    vec![node("040904B0", &[], vec![text("CompanyName", "pepeek"), text("FileVersion", "1.0.0.0")])],
+                ),
+                node("VarFileInfo", &[], vec![node("Translation", &[0x09, 0x04, 0xB0, 0x04], Vec::new())]),
+            ],
+        );
+        version_info_to_bytes(&root).unwrap()
+    }
+
+    /// Serialises a `PeFile` and reads it back, along with its resource tree.
+    fn reread(pe_file: &PeFile) -> (PeFile, ResourceTree) {
+        let mut fh = testdata::open_bytes(&pe_file_to_bytes(pe_file).unwrap());
+        let pe_file = get_pe_file(&mut fh).unwrap();
+        let tree = get_resource_tree(&mut fh, &pe_file, &pe_file.section_table()).unwrap().unwrap();
+        (pe_file, tree)
+    }
+
+    fn string(version_info: &VersionNode, key: &str) -> String {
+        let value = &version_info.children[0].children[0]
+            .children
+            .iter()
+            .find(|string| string.key == key)
+            .unwrap()
+            .value;
+        let units: Vec<u16> = value.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+        String::from_utf16(&units).unwrap().trim_end_matches('\0').to_string()
+    }
+
+    #[test]
+    fn version_info_is_set_and_read_back() {
+        for name in ["test-x64.dll", "test-x86.dll"] {
+            let mut pe_file = testdata::read_pe_file(name);
+            let mut tree = ResourceTree::default();
+            assert_eq!(tree.set(&ResourceId::VERSION, &ResourceId::Id(1), None, &version_resource()), 0);
+            write_resource_tree(&mut pe_file, &tree).unwrap();
+
+            let (mut pe_file, mut tree) = reread(&pe_file);
+            let mut version_info = parse_version_info(&tree.find(&ResourceId::VERSION, &ResourceId::Id(1)).next().unwrap().data).unwrap();
+            assert_eq!(string(&version_info, "FileVersion"), "1.0.0.0");
+            version_info.set_file_version([1, 2, 3, 4]).unwrap();
+            version_info.set_string("CompanyName", "Example Corporation");
+            version_info.set_string("ProductName", "pepeek tests");
+            let data = version_info_to_bytes(&version_info).unwrap();
+            assert_eq!(tree.set(&ResourceId::VERSION, &ResourceId::Id(1), None, &data), 1);
+            write_resource_tree(&mut pe_file, &tree).unwrap();
+
+            let (pe_file, tree) = reread(&pe_file);
+            let versions: Vec<&ResourceItem> = tree.find(&ResourceId::VERSION, &ResourceId::Id(1)).collect();
+            assert_eq!(versions.len(), 1);
+            assert_eq!(versions[0].language, LANGUAGE_NEUTRAL);
+            let version_info = parse_version_info(&versions[0].data).unwrap();
+            assert_eq!(version_info.value[8..16], [2, 0, 1, 0, 4, 0, 3, 0]);
+            assert_eq!(string(&version_info, "FileVersion"), "1.2.3.4");
+            assert_eq!(string(&version_info, "CompanyName"), "Example Corporation");
+            assert_eq!(string(&version_info, "ProductName"), "pepeek tests");
+            assert_eq!(version_info.children[1].children[0].value, [0x09, 0x04, 0xB0, 0x04]);
+
+            let mut fh = testdata::open_bytes(&pe_file_to_bytes(&pe_file).unwrap());
+            assert_eq!(get_imports(&mut fh, &pe_file, &pe_file.section_table()).unwrap().len(), 1, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,245 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::headers::DataDirectory;
+use super::internal::agnostic_fio::{read_exact, read_vec};
+use super::internal::rva::rva_to_addr;
+use super::traits::PEHeader;
+use std::fmt::Display;
+use std::fs::File;
+use std::io;
+use std::mem::{size_of, transmute};
+
+/// Deepest directory that will be followed. Well-formed trees are three levels deep (type, name, language).
+const MAX_DEPTH: usize = 8;
+/// Most entries that will be read across the whole tree, to bound malformed tables.
+const MAX_ENTRIES: usize = 0x10000;
+/// Set on an entry's name to mark it as an offset to a string rather than an integer ID.
+pub const NAME_IS_STRING: u32 = 0x80000000;
+/// Set on an entry's offset to mark it as pointing to a subdirectory rather than a data entry.
+pub const OFFSET_IS_DIRECTORY: u32 = 0x80000000;
+
+/// Names of the predefined resource types (`RT_*`), indexed by type ID.
+const RESOURCE_TYPE_NAMES: [Option<&str>; 25] = [
+    None,
+    Some("RT_CURSOR"),
+    Some("RT_BITMAP"),
+    Some("RT_ICON"),
+    Some("RT_MENU"),
+    Some("RT_DIALOG"),
+    Some("RT_STRING"),
+    Some("RT_FONTDIR"),
+    Some("RT_FONT"),
+    Some("RT_ACCELERATOR"),
+    Some("RT_RCDATA"),
+    Some("RT_MESSAGETABLE"),
+    Some("RT_GROUP_CURSOR"),
+    None,
+    Some("RT_GROUP_ICON"),
+    None,
+    Some("RT_VERSION"),
+    Some("RT_DLGINCLUDE"),
+    None,
+    Some("RT_PLUGPLAY"),
+    Some("RT_VXD"),
+    Some("RT_ANICURSOR"),
+    Some("RT_ANIICON"),
+    Some("RT_HTML"),
+    Some("RT_MANIFEST"),
+];
+
+/// The header of a resource directory table (`IMAGE_RESOURCE_DIRECTORY`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceDirectoryTable {
+    pub characteristics: u32,
+    pub time_date_stamp: u32,
+    pub major_version: u16,
+    pub minor_version: u16,
+    /// Number of entries identified by a string, which come before those identified by an integer.
+    pub number_of_name_entries: u16,
+    pub number_of_id_entries: u16,
+}
+
+/// An entry in a resource directory table (`IMAGE_RESOURCE_DIRECTORY_ENTRY`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct ResourceDirectoryEntry {
+    name_or_id: u32,
+    offset: u32,
+}
+
+/// A leaf of the resource tree (`IMAGE_RESOURCE_DATA_ENTRY`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceDataEntry {
+    /// RVA of the resource data. Unlike the rest of the tree, this is not relative to the resource section.
+    pub data_rva: u32,
+    pub size: u32,
+    pub codepage: u32,
+    pub reserved: u32,
+}
+
+/// Identifies a resource directory entry, either by a string or an integer ID.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceId {
+    Name(String),
+    Id(u32),
+}
+
+/// A node of the resource tree.
+#[derive(Debug, Clone)]
+pub enum ResourceNode {
+    Directory(ResourceDirectory),
+    Data(ResourceDataEntry),
+}
+
+/// An entry of a resource directory, with the node it points to.
+#[derive(Debug, Clone)]
+pub struct ResourceEntry {
+    pub id: ResourceId,
+    pub node: ResourceNode,
+}
+
+/// A resource directory table and its entries.
+#[derive(Debug, Clone)]
+pub struct ResourceDirectory {
+    pub table: ResourceDirectoryTable,
+    pub entries: Vec<ResourceEntry>,
+}
+
+/// A resource flattened out of the usual three level tree.
+#[derive(Debug, Clone)]
+pub struct Resource {
+    pub resource_type: ResourceId,
+    pub name: ResourceId,
+    pub language: ResourceId,
+    pub data: ResourceDataEntry,
+}
+
+impl ResourceId {
+    pub const ICON: ResourceId = ResourceId::Id(3);
+    pub const GROUP_ICON: ResourceId = ResourceId::Id(14);
+    pub const VERSION: ResourceId = ResourceId::Id(16);
+    pub const MANIFEST: ResourceId = ResourceId::Id(24);
+
+    /// Looks up a predefined resource type by name, with or without the `RT_` prefix, such as `RT_ICON` or `manifest`.
+    pub fn from_type_name(name: &str) -> Option<ResourceId> {
+        let name = name.to_ascii_uppercase();
+        let name = name.strip_prefix("RT_").unwrap_or(&name);
+        RESOURCE_TYPE_NAMES
+            .iter()
+            .position(|type_name| type_name.and_then(|type_name| type_name.strip_prefix("RT_")) == Some(name))
+            .map(|id| ResourceId::Id(id as u32))
+    }
+
+    /// The name of the predefined resource type with this ID, such as `RT_ICON`.
+    pub fn type_name(&self) -> Option<&'static str> {
+        match self {
+            ResourceId::Id(id) => RESOURCE_TYPE_NAMES.get(*id as usize).copied().flatten(),
+            ResourceId::Name(_) => None,
+        }
+    }
+}
+
+impl Display for ResourceId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResourceId::Name(name) => write!(f, "\"{}\"", name),
+            ResourceId::Id(id) => write!(f, "{}", id),
+        }
+    }
+}
+
+impl ResourceDirectory {
+    /// Flattens the tree into a list of resources, skipping any leaves that are not three levels deep.
+    pub fn resources(&self) -> Vec<Resource> {
+        let mut ret: Vec<Resource> = Vec::new();
+        for type_entry in &self.entries {
+            let ResourceNode::Directory(names) = &type_entry.node else {
+                continue;
+            };
+            for name_entry in &names.entries {
+                let ResourceNode::Directory(languages) = &name_entry.node else {
+                    continue;
+                };
+                for language_entry in &languages.entries {
+                    if let ResourceNode::Data(data) = &language_entry.node {
+                        ret.push(Resource {
+                            resource_type: type_entry.id.clone(),
+                            name: name_entry.id.clone(),
+                            language: language_entry.id.clone(),
+                            data: *data,
+                        });
+                    }
+                }
+            }
+        }
+        ret
+    }
+}
+
+/// Reads the resource tree, or `None` if the image has no resources.
+pub fn get_resources(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Option<ResourceDirectory>, PEError> {
+    let resource_dir = match headers.data_directory(DataDirectory::RESOURCE_TABLE) {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+
+    let mut entries_left = MAX_ENTRIES;
+    match rva_to_addr(section_table, resource_dir.virtual_address).and_then(|base_addr| read_directory(fh, base_addr, 0, 0, &mut entries_left)) {
+        Ok(root) => Ok(Some(root)),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+/// Reads the data of a resource.
+pub fn get_resource_data(fh: &mut File, section_table: &[SectionHeader], data: &ResourceDataEntry) -> Result<Vec<u8>, PEError> {
+    match rva_to_addr(section_table, data.data_rva).and_then(|addr| read_vec(fh, addr, data.size as usize)) {
+        Ok(bytes) => Ok(bytes),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn read_directory(fh: &mut File, base_addr: u64, offset: u32, depth: usize, entries_left: &mut usize) -> io::Result<ResourceDirectory> {
+    if depth >= MAX_DEPTH {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "resource tree is too deep"));
+    }
+
+    let table_addr = base_addr + offset as u64;
+    let table: ResourceDirectoryTable = unsafe { transmute(read_exact::<{ size_of::<ResourceDirectoryTable>() }>(fh, table_addr)?) };
+    let num_entries = table.number_of_name_entries as usize + table.number_of_id_entries as usize;
+    if num_entries > *entries_left {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "too many resource entries"));
+    }
+    *entries_left -= num_entries;
+
+    let mut entries: Vec<ResourceEntry> = Vec::with_capacity(num_entries);
+    for i in 0..num_entries {
+        let entry_addr = table_addr + (size_of::<ResourceDirectoryTable>() + i * size_of::<ResourceDirectoryEntry>()) as u64;
+        let entry: ResourceDirectoryEntry = unsafe { transmute(read_exact::<{ size_of::<ResourceDirectoryEntry>() }>(fh, entry_addr)?) };
+
+        let id = if entry.name_or_id & NAME_IS_STRING != 0 {
+            ResourceId::Name(read_resource_string(fh, base_addr + (entry.name_or_id & !NAME_IS_STRING) as u64)?)
+        } else {
+            ResourceId::Id(entry.name_or_id)
+        };
+        let node = if entry.offset & OFFSET_IS_DIRECTORY != 0 {
+            ResourceNode::Directory(read_directory(fh, base_addr, entry.offset & !OFFSET_IS_DIRECTORY, depth + 1, entries_left)?)
+        } else {
+            ResourceNode::Data(unsafe {
+                transmute::<[u8; 16], ResourceDataEntry>(read_exact::<{ size_of::<ResourceDataEntry>() }>(fh, base_addr + entry.offset as u64)?)
+            })
+        };
+        entries.push(ResourceEntry { id, node });
+    }
+
+    Ok(ResourceDirectory { table, entries })
+}
+
+/// Reads a length-prefixed UTF-16 string from the resource section.
+fn read_resource_string(fh: &mut File, addr: u64) -> io::Result<String> {
+    let len = u16::from_le_bytes(read_exact(fh, addr)?) as usize;
+    let bytes = read_vec(fh, addr + 2, len * 2)?;
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+    Ok(String::from_utf16_lossy(&units))
+}
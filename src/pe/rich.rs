@@ -0,0 +1,403 @@
+use super::deser::get_dos_header;
+use super::err::PEError;
+use super::headers::DosHeader;
+use super::internal::agnostic_fio::read_vec;
+use md5::{Digest, Md5};
+use std::fmt::Display;
+use std::fs::File;
+use std::mem::size_of;
+
+const RICH_SIGNATURE: u32 = u32::from_le_bytes(*b"Rich");
+const DANS_SIGNATURE: u32 = u32::from_le_bytes(*b"DanS");
+
+/// Names of the tools that can appear in a Rich header, indexed by product ID.
+const PRODUCT_NAMES: [&str; 271] = [
+    "Unknown",
+    "Import0",
+    "Linker510",
+    "Cvtomf510",
+    "Linker600",
+    "Cvtomf600",
+    "Cvtres500",
+    "Utc11_Basic",
+    "Utc11_C",
+    "Utc12_Basic",
+    "Utc12_C",
+    "Utc12_CPP",
+    "AliasObj60",
+    "VisualBasic60",
+    "Masm613",
+    "Masm710",
+    "Linker511",
+    "Cvtomf511",
+    "Masm614",
+    "Linker512",
+    "Cvtomf512",
+    "Utc12_C_Std",
+    "Utc12_CPP_Std",
+    "Utc12_C_Book",
+    "Utc12_CPP_Book",
+    "Implib700",
+    "Cvtomf700",
+    "Utc13_Basic",
+    "Utc13_C",
+    "Utc13_CPP",
+    "Linker610",
+    "Cvtomf610",
+    "Linker601",
+    "Cvtomf601",
+    "Utc12_1_Basic",
+    "Utc12_1_C",
+    "Utc12_1_CPP",
+    "Linker620",
+    "Cvtomf620",
+    "AliasObj70",
+    "Linker621",
+    "Cvtomf621",
+    "Masm615",
+    "Utc13_LTCG_C",
+    "Utc13_LTCG_CPP",
+    "Masm620",
+    "ILAsm100",
+    "Utc12_2_Basic",
+    "Utc12_2_C",
+    "Utc12_2_CPP",
+    "Utc12_2_C_Std",
+    "Utc12_2_CPP_Std",
+    "Utc12_2_C_Book",
+    "Utc12_2_CPP_Book",
+    "Implib622",
+    "Cvtomf622",
+    "Cvtres501",
+    "Utc13_C_Std",
+    "Utc13_CPP_Std",
+    "Cvtpgd1300",
+    "Linker622",
+    "Linker700",
+    "Export622",
+    "Export700",
+    "Masm700",
+    "Utc13_POGO_I_C",
+    "Utc13_POGO_I_CPP",
+    "Utc13_POGO_O_C",
+    "Utc13_POGO_O_CPP",
+    "Cvtres700",
+    "Cvtres710p",
+    "Linker710p",
+    "Cvtomf710p",
+    "Export710p",
+    "Implib710p",
+    "Masm710p",
+    "Utc1310p_C",
+    "Utc1310p_CPP",
+    "Utc1310p_C_Std",
+    "Utc1310p_CPP_Std",
+    "Utc1310p_LTCG_C",
+    "Utc1310p_LTCG_CPP",
+    "Utc1310p_POGO_I_C",
+    "Utc1310p_POGO_I_CPP",
+    "Utc1310p_POGO_O_C",
+    "Utc1310p_POGO_O_CPP",
+    "Linker624",
+    "Cvtomf624",
+    "Export624",
+    "Implib624",
+    "Linker710",
+    "Cvtomf710",
+    "Export710",
+    "Implib710",
+    "Cvtres710",
+    "Utc1310_C",
+    "Utc1310_CPP",
+    "Utc1310_C_Std",
+    "Utc1310_CPP_Std",
+    "Utc1310_LTCG_C",
+    "Utc1310_LTCG_CPP",
+    "Utc1310_POGO_I_C",
+    "Utc1310_POGO_I_CPP",
+    "Utc1310_POGO_O_C",
+    "Utc1310_POGO_O_CPP",
+    "AliasObj710",
+    "AliasObj710p",
+    "Cvtpgd1310",
+    "Cvtpgd1310p",
+    "Utc1400_C",
+    "Utc1400_CPP",
+    "Utc1400_C_Std",
+    "Utc1400_CPP_Std",
+    "Utc1400_LTCG_C",
+    "Utc1400_LTCG_CPP",
+    "Utc1400_POGO_I_C",
+    "Utc1400_POGO_I_CPP",
+    "Utc1400_POGO_O_C",
+    "Utc1400_POGO_O_CPP",
+    "Cvtpgd1400",
+    "Linker800",
+    "Cvtomf800",
+    "Export800",
+    "Implib800",
+    "Cvtres800",
+    "Masm800",
+    "AliasObj800",
+    "PhoenixPrerelease",
+    "Utc1400_CVTCIL_C",
+    "Utc1400_CVTCIL_CPP",
+    "Utc1400_LTCG_MSIL",
+    "Utc1500_C",
+    "Utc1500_CPP",
+    "Utc1500_C_Std",
+    "Utc1500_CPP_Std",
+    "Utc1500_CVTCIL_C",
+    "Utc1500_CVTCIL_CPP",
+    "Utc1500_LTCG_C",
+    "Utc1500_LTCG_CPP",
+    "Utc1500_LTCG_MSIL",
+    "Utc1500_POGO_I_C",
+    "Utc1500_POGO_I_CPP",
+    "Utc1500_POGO_O_C",
+    "Utc1500_POGO_O_CPP",
+    "Cvtpgd1500",
+    "Linker900",
+    "Export900",
+    "Implib900",
+    "Cvtres900",
+    "Masm900",
+    "AliasObj900",
+    "Resource",
+    "AliasObj1000",
+    "Cvtpgd1600",
+    "Cvtres1000",
+    "Export1000",
+    "Implib1000",
+    "Linker1000",
+    "Masm1000",
+    "Phx1600_C",
+    "Phx1600_CPP",
+    "Phx1600_CVTCIL_C",
+    "Phx1600_CVTCIL_CPP",
+    "Phx1600_LTCG_C",
+    "Phx1600_LTCG_CPP",
+    "Phx1600_LTCG_MSIL",
+    "Phx1600_POGO_I_C",
+    "Phx1600_POGO_I_CPP",
+    "Phx1600_POGO_O_C",
+    "Phx1600_POGO_O_CPP",
+    "Utc1600_C",
+    "Utc1600_CPP",
+    "Utc1600_CVTCIL_C",
+    "Utc1600_CVTCIL_CPP",
+    "Utc1600_LTCG_C",
+    "Utc1600_LTCG_CPP",
+    "Utc1600_LTCG_MSIL",
+    "Utc1600_POGO_I_C",
+    "Utc1600_POGO_I_CPP",
+    "Utc1600_POGO_O_C",
+    "Utc1600_POGO_O_CPP",
+    "AliasObj1010",
+    "Cvtpgd1610",
+    "Cvtres1010",
+    "Export1010",
+    "Implib1010",
+    "Linker1010",
+    "Masm1010",
+    "Utc1610_C",
+    "Utc1610_CPP",
+    "Utc1610_CVTCIL_C",
+    "Utc1610_CVTCIL_CPP",
+    "Utc1610_LTCG_C",
+    "Utc1610_LTCG_CPP",
+    "Utc1610_LTCG_MSIL",
+    "Utc1610_POGO_I_C",
+    "Utc1610_POGO_I_CPP",
+    "Utc1610_POGO_O_C",
+    "Utc1610_POGO_O_CPP",
+    "AliasObj1100",
+    "Cvtpgd1700",
+    "Cvtres1100",
+    "Export1100",
+    "Implib1100",
+    "Linker1100",
+    "Masm1100",
+    "Utc1700_C",
+    "Utc1700_CPP",
+    "Utc1700_CVTCIL_C",
+    "Utc1700_CVTCIL_CPP",
+    "Utc1700_LTCG_C",
+    "Utc1700_LTCG_CPP",
+    "Utc1700_LTCG_MSIL",
+    "Utc1700_POGO_I_C",
+    "Utc1700_POGO_I_CPP",
+    "Utc1700_POGO_O_C",
+    "Utc1700_POGO_O_CPP",
+    "AliasObj1200",
+    "Cvtpgd1800",
+    "Cvtres1200",
+    "Export1200",
+    "Implib1200",
+    "Linker1200",
+    "Masm1200",
+    "Utc1800_C",
+    "Utc1800_CPP",
+    "Utc1800_CVTCIL_C",
+    "Utc1800_CVTCIL_CPP",
+    "Utc1800_LTCG_C",
+    "Utc1800_LTCG_CPP",
+    "Utc1800_LTCG_MSIL",
+    "Utc1800_POGO_I_C",
+    "Utc1800_POGO_I_CPP",
+    "Utc1800_POGO_O_C",
+    "Utc1800_POGO_O_CPP",
+    "AliasObj1210",
+    "Cvtpgd1810",
+    "Cvtres1210",
+    "Export1210",
+    "Implib1210",
+    "Linker1210",
+    "Masm1210",
+    "Utc1810_C",
+    "Utc1810_CPP",
+    "Utc1810_CVTCIL_C",
+    "Utc1810_CVTCIL_CPP",
+    "Utc1810_LTCG_C",
+    "Utc1810_LTCG_CPP",
+    "Utc1810_LTCG_MSIL",
+    "Utc1810_POGO_I_C",
+    "Utc1810_POGO_I_CPP",
+    "Utc1810_POGO_O_C",
+    "Utc1810_POGO_O_CPP",
+    "AliasObj1400",
+    "Cvtpgd1900",
+    "Cvtres1400",
+    "Export1400",
+    "Implib1400",
+    "Linker1400",
+    "Masm1400",
+    "Utc1900_C",
+    "Utc1900_CPP",
+    "Utc1900_CVTCIL_C",
+    "Utc1900_CVTCIL_CPP",
+    "Utc1900_LTCG_C",
+    "Utc1900_LTCG_CPP",
+    "Utc1900_LTCG_MSIL",
+    "Utc1900_POGO_I_C",
+    "Utc1900_POGO_I_CPP",
+    "Utc1900_POGO_O_C",
+    "Utc1900_POGO_O_CPP",
+];
+
+/// One `@comp.id` entry from a Rich header: a tool that contributed objects to the image.
+#[derive(Debug, Clone, Copy)]
+pub struct RichEntry {
+    pub product_id: u16,
+    pub build: u16,
+    /// Number of objects produced by this tool.
+    pub count: u32,
+}
+
+/// A decoded Rich header.
+#[derive(Debug, Clone)]
+pub struct RichHeader {
+    /// File offset of the `DanS` marker.
+    pub offset: u32,
+    /// XOR key, which doubles as the stored checksum.
+    pub key: u32,
+    /// Checksum computed from the DOS header, DOS stub and entries.
+    pub computed_checksum: u32,
+    pub entries: Vec<RichEntry>,
+    /// Decoded header, from the `DanS` marker up to but excluding the `Rich` marker.
+    pub clear_data: Vec<u8>,
+}
+
+impl RichEntry {
+    /// The combined `@comp.id` value, with the product ID in the high word.
+    pub fn comp_id(&self) -> u32 {
+        ((self.product_id as u32) << 16) | self.build as u32
+    }
+
+    /// Name of the tool, such as `Utc1900_CPP`, if the product ID is known.
+    pub fn product_name(&self) -> Option<&'static str> {
+        PRODUCT_NAMES.get(self.product_id as usize).copied()
+    }
+}
+
+impl RichHeader {
+    pub fn is_checksum_valid(&self) -> bool {
+        self.key == self.computed_checksum
+    }
+
+    /// MD5 of the decoded header, which stays the same across builds made with the same toolchain and inputs.
+    pub fn rich_hash(&self) -> String {
+        format!("{:x}", Md5::digest(&self.clear_data))
+    }
+}
+
+impl Display for RichEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.product_name() {
+            Some(name) => {
+                let words: Vec<&str> = name.split('_').map(|word| if word == "CPP" { "C++" } else { word }).collect();
+                write!(f, "{} {}", words.join(" "), self.build)
+            }
+            None => write!(f, "Unknown (0x{:04x}) {}", self.product_id, self.build),
+        }
+    }
+}
+
+/// Locates and decodes the Rich header between the DOS stub and the PE signature, or `None` if there is none.
+pub fn get_rich_header(fh: &mut File) -> Result<Option<RichHeader>, PEError> {
+    let dos_header = get_dos_header(fh)?;
+    let stub = match read_vec(fh, 0, dos_header.address_of_new_header as usize) {
+        Ok(stub) => stub,
+        Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+    };
+    Ok(decode_rich_header(&stub))
+}
+
+fn decode_rich_header(stub: &[u8]) -> Option<RichHeader> {
+    let dwords: Vec<u32> = stub.chunks_exact(4).map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap())).collect();
+    let first_stub_dword = size_of::<DosHeader>() / 4;
+
+    // the Rich marker is followed by the key, and everything before it back to DanS is XORed with the key
+    let rich_index = (first_stub_dword..dwords.len().saturating_sub(1)).find(|&i| dwords[i] == RICH_SIGNATURE)?;
+    let key = dwords[rich_index + 1];
+    let dans_index = (first_stub_dword..rich_index).rev().find(|&i| dwords[i] ^ key == DANS_SIGNATURE)?;
+
+    // DanS is followed by three padding dwords, then (comp.id, count) pairs
+    let entries: Vec<RichEntry> = dwords[(dans_index + 4).min(rich_index)..rich_index]
+        .chunks_exact(2)
+        .map(|pair| {
+            let comp_id = pair[0] ^ key;
+            RichEntry {
+                product_id: (comp_id >> 16) as u16,
+                build: comp_id as u16,
+                count: pair[1] ^ key,
+            }
+        })
+        .collect();
+
+    let clear_data: Vec<u8> = dwords[dans_index..rich_index].iter().flat_map(|dword| (dword ^ key).to_le_bytes()).collect();
+    let offset = (dans_index * 4) as u32;
+
+    Some(RichHeader {
+        offset,
+        key,
+        computed_checksum: compute_checksum(&stub[..offset as usize], &entries),
+        entries,
+        clear_data,
+    })
+}
+
+fn compute_checksum(dos_bytes: &[u8], entries: &[RichEntry]) -> u32 {
+    let mut checksum = dos_bytes.len() as u32;
+    for (i, byte) in dos_bytes.iter().enumerate() {
+        // e_lfanew is excluded, as it is only known after the Rich header is written
+        if (0x3c..0x40).contains(&i) {
+            continue;
+        }
+        checksum = checksum.wrapping_add((*byte as u32).rotate_left(i as u32));
+    }
+    for entry in entries {
+        checksum = checksum.wrapping_add(entry.comp_id().rotate_left(entry.count));
+    }
+    checksum
+}
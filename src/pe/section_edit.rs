@@ -0,0 +1,505 @@
+use super::body::{SectionFlags, SectionHeader};
+use super::debug::DebugDirectory;
+use super::err::PEError;
+use super::file::{OptionalHeader, PeFile, Section};
+use super::headers::{CoffCharacteristics, DataDirectory, DllCharacteristics};
+use super::traits::PEHeader;
+use std::mem::size_of;
+
+/// Largest file alignment the loader accepts.
+const MAX_FILE_ALIGNMENT: u32 = 0x10000;
+
+/// The optional header fields that section edits keep up to date, gathered from either flavour.
+struct ImageSizes {
+    size_of_code: u32,
+    size_of_initialised_data: u32,
+    size_of_uninitialised_data: u32,
+    section_alignment: u32,
+    file_alignment: u32,
+    size_of_image: u32,
+    size_of_headers: u32,
+}
+
+/// Appends a section after the last one, both in memory and in the file, returning its index.
+///
+/// The raw data is zero-padded to the file alignment. If there is no room left in the headers for another section
+/// header, the headers are grown by the file alignment and everything after them moved along, which only works if the
+/// first section's RVA leaves room for this.
+pub fn add_section(pe_file: &mut PeFile, name: &str, characteristics: SectionFlags, data: &[u8]) -> Result<usize, PEError> {
+    let mut sizes = get_image_sizes(pe_file)?;
+    if name.is_empty() || name.len() > 8 {
+        return Err(PEError::InvalidEdit(format!("section name {:?} must be 1 to 8 bytes long", name)));
+    }
+    if data.is_empty() {
+        return Err(PEError::InvalidEdit(String::from("a new section must have some data")));
+    }
+    let virtual_size = match u32::try_from(data.len()) {
+        Ok(virtual_size) => virtual_size,
+        Err(_) => return Err(PEError::InvalidEdit(String::from("section data is larger than 4 GiB"))),
+    };
+    make_room_for_section_header(pe_file, &mut sizes)?;
+
+    let virtual_address = pe_file
+        .sections
+        .iter()
+        .map(|section| section.header.virtual_address as u64 + section.header.mapped_size() as u64)
+        .fold(sizes.size_of_headers as u64, u64::max);
+    let virtual_address = align_up(virtual_address, sizes.section_alignment);
+    let size_of_raw_data = align_up(data.len() as u64, sizes.file_alignment);
+    if virtual_address + align_up(virtual_size as u64, sizes.section_alignment) > u32::MAX as u64 || size_of_raw_data > u32::MAX as u64 {
+        return Err(PEError::InvalidEdit(String::from("the image would be larger than 4 GiB")));
+    }
+
+    // the overlay, such as the certificate table, moves along to follow the new section's data
+    let old_overlay_offset = pe_file.overlay_offset();
+    let pointer_to_raw_data = align_up(old_overlay_offset, sizes.file_alignment);
+    shift_file_offsets(
+        pe_file,
+        old_overlay_offset,
+        (pointer_to_raw_data + size_of_raw_data - old_overlay_offset) as i64,
+    );
+
+    let mut name_bytes = [0u8; 8];
+    name_bytes[..name.len()].copy_from_slice(name.as_bytes());
+    let mut data = data.to_vec();
+    data.resize(size_of_raw_data as usize, 0);
+    pe_file.sections.push(Section {
+        header: SectionHeader {
+            name: name_bytes,
+            virtual_size,
+            virtual_address: virtual_address as u32,
+            size_of_raw_data: size_of_raw_data as u32,
+            pointer_to_raw_data: pointer_to_raw_data as u32,
+            pointer_to_relocations: 0,
+            pointer_to_line_numbers: 0,
+            number_of_relocations: 0,
+            number_of_line_numbers: 0,
+            characteristics,
+        },
+        data,
+    });
+    pe_file.coff_header.number_of_sections += 1;
+    add_to_size_fields(&mut sizes, characteristics, size_of_raw_data as i64);
+    sizes.size_of_image = get_size_of_image(pe_file, sizes.section_alignment);
+    set_image_sizes(pe_file, &sizes);
+    Ok(pe_file.sections.len() - 1)
+}
+
+/// Changes the size of a section's data, zero-filling it when it grows and moving the data of later sections in the
+/// file along to make room.
+///
+/// Sections must follow each other in memory with no gaps, so only the last section in memory can change its size
+/// rounded up to the section alignment. Shrinking fails if it would cut off a data directory.
+pub fn resize_section(pe_file: &mut PeFile, index: usize, new_size: u32) -> Result<(), PEError> {
+    let mut sizes = get_image_sizes(pe_file)?;
+    let header = match pe_file.sections.get(index) {
+        Some(section) => section.header.clone(),
+        None => return Err(PEError::InvalidEdit(format!("there is no section {}", index))),
+    };
+    if header.pointer_to_raw_data != 0 && pe_file.sections[index].data.len() < header.size_of_raw_data as usize {
+        return Err(PEError::InvalidEdit(format!("section {} runs past the end of the file", header.name_str())));
+    }
+    let old_size = header.mapped_size();
+    if !can_resize_in_place(pe_file, index, new_size) {
+        return Err(PEError::InvalidEdit(format!(
+            "section {} is followed by another section in memory, so can only be resized within its {:X}h-byte alignment",
+            header.name_str(),
+            sizes.section_alignment
+        )));
+    }
+    if header.virtual_address as u64 + align_up(new_size as u64, sizes.section_alignment) > u32::MAX as u64 {
+        return Err(PEError::InvalidEdit(String::from("the image would be larger than 4 GiB")));
+    }
+    if new_size < old_size {
+        let cut_start = header.virtual_address + new_size;
+        let cut_end = header.virtual_address + old_size;
+        if let Some(i) = find_directory_in(pe_file, cut_start, cut_end) {
+            return Err(PEError::InvalidEdit(format!(
+                "shrinking section {} would cut off the {}",
+                header.name_str(),
+                DataDirectory::DISPLAY_NAMES.get(i).copied().unwrap_or("data directory")
+            )));
+        }
+    }
+
+    // uninitialised sections have no raw data, so only their virtual size changes
+    let new_size_of_raw_data = if header.pointer_to_raw_data == 0 {
+        0
+    } else {
+        align_up(new_size as u64, sizes.file_alignment) as u32
+    };
+    let delta = new_size_of_raw_data as i64 - header.size_of_raw_data as i64;
+    if delta != 0 && header.pointer_to_raw_data != 0 {
+        shift_file_offsets(pe_file, header.pointer_to_raw_data as u64 + header.size_of_raw_data as u64, delta);
+    }
+    let section = &mut pe_file.sections[index];
+    section.data.resize(new_size_of_raw_data as usize, 0);
+    section.header.size_of_raw_data = new_size_of_raw_data;
+    section.header.virtual_size = new_size;
+    add_to_size_fields(&mut sizes, header.characteristics, delta);
+    sizes.size_of_image = get_size_of_image(pe_file, sizes.section_alignment);
+    set_image_sizes(pe_file, &sizes);
+    Ok(())
+}
+
+/// Checks whether a section can be given a new virtual size without running into the section after it in memory, which
+/// is always the case for the last section.
+pub fn can_resize_in_place(pe_file: &PeFile, index: usize, new_size: u32) -> bool {
+    let (Ok(sizes), Some(section)) = (get_image_sizes(pe_file), pe_file.sections.get(index)) else {
+        return false;
+    };
+    let header = &section.header;
+    let is_last = !pe_file.sections.iter().any(|section| section.header.virtual_address > header.virtual_address);
+    is_last || align_up(new_size as u64, sizes.section_alignment) == align_up(header.mapped_size() as u64, sizes.section_alignment)
+}
+
+/// Removes a section, returning the indices of the data directories that were cleared because they were in it.
+///
+/// The data of later sections in the file is moved back to close the gap. If the section is not the last in memory,
+/// the section before it is extended over its addresses, as sections must follow each other in memory with no gaps.
+/// Removing the base relocations also marks the image as not relocatable.
+pub fn remove_section(pe_file: &mut PeFile, index: usize) -> Result<Vec<usize>, PEError> {
+    let mut sizes = get_image_sizes(pe_file)?;
+    let header = match pe_file.sections.get(index) {
+        Some(section) => section.header.clone(),
+        None => return Err(PEError::InvalidEdit(format!("there is no section {}", index))),
+    };
+    let entry_point = match &pe_file.optional_header {
+        Some(OptionalHeader::Pe32(optional_header)) => optional_header.standard_fields.address_of_entry_point,
+        Some(OptionalHeader::Pe32Plus(optional_header)) => optional_header.standard_fields.address_of_entry_point,
+        None => 0,
+    };
+    if entry_point != 0 && header.contains_rva(entry_point) {
+        return Err(PEError::InvalidEdit(format!("section {} contains the entry point", header.name_str())));
+    }
+    let is_last = !pe_file.sections.iter().any(|section| section.header.virtual_address > header.virtual_address);
+    let previous = pe_file
+        .sections
+        .iter()
+        .enumerate()
+        .filter(|(_, section)| section.header.virtual_address < header.virtual_address)
+        .max_by_key(|(_, section)| section.header.virtual_address)
+        .map(|(i, _)| i);
+    if !is_last && previous.is_none() {
+        return Err(PEError::InvalidEdit(format!(
+            "section {} is the first in memory, so removing it would leave a gap after the headers",
+            header.name_str()
+        )));
+    }
+
+    // directories in the removed section would point at nothing
+    let virtual_end = header.virtual_address + header.mapped_size();
+    let mut cleared: Vec<usize> = Vec::new();
+    while let Some(i) = find_directory_in(pe_file, header.virtual_address, virtual_end) {
+        pe_file.data_directories[i] = DataDirectory { virtual_address: 0, size: 0 };
+        cleared.push(i);
+    }
+    if cleared.contains(&DataDirectory::BASE_RELOCATION_TABLE) {
+        pe_file.coff_header.characteristics.insert(CoffCharacteristics::RelocsStripped);
+        set_dll_characteristics(pe_file, DllCharacteristics::DynamicBase, false);
+    }
+
+    if !is_last {
+        let previous = &mut pe_file.sections[previous.unwrap()].header;
+        let end = header.virtual_address as u64 + align_up(header.mapped_size() as u64, sizes.section_alignment);
+        previous.virtual_size = (end - previous.virtual_address as u64) as u32;
+    }
+    pe_file.sections.remove(index);
+    if header.pointer_to_raw_data != 0 && header.size_of_raw_data != 0 {
+        shift_file_offsets(
+            pe_file,
+            header.pointer_to_raw_data as u64 + header.size_of_raw_data as u64,
+            -(header.size_of_raw_data as i64),
+        );
+    }
+    pe_file.coff_header.number_of_sections -= 1;
+    add_to_size_fields(&mut sizes, header.characteristics, -(header.size_of_raw_data as i64));
+    sizes.size_of_image = get_size_of_image(pe_file, sizes.section_alignment);
+    set_image_sizes(pe_file, &sizes);
+    Ok(cleared)
+}
+
+/// Makes sure there is room for one more section header before the first section's data, growing the headers if not.
+fn make_room_for_section_header(pe_file: &mut PeFile, sizes: &mut ImageSizes) -> Result<(), PEError> {
+    let table_end = pe_file.section_table_offset() + ((pe_file.sections.len() + 1) * size_of::<SectionHeader>()) as u64;
+    let first_rva = pe_file
+        .sections
+        .iter()
+        .map(|section| section.header.virtual_address as u64)
+        .min()
+        .unwrap_or(u64::MAX);
+    let first_raw = pe_file
+        .sections
+        .iter()
+        .filter(|section| section.header.pointer_to_raw_data != 0)
+        .map(|section| section.header.pointer_to_raw_data as u64)
+        .min()
+        .unwrap_or(u64::MAX);
+
+    // bound imports are usually kept right after the section table, and are only an optimisation, so can be dropped
+    if let Some(bound) = pe_file.data_directory(DataDirectory::BOUND_IMPORT).copied() {
+        if (bound.virtual_address as u64) < table_end && bound.virtual_address as u64 + bound.size as u64 > table_end - size_of::<SectionHeader>() as u64 {
+            pe_file.data_directories[DataDirectory::BOUND_IMPORT] = DataDirectory { virtual_address: 0, size: 0 };
+        }
+    }
+    if table_end <= sizes.size_of_headers as u64 && table_end <= first_raw {
+        return Ok(());
+    }
+
+    let new_size_of_headers = align_up(table_end, sizes.file_alignment);
+    if new_size_of_headers > first_rva {
+        return Err(PEError::InvalidEdit(String::from(
+            "there is no room for another section header before the first section in memory",
+        )));
+    }
+    let old_end = (sizes.size_of_headers as u64).min(first_raw);
+    if new_size_of_headers > old_end {
+        shift_file_offsets(pe_file, old_end, align_up(new_size_of_headers - old_end, sizes.file_alignment) as i64);
+    }
+    sizes.size_of_headers = new_size_of_headers as u32;
+    Ok(())
+}
+
+/// Moves everything at or after a file offset by `delta` bytes: section data, COFF relocations and line numbers, gaps,
+/// the certificate table, the COFF symbol table, and the data that debug directory entries point to.
+fn shift_file_offsets(pe_file: &mut PeFile, from: u64, delta: i64) {
+    let shift = |offset: u32| {
+        if offset != 0 && offset as u64 >= from {
+            (offset as i64 + delta) as u32
+        } else {
+            offset
+        }
+    };
+
+    // the debug directory lives in a section, so is patched before the sections move
+    if let Some(debug_dir) = pe_file.data_directory(DataDirectory::DEBUG).copied() {
+        if let Some((index, start)) = pe_file.find_rva(debug_dir.virtual_address) {
+            let data = &mut pe_file.sections[index].data;
+            let end = (start + debug_dir.size as usize).min(data.len());
+            for entry in data[start..end].chunks_exact_mut(size_of::<DebugDirectory>()) {
+                let field = &mut entry[DebugDirectory::POINTER_TO_RAW_DATA_OFFSET..DebugDirectory::POINTER_TO_RAW_DATA_OFFSET + 4];
+                let pointer = u32::from_le_bytes(field.try_into().unwrap());
+                field.copy_from_slice(&shift(pointer).to_le_bytes());
+            }
+        }
+    }
+    for section in &mut pe_file.sections {
+        section.header.pointer_to_raw_data = shift(section.header.pointer_to_raw_data);
+        section.header.pointer_to_relocations = shift(section.header.pointer_to_relocations);
+        section.header.pointer_to_line_numbers = shift(section.header.pointer_to_line_numbers);
+    }
+    for gap in pe_file.gaps.iter_mut().filter(|gap| gap.offset >= from) {
+        gap.offset = (gap.offset as i64 + delta) as u64;
+    }
+    if let Some(cert) = pe_file.data_directories.get_mut(DataDirectory::CERTIFICATE_TABLE) {
+        // unlike other data directories, the certificate table's address is a file offset
+        cert.virtual_address = shift(cert.virtual_address);
+    }
+    pe_file.coff_header.pointer_to_symbol_table = shift(pe_file.coff_header.pointer_to_symbol_table);
+}
+
+/// Finds a data directory, other than the certificate table, that overlaps the RVAs `start..end`.
+fn find_directory_in(pe_file: &PeFile, start: u32, end: u32) -> Option<usize> {
+    pe_file.data_directories.iter().enumerate().position(|(i, dir)| {
+        i != DataDirectory::CERTIFICATE_TABLE
+            && dir.virtual_address != 0
+            && dir.virtual_address < end
+            && dir.virtual_address as u64 + dir.size.max(1) as u64 > start as u64
+    })
+}
+
+fn get_size_of_image(pe_file: &PeFile, section_alignment: u32) -> u32 {
+    let end = pe_file
+        .sections
+        .iter()
+        .map(|section| section.header.virtual_address as u64 + section.header.mapped_size() as u64)
+        .max()
+        .unwrap_or(0);
+    align_up(end, section_alignment).min(u32::MAX as u64) as u32
+}
+
+/// Adds to the code, initialised data or uninitialised data size totals, according to a section's flags.
+fn add_to_size_fields(sizes: &mut ImageSizes, characteristics: SectionFlags, delta: i64) {
+    let add = |size: u32| (size as i64 + delta).clamp(0, u32::MAX as i64) as u32;
+    if characteristics.contains(SectionFlags::CntCode) {
+        sizes.size_of_code = add(sizes.size_of_code);
+    }
+    if characteristics.contains(SectionFlags::CntInitialisedData) {
+        sizes.size_of_initialised_data = add(sizes.size_of_initialised_data);
+    }
+    if characteristics.contains(SectionFlags::CntUninitialisedData) {
+        sizes.size_of_uninitialised_data = add(sizes.size_of_uninitialised_data);
+    }
+}
+
+fn align_up(value: u64, alignment: u32) -> u64 {
+    if alignment == 0 {
+        value
+    } else {
+        value.div_ceil(alignment as u64) * alignment as u64
+    }
+}
+
+/// Gathers the size fields, checking that the alignments are ones the loader would accept, since everything added or
+/// moved is aligned to them.
+fn get_image_sizes(pe_file: &PeFile) -> Result<ImageSizes, PEError> {
+    let sizes = match &pe_file.optional_header {
+        Some(OptionalHeader::Pe32(optional_header)) => ImageSizes {
+            size_of_code: optional_header.standard_fields.size_of_code,
+            size_of_initialised_data: optional_header.standard_fields.size_of_initialised_data,
+            size_of_uninitialised_data: optional_header.standard_fields.size_of_uninitialised_data,
+            section_alignment: optional_header.windows_fields.section_alignment,
+            file_alignment: optional_header.windows_fields.file_alignment,
+            size_of_image: optional_header.windows_fields.size_of_image,
+            size_of_headers: optional_header.windows_fields.size_of_headers,
+        },
+        Some(OptionalHeader::Pe32Plus(optional_header)) => ImageSizes {
+            size_of_code: optional_header.standard_fields.size_of_code,
+            size_of_initialised_data: optional_header.standard_fields.size_of_initialised_data,
+            size_of_uninitialised_data: optional_header.standard_fields.size_of_uninitialised_data,
+            section_alignment: optional_header.windows_fields.section_alignment,
+            file_alignment: optional_header.windows_fields.file_alignment,
+            size_of_image: optional_header.windows_fields.size_of_image,
+            size_of_headers: optional_header.windows_fields.size_of_headers,
+        },
+        None => return Err(PEError::InvalidEdit(String::from("sections can only be edited in images, not object files"))),
+    };
+    if !sizes.file_alignment.is_power_of_two() || sizes.file_alignment > MAX_FILE_ALIGNMENT {
+        return Err(PEError::InvalidEdit(format!(
+            "file alignment {:X}h is not a power of two up to 64 KiB",
+            sizes.file_alignment
+        )));
+    }
+    if !sizes.section_alignment.is_power_of_two() || sizes.section_alignment < sizes.file_alignment {
+        return Err(PEError::InvalidEdit(format!(
+            "section alignment {:X}h is not a power of two at least the file alignment",
+            sizes.section_alignment
+        )));
+    }
+    Ok(sizes)
+}
+
+fn set_image_sizes(pe_file: &mut PeFile, sizes: &ImageSizes) {
+    match &mut pe_file.optional_header {
+        Some(OptionalHeader::Pe32(optional_header)) => {
+            optional_header.standard_fields.size_of_code = sizes.size_of_code;
+            optional_header.standard_fields.size_of_initialised_data = sizes.size_of_initialised_data;
+            optional_header.standard_fields.size_of_uninitialised_data = sizes.size_of_uninitialised_data;
+            optional_header.windows_fields.size_of_image = sizes.size_of_image;
+            optional_header.windows_fields.size_of_headers = sizes.size_of_headers;
+        }
+        Some(OptionalHeader::Pe32Plus(optional_header)) => {
+            optional_header.standard_fields.size_of_code = sizes.size_of_code;
+            optional_header.standard_fields.size_of_initialised_data = sizes.size_of_initialised_data;
+            optional_header.standard_fields.size_of_uninitialised_data = sizes.size_of_uninitialised_data;
+            optional_header.windows_fields.size_of_image = sizes.size_of_image;
+            optional_header.windows_fields.size_of_headers = sizes.size_of_headers;
+        }
+        None => (),
+    }
+}
+
+fn set_dll_characteristics(pe_file: &mut PeFile, flags: DllCharacteristics, value: bool) {
+    match &mut pe_file.optional_header {
+        Some(OptionalHeader::Pe32(optional_header)) => optional_header.windows_fields.dll_characteristics.set(flags, value),
+        Some(OptionalHeader::Pe32Plus(optional_header)) => optional_header.windows_fields.dll_characteristics.set(flags, value),
+        None => (),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::debug::{get_debug_data, get_debug_directories};
+    use crate::pe::imports::get_imports;
+    use crate::pe::internal::testdata;
+
+    /// The imported function names, and the data of each debug entry, as read back from the serialised file.
+    fn imports_and_debug_data(pe_file: &PeFile) -> (Vec<String>, Vec<Vec<u8>>) {
+        let (mut fh, headers, section_table) = testdata::reparse(pe_file);
+        let imports = get_imports(&mut fh, headers.as_ref(), &section_table).unwrap();
+        let names = imports
+            .iter()
+            .flat_map(|dll| {
+                dll.functions
+                    .iter()
+                    .map(move |function| format!("{}!{}", dll.dll_name, function.display_name()))
+            })
+            .collect();
+        let debug_directories = get_debug_directories(&mut fh, headers.as_ref(), &section_table).unwrap();
+        let debug_data = debug_directories.iter().map(|entry| get_debug_data(&mut fh, entry).unwrap()).collect();
+        (names, debug_data)
+    }
+
+    fn section_index(pe_file: &PeFile, name: &str) -> usize {
+        pe_file.sections.iter().position(|section| section.header.name_str() == name).unwrap()
+    }
+
+    #[test]
+    fn adding_sections_keeps_imports_and_debug_readable() {
+        for name in ["test-x64.dll", "test-x86.dll"] {
+            let mut pe_file = testdata::read_pe_file(name);
+            let expected = imports_and_debug_data(&pe_file);
+            assert_eq!(expected.0.len(), 3);
+            assert!(expected.1[0].starts_with(b"RSDS"));
+
+            // enough sections to fill the headers, so that they have to grow and move everything after them
+            let first_raw = pe_file.sections[0].header.pointer_to_raw_data;
+            for i in 0..20 {
+                let index = add_section(
+                    &mut pe_file,
+                    &format!(".cfg{}", i),
+                    SectionFlags::CntInitialisedData | SectionFlags::MemRead,
+                    b"config",
+                )
+                .unwrap();
+                assert_eq!(pe_file.sections[index].data[..6], *b"config");
+            }
+            assert!(pe_file.sections[0].header.pointer_to_raw_data > first_raw, "{}", name);
+            assert_eq!(imports_and_debug_data(&pe_file), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn resizing_sections_keeps_imports_and_debug_readable() {
+        for name in ["test-x64.dll", "test-x86.dll"] {
+            let mut pe_file = testdata::read_pe_file(name);
+            let expected = imports_and_debug_data(&pe_file);
+
+            // growing .text within its section alignment moves the raw data of the sections after it in the file
+            let text = section_index(&pe_file, ".text");
+            let rdata_raw = pe_file.sections[section_index(&pe_file, ".rdata")].header.pointer_to_raw_data;
+            resize_section(&mut pe_file, text, 0xC00).unwrap();
+            assert_eq!(pe_file.sections[text].header.size_of_raw_data, 0xC00);
+            assert!(pe_file.sections[section_index(&pe_file, ".rdata")].header.pointer_to_raw_data > rdata_raw);
+            // only the last section in memory can grow past its alignment
+            assert!(resize_section(&mut pe_file, text, 0x1001).is_err());
+            let last = pe_file.sections.len() - 1;
+            resize_section(&mut pe_file, last, 0x3000).unwrap();
+            assert_eq!(imports_and_debug_data(&pe_file), expected, "{}", name);
+
+            resize_section(&mut pe_file, text, 0x10).unwrap();
+            assert_eq!(imports_and_debug_data(&pe_file), expected, "{}", name);
+        }
+    }
+
+    #[test]
+    fn removing_sections_keeps_imports_and_debug_readable() {
+        for name in ["test-x64.dll", "test-x86.dll"] {
+            let mut pe_file = testdata::read_pe_file(name);
+            let expected = imports_and_debug_data(&pe_file);
+
+            let data = section_index(&pe_file, ".data");
+            assert!(remove_section(&mut pe_file, data).unwrap().is_empty());
+            let reloc = section_index(&pe_file, ".reloc");
+            assert_eq!(remove_section(&mut pe_file, reloc).unwrap(), [DataDirectory::BASE_RELOCATION_TABLE]);
+            assert!(pe_file.coff_header.characteristics.contains(CoffCharacteristics::RelocsStripped));
+            assert!(!pe_file.sections.iter().any(|section| section.header.name_str() == ".data"));
+            assert_eq!(imports_and_debug_data(&pe_file), expected, "{}", name);
+
+            // the entry point is in .text
+            let text = section_index(&pe_file, ".text");
+            assert!(remove_section(&mut pe_file, text).is_err());
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::file::{OptionalHeader, PeFile, PE_SIGNATURE_LEN};
+use super::headers::{CoffHeader, DataDirectory, DosHeader};
+use std::fs;
+use std::mem::size_of;
+use std::path::Path;
+use std::slice;
+
+/// Largest file that will be written, to catch headers that would place data absurdly far into the file.
+const MAX_FILE_LEN: u64 = 0x1_0000_0000;
+
+/// Serialises a `PeFile`, writing each part at the offset its headers give. An unmodified `PeFile` from
+/// `deser::get_pe_file` gives back the bytes it was read from.
+///
+/// Gaps are written first, so that if a modified header now overlaps one, the header wins.
+pub fn pe_file_to_bytes(pe_file: &PeFile) -> Result<Vec<u8>, PEError> {
+    let mut out: Vec<u8> = Vec::new();
+    for gap in &pe_file.gaps {
+        put(&mut out, gap.offset, &gap.data)?;
+    }
+
+    if let Some(dos_header) = &pe_file.dos_header {
+        put(&mut out, 0, struct_bytes(dos_header))?;
+        put(&mut out, size_of::<DosHeader>() as u64, &pe_file.dos_stub)?;
+        put(&mut out, pe_file.coff_header_offset() - PE_SIGNATURE_LEN, b"PE\0\0")?;
+    }
+    put(&mut out, pe_file.coff_header_offset(), struct_bytes::<CoffHeader>(&pe_file.coff_header))?;
+    if let Some(optional_header) = &pe_file.optional_header {
+        let offset = pe_file.optional_header_offset();
+        match optional_header {
+            OptionalHeader::Pe32(optional_header) => put(&mut out, offset, struct_bytes(optional_header))?,
+            OptionalHeader::Pe32Plus(optional_header) => put(&mut out, offset, struct_bytes(optional_header))?,
+        }
+        let mut dir_offset = offset + optional_header.size();
+        for dir in &pe_file.data_directories {
+            put(&mut out, dir_offset, struct_bytes::<DataDirectory>(dir))?;
+            dir_offset += size_of::<DataDirectory>() as u64;
+        }
+    }
+
+    let mut header_offset = pe_file.section_table_offset();
+    for section in &pe_file.sections {
+        put(&mut out, header_offset, struct_bytes::<SectionHeader>(&section.header))?;
+        header_offset += size_of::<SectionHeader>() as u64;
+    }
+    for section in pe_file.sections.iter().filter(|section| section.header.pointer_to_raw_data != 0) {
+        put(&mut out, section.header.pointer_to_raw_data as u64, &section.data)?;
+    }
+
+    put(&mut out, pe_file.overlay_offset(), &pe_file.overlay)?;
+    Ok(out)
+}
+
+/// Serialises a `PeFile` and writes it to a file.
+pub fn write_pe_file(pe_file: &PeFile, path: &Path) -> Result<(), PEError> {
+    let bytes = pe_file_to_bytes(pe_file)?;
+    match fs::write(path, bytes) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(PEError::SerialiseError(format!("{}: {}", path.display(), err))),
+    }
+}
+
+/// Copies bytes into the output at an offset, zero-filling up to it if the output is shorter. Nothing is written for
+/// empty data, so that a section whose raw data is past EOF does not extend the file.
+fn put(out: &mut Vec<u8>, offset: u64, bytes: &[u8]) -> Result<(), PEError> {
+    if bytes.is_empty() {
+        return Ok(());
+    }
+    let end = offset + bytes.len() as u64;
+    if end > MAX_FILE_LEN {
+        return Err(PEError::SerialiseError(format!("data at {:X}h would make the file too large", offset)));
+    }
+    if end as usize > out.len() {
+        out.resize(end as usize, 0);
+    }
+    out[offset as usize..end as usize].copy_from_slice(bytes);
+    Ok(())
+}
+
+/// Views a header struct as the bytes it was transmuted from in `deser`.
+fn struct_bytes<T>(value: &T) -> &[u8] {
+    // the header structs are all `repr(C)` with no padding, so every byte is initialised
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pe::internal::testdata;
+
+    #[test]
+    fn unmodified_file_round_trips() {
+        for name in ["test-x64.dll", "test-x86.dll", "test-x64.obj"] {
+            let pe_file = testdata::read_pe_file(name);
+            assert!(pe_file_to_bytes(&pe_file).unwrap() == testdata::read(name), "{} changed", name);
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use super::body::{SectionFlags, SectionHeader};
+use super::err::PEError;
+use super::internal::agnostic_fio::read_up_to;
+use super::overlay::get_overlay;
+use super::traits::PEHeader;
+use std::fmt::Display;
+use std::fs::File;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum StringEncoding {
+    /// Printable ASCII, one byte per character.
+    Ascii,
+    /// Printable ASCII characters as UTF-16LE code units, as used by most Windows APIs.
+    Utf16le,
+}
+
+/// Which strings to look for.
+#[derive(Debug, Clone, Copy)]
+pub struct StringOptions {
+    /// Fewest characters a string must have to be reported. Zero is treated as one, as empty strings are never reported.
+    pub min_len: usize,
+    pub ascii: bool,
+    pub utf16le: bool,
+    /// Only search sections with initialised data, and the overlay, skipping code.
+    pub data_only: bool,
+}
+
+/// A string found in a file, and where it was found.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FoundString {
+    /// Name of the section the string is in, or `None` if it is in the overlay.
+    pub section: Option<String>,
+    pub offset: u64,
+    /// RVA of the string, or `None` if it is not mapped, such as in the overlay or in an object file.
+    pub rva: Option<u32>,
+    pub encoding: StringEncoding,
+    pub value: String,
+}
+
+impl Display for StringEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StringEncoding::Ascii => f.pad("ascii"),
+            StringEncoding::Utf16le => f.pad("utf16le"),
+        }
+    }
+}
+
+/// Finds strings in each section's raw data and in the overlay, in file order within each.
+pub fn get_strings(
+    fh: &mut File,
+    headers: &(impl PEHeader + ?Sized),
+    section_table: &[SectionHeader],
+    options: &StringOptions,
+) -> Result<Vec<FoundString>, PEError> {
+    let is_image = headers.optional_header_pe32().is_some() || headers.optional_header_pe32plus().is_some();
+    let mut ret: Vec<FoundString> = Vec::new();
+    for section in section_table {
+        if options.data_only && !section.characteristics.contains(SectionFlags::CntInitialisedData) {
+            continue;
+        }
+        if section.pointer_to_raw_data == 0 {
+            continue;
+        }
+        let data = match read_up_to(fh, section.pointer_to_raw_data as u64, section.size_of_raw_data as usize) {
+            Ok(data) => data,
+            Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+        };
+        for (start, encoding, value) in find_strings(&data, options) {
+            // raw data is padded to the file alignment, and the padding is not mapped
+            let rva = (is_image && (start as u64) < section.mapped_size() as u64).then(|| section.virtual_address + start as u32);
+            ret.push(FoundString {
+                section: Some(section.name_str()),
+                offset: section.pointer_to_raw_data as u64 + start as u64,
+                rva,
+                encoding,
+                value,
+            });
+        }
+    }
+
+    if let Some(overlay) = get_overlay(fh, headers, section_table)? {
+        let data = match read_up_to(fh, overlay.offset, overlay.size as usize) {
+            Ok(data) => data,
+            Err(err) => return Err(PEError::DeserialiseError(err.to_string())),
+        };
+        for (start, encoding, value) in find_strings(&data, options) {
+            ret.push(FoundString {
+                section: None,
+                offset: overlay.offset + start as u64,
+                rva: None,
+                encoding,
+                value,
+            });
+        }
+    }
+    Ok(ret)
+}
+
+/// Finds runs of printable characters in a buffer, returning their start offsets, sorted, with their encodings.
+pub fn find_strings(data: &[u8], options: &StringOptions) -> Vec<(usize, StringEncoding, String)> {
+    let min_len = options.min_len.max(1);
+    let mut ret: Vec<(usize, StringEncoding, String)> = Vec::new();
+    if options.ascii {
+        find_runs(data, 0, 1, min_len, StringEncoding::Ascii, &mut ret);
+    }
+    if options.utf16le {
+        // a string can start at an odd offset, so both alignments are searched
+        for alignment in 0..data.len().min(2) {
+            find_runs(data, alignment, 2, min_len, StringEncoding::Utf16le, &mut ret);
+        }
+    }
+    ret.sort_by_key(|found| found.0);
+    ret
+}
+
+/// Finds runs of printable characters of `width` bytes each from `base` onwards, where every byte after the first is
+/// zero.
+fn find_runs(data: &[u8], base: usize, width: usize, min_len: usize, encoding: StringEncoding, ret: &mut Vec<(usize, StringEncoding, String)>) {
+    let mut start = 0;
+    let mut value = String::new();
+    for (i, unit) in data[base..].chunks_exact(width).enumerate() {
+        if is_printable(unit[0]) && unit[1..].iter().all(|&b| b == 0) {
+            if value.is_empty() {
+                start = base + i * width;
+            }
+            value.push(unit[0] as char);
+        } else {
+            if value.len() >= min_len {
+                ret.push((start, encoding, value.clone()));
+            }
+            value.clear();
+        }
+    }
+    if value.len() >= min_len {
+        ret.push((start, encoding, value));
+    }
+}
+
+fn is_printable(b: u8) -> bool {
+    b == b'\t' || (0x20..0x7f).contains(&b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_min_len_finds_no_empty_strings() {
+        let options = StringOptions {
+            min_len: 0,
+            ascii: true,
+            utf16le: false,
+            data_only: false,
+        };
+        let found = find_strings(b"\0ab\0\0c", &options);
+        let values: Vec<&str> = found.iter().map(|(_, _, value)| value.as_str()).collect();
+        assert_eq!(values, ["ab", "c"]);
+    }
+}
@@ -0,0 +1,106 @@
+use super::body::SectionHeader;
+use super::err::PEError;
+use super::headers::DataDirectory;
+use super::internal::agnostic_fio::read_exact;
+use super::internal::rva::rva_to_addr;
+use super::traits::PEHeader;
+use std::fs::File;
+use std::io;
+
+/// Most TLS callbacks that will be read, to bound unterminated arrays.
+const MAX_CALLBACKS: usize = 0x100;
+
+/// The TLS directory (`IMAGE_TLS_DIRECTORY32/64`), widened to 64 bits. Addresses are VAs, not RVAs.
+#[derive(Debug, Clone, Copy)]
+pub struct TlsDirectory {
+    pub start_address_of_raw_data: u64,
+    pub end_address_of_raw_data: u64,
+    pub address_of_index: u64,
+    /// VA of a null-terminated array of callback VAs.
+    pub address_of_callbacks: u64,
+    pub size_of_zero_fill: u32,
+    pub characteristics: u32,
+}
+
+/// The TLS directory of an image, with its callbacks.
+#[derive(Debug, Clone)]
+pub struct Tls {
+    pub directory: TlsDirectory,
+    /// RVAs of the TLS callbacks, which run before the entry point.
+    pub callbacks: Vec<u32>,
+}
+
+/// Reads the TLS directory and its callbacks, or `None` if the image has no TLS directory.
+pub fn get_tls(fh: &mut File, headers: &(impl PEHeader + ?Sized), section_table: &[SectionHeader]) -> Result<Option<Tls>, PEError> {
+    let tls_dir = match headers.data_directory(DataDirectory::TLS_TABLE) {
+        Some(dir) => dir,
+        None => return Ok(None),
+    };
+    let image_base = match (headers.optional_header_pe32(), headers.optional_header_pe32plus()) {
+        (Some(pe32_header), _) => pe32_header.windows_fields.image_base as u64,
+        (_, Some(pe32plus_header)) => pe32plus_header.windows_fields.image_base,
+        _ => return Ok(None),
+    };
+
+    match read_tls(fh, tls_dir, section_table, headers.is_pe32plus(), image_base) {
+        Ok(tls) => Ok(Some(tls)),
+        Err(err) => Err(PEError::DeserialiseError(err.to_string())),
+    }
+}
+
+fn read_tls(fh: &mut File, tls_dir: &DataDirectory, section_table: &[SectionHeader], is_pe32plus: bool, image_base: u64) -> io::Result<Tls> {
+    let addr = rva_to_addr(section_table, tls_dir.virtual_address)?;
+    let directory = if is_pe32plus {
+        let bytes = read_exact::<40>(fh, addr)?;
+        let u64_at = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        TlsDirectory {
+            start_address_of_raw_data: u64_at(0),
+            end_address_of_raw_data: u64_at(8),
+            address_of_index: u64_at(16),
+            address_of_callbacks: u64_at(24),
+            size_of_zero_fill: u32::from_le_bytes(bytes[32..36].try_into().unwrap()),
+            characteristics: u32::from_le_bytes(bytes[36..40].try_into().unwrap()),
+        }
+    } else {
+        let bytes = read_exact::<24>(fh, addr)?;
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        TlsDirectory {
+            start_address_of_raw_data: u32_at(0) as u64,
+            end_address_of_raw_data: u32_at(4) as u64,
+            address_of_index: u32_at(8) as u64,
+            address_of_callbacks: u32_at(12) as u64,
+            size_of_zero_fill: u32_at(16),
+            characteristics: u32_at(20),
+        }
+    };
+
+    let mut callbacks: Vec<u32> = Vec::new();
+    if directory.address_of_callbacks != 0 {
+        let array_rva = va_to_rva(directory.address_of_callbacks, image_base)?;
+        let mut entry_addr = rva_to_addr(section_table, array_rva)?;
+        let entry_size = if is_pe32plus { 8 } else { 4 };
+        loop {
+            let callback = if is_pe32plus {
+                u64::from_le_bytes(read_exact(fh, entry_addr)?)
+            } else {
+                u32::from_le_bytes(read_exact(fh, entry_addr)?) as u64
+            };
+            if callback == 0 {
+                break;
+            }
+            if callbacks.len() >= MAX_CALLBACKS {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "too many TLS callbacks"));
+            }
+            callbacks.push(va_to_rva(callback, image_base)?);
+            entry_addr += entry_size;
+        }
+    }
+    Ok(Tls { directory, callbacks })
+}
+
+fn va_to_rva(va: u64, image_base: u64) -> io::Result<u32> {
+    match va.checked_sub(image_base).and_then(|rva| u32::try_from(rva).ok()) {
+        Some(rva) => Ok(rva),
+        None => Err(io::Error::new(io::ErrorKind::InvalidData, format!("VA {:X}h is outside the image", va))),
+    }
+}
@@ -5,4 +5,13 @@ pub trait PEHeader {
     fn optional_header_pe32(&self) -> Option<&OptionalHeaderPe32>;
     fn optional_header_pe32plus(&self) -> Option<&OptionalHeaderPe32Plus>;
     fn data_directories(&self) -> Option<&Vec<DataDirectory>>;
+
+    /// Gets a data directory by index, or `None` if it is absent or unused.
+    fn data_directory(&self, index: usize) -> Option<&DataDirectory> {
+        self.data_directories()?.get(index).filter(|dir| dir.virtual_address != 0)
+    }
+
+    fn is_pe32plus(&self) -> bool {
+        self.optional_header_pe32plus().is_some()
+    }
 }
@@ -0,0 +1,164 @@
+use super::err::PEError;
+
+/// Deepest node that will be followed. Well-formed version resources are four levels deep.
+const MAX_DEPTH: usize = 8;
+/// Size of the length, value length and type fields that start each node.
+const NODE_HEADER_LEN: usize = 6;
+/// Marks the fixed file info (`VS_FIXEDFILEINFO`) that is the root node's value.
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xFEEF04BD;
+/// Offset of `dwFileVersionMS` in the fixed file info. `dwFileVersionLS` and the product version follow it.
+const FILE_VERSION_OFFSET: usize = 8;
+/// Offset of `dwProductVersionMS` in the fixed file info.
+const PRODUCT_VERSION_OFFSET: usize = 16;
+/// Key of the node holding the string tables.
+const STRING_FILE_INFO_KEY: &str = "StringFileInfo";
+
+/// A node of a version resource (`VS_VERSIONINFO` and the `StringFileInfo`, `StringTable`, `String`, `VarFileInfo` and
+/// `Var` structures below it), which all share the same layout.
+#[derive(Debug, Clone)]
+pub struct VersionNode {
+    pub key: String,
+    /// Whether the value is a string, in which case its length is counted in UTF-16 code units rather than bytes.
+    pub is_text: bool,
+    pub value: Vec<u8>,
+    pub children: Vec<VersionNode>,
+}
+
+impl VersionNode {
+    /// Sets the file version in the fixed file info, along with the `FileVersion` string in every string table.
+    pub fn set_file_version(&mut self, version: [u16; 4]) -> Result<(), PEError> {
+        self.set_fixed_version(FILE_VERSION_OFFSET, version)?;
+        self.set_string("FileVersion", &format_version(version));
+        Ok(())
+    }
+
+    /// Sets the product version in the fixed file info, along with the `ProductVersion` string in every string table.
+    pub fn set_product_version(&mut self, version: [u16; 4]) -> Result<(), PEError> {
+        self.set_fixed_version(PRODUCT_VERSION_OFFSET, version)?;
+        self.set_string("ProductVersion", &format_version(version));
+        Ok(())
+    }
+
+    /// Sets a string, such as `CompanyName`, in every string table, returning how many tables there are.
+    pub fn set_string(&mut self, key: &str, value: &str) -> usize {
+        let value_bytes: Vec<u8> = value.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect();
+        let mut tables = 0;
+        for string_file_info in self.children.iter_mut().filter(|child| child.key == STRING_FILE_INFO_KEY) {
+            for table in &mut string_file_info.children {
+                match table.children.iter_mut().find(|string| string.key == key) {
+                    Some(string) => {
+                        string.is_text = true;
+                        string.value = value_bytes.clone();
+                    }
+                    None => table.children.push(VersionNode {
+                        key: String::from(key),
+                        is_text: true,
+                        value: value_bytes.clone(),
+                        children: Vec::new(),
+                    }),
+                }
+                tables += 1;
+            }
+        }
+        tables
+    }
+
+    fn set_fixed_version(&mut self, offset: usize, version: [u16; 4]) -> Result<(), PEError> {
+        let signature = self.value.get(..4).map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()));
+        if signature != Some(FIXED_FILE_INFO_SIGNATURE) || self.value.len() < offset + 8 {
+            return Err(PEError::InvalidEdit(String::from("the version resource has no fixed file info")));
+        }
+        let most_significant = (version[0] as u32) << 16 | version[1] as u32;
+        let least_significant = (version[2] as u32) << 16 | version[3] as u32;
+        self.value[offset..offset + 4].copy_from_slice(&most_significant.to_le_bytes());
+        self.value[offset + 4..offset + 8].copy_from_slice(&least_significant.to_le_bytes());
+        Ok(())
+    }
+}
+
+/// Parses a version resource's data.
+pub fn parse_version_info(data: &[u8]) -> Result<VersionNode, PEError> {
+    match parse_node(data, 0, 0) {
+        Ok((node, _)) => Ok(node),
+        Err(msg) => Err(PEError::DeserialiseError(format!("version resource {}", msg))),
+    }
+}
+
+/// Serialises a version resource, padding each node to 4 bytes.
+pub fn version_info_to_bytes(node: &VersionNode) -> Result<Vec<u8>, PEError> {
+    let mut out: Vec<u8> = Vec::new();
+    write_node(node, &mut out)?;
+    Ok(out)
+}
+
+/// Formats a version the way resource compilers write it into the string tables, such as `1.2.0.0`.
+pub fn format_version(version: [u16; 4]) -> String {
+    format!("{}.{}.{}.{}", version[0], version[1], version[2], version[3])
+}
+
+/// Parses the node at `start`, returning it along with the offset just past its end.
+fn parse_node(data: &[u8], start: usize, depth: usize) -> Result<(VersionNode, usize), String> {
+    if depth >= MAX_DEPTH {
+        return Err(String::from("is too deep"));
+    }
+    let read_u16 = |offset: usize| data.get(offset..offset + 2).map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]));
+    let (Some(len), Some(value_len), Some(value_type)) = (read_u16(start), read_u16(start + 2), read_u16(start + 4)) else {
+        return Err(String::from("is cut off"));
+    };
+    let end = start + len as usize;
+    if (len as usize) < NODE_HEADER_LEN || end > data.len() {
+        return Err(format!("has a node with a bad length at {:X}h", start));
+    }
+
+    let mut units: Vec<u16> = Vec::new();
+    let mut pos = start + NODE_HEADER_LEN;
+    loop {
+        match read_u16(pos).filter(|_| pos + 2 <= end) {
+            Some(0) => break,
+            Some(unit) => units.push(unit),
+            None => return Err(format!("has an unterminated key at {:X}h", start)),
+        }
+        pos += 2;
+    }
+    let key = String::from_utf16_lossy(&units);
+    pos = (pos + 2).next_multiple_of(4).min(end);
+
+    let is_text = value_type == 1;
+    let value_end = (pos + if is_text { value_len as usize * 2 } else { value_len as usize }).min(end);
+    let value = data[pos..value_end].to_vec();
+    pos = value_end.next_multiple_of(4);
+
+    let mut children: Vec<VersionNode> = Vec::new();
+    while pos + NODE_HEADER_LEN <= end {
+        let (child, child_end) = parse_node(data, pos, depth + 1)?;
+        children.push(child);
+        pos = child_end.next_multiple_of(4);
+    }
+    Ok((VersionNode { key, is_text, value, children }, end))
+}
+
+fn write_node(node: &VersionNode, out: &mut Vec<u8>) -> Result<(), PEError> {
+    let start = out.len();
+    out.extend_from_slice(&[0; NODE_HEADER_LEN]);
+    out.extend(node.key.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+    pad_to_4(out);
+    out.extend_from_slice(&node.value);
+    for child in &node.children {
+        pad_to_4(out);
+        write_node(child, out)?;
+    }
+
+    let len = out.len() - start;
+    let value_len = if node.is_text { node.value.len() / 2 } else { node.value.len() };
+    if len > u16::MAX as usize || value_len > u16::MAX as usize {
+        return Err(PEError::SerialiseError(format!("version resource node {:?} is too large", node.key)));
+    }
+    out[start..start + 2].copy_from_slice(&(len as u16).to_le_bytes());
+    out[start + 2..start + 4].copy_from_slice(&(value_len as u16).to_le_bytes());
+    out[start + 4..start + 6].copy_from_slice(&(node.is_text as u16).to_le_bytes());
+    Ok(())
+}
+
+fn pad_to_4(out: &mut Vec<u8>) {
+    out.resize(out.len().next_multiple_of(4), 0);
+}
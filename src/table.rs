@@ -0,0 +1,111 @@
+//! Delimited tabular output, such as CSV and TSV.
+
+use crate::pe::body::SectionHeader;
+use crate::pe::entropy::shannon_entropy;
+use crate::pe::exports::Exports;
+use crate::pe::hashes::get_digests;
+use crate::pe::imports::ImportedDll;
+use crate::pe::strings::FoundString;
+
+/// Joins fields into a row, quoting any that contain the delimiter, a quote or a line break.
+pub fn format_row<S: AsRef<str>>(fields: &[S], delimiter: char) -> String {
+    let quoted: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([delimiter, '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    quoted.join(&delimiter.to_string())
+}
+
+/// Prints one row per section, with its hashes and entropy.
+pub fn print_sections_table(sections: &[(usize, &SectionHeader)], section_data: &[Vec<u8>], delimiter: char) {
+    let columns = [
+        "index",
+        "name",
+        "virtual_address",
+        "virtual_size",
+        "pointer_to_raw_data",
+        "size_of_raw_data",
+        "characteristics",
+        "flags",
+        "entropy",
+        "md5",
+        "sha256",
+    ];
+    println!("{}", format_row(&columns, delimiter));
+    for ((i, header), data) in sections.iter().zip(section_data) {
+        let digests = get_digests(data);
+        let flags: Vec<&str> = header.characteristics.iter_names().map(|(name, _)| name).collect();
+        let row = [
+            i.to_string(),
+            header.name_str(),
+            header.virtual_address.to_string(),
+            header.virtual_size.to_string(),
+            header.pointer_to_raw_data.to_string(),
+            header.size_of_raw_data.to_string(),
+            format!("0x{:08x}", header.characteristics.bits()),
+            flags.join(" | "),
+            format!("{:.4}", shannon_entropy(data)),
+            digests.md5,
+            digests.sha256,
+        ];
+        println!("{}", format_row(&row, delimiter));
+    }
+}
+
+/// Prints one row per imported function.
+pub fn print_imports_table(imports: &[ImportedDll], delimiter: char) {
+    println!("{}", format_row(&["dll", "function", "ordinal", "hint", "thunk_rva"], delimiter));
+    for dll in imports {
+        for function in &dll.functions {
+            let row = [
+                dll.dll_name.clone(),
+                function.name.clone().unwrap_or_default(),
+                function.ordinal.map(|ordinal| ordinal.to_string()).unwrap_or_default(),
+                function.hint.map(|hint| hint.to_string()).unwrap_or_default(),
+                function.thunk_rva.to_string(),
+            ];
+            println!("{}", format_row(&row, delimiter));
+        }
+    }
+}
+
+/// Prints one row per exported function.
+pub fn print_exports_table(exports: Option<&Exports>, delimiter: char) {
+    println!("{}", format_row(&["dll", "ordinal", "name", "rva", "forwarder"], delimiter));
+    let Some(exports) = exports else {
+        return;
+    };
+    for function in &exports.functions {
+        let row = [
+            exports.dll_name.clone(),
+            function.ordinal.to_string(),
+            function.name.clone().unwrap_or_default(),
+            function.rva.to_string(),
+            function.forwarder.clone().unwrap_or_default(),
+        ];
+        println!("{}", format_row(&row, delimiter));
+    }
+}
+
+/// Prints one row per string, with the section it is in and its offset and RVA.
+pub fn print_strings_table(strings: &[FoundString], delimiter: char) {
+    let columns = ["section", "offset", "rva", "encoding", "string"];
+    println!("{}", format_row(&columns, delimiter));
+    for found in strings {
+        let row = [
+            found.section.clone().unwrap_or_default(),
+            found.offset.to_string(),
+            found.rva.map(|rva| rva.to_string()).unwrap_or_default(),
+            found.encoding.to_string(),
+            found.value.clone(),
+        ];
+        println!("{}", format_row(&row, delimiter));
+    }
+}
@@ -0,0 +1,17 @@
+#![feature(no_core, lang_items)]
+#![no_core]
+#![crate_type = "lib"]
+#[lang = "sized"] pub trait Sized: MetaSized {}
+#[lang = "copy"] pub trait Copy {}
+#[lang = "pointee_sized"] pub trait PointeeSized {}
+#[lang = "meta_sized"] pub trait MetaSized: PointeeSized {}
+#[lang = "drop_glue"] pub unsafe fn drop_glue<T: ?Sized>(_: *mut T) {}
+#[lang = "sync"] pub trait Sync {}
+impl Sync for u32 {}
+impl<T: ?Sized> Sync for &T {}
+extern "system" { fn GetTickCount() -> u32; fn Sleep(ms: u32); fn CreateThread(a: usize, b: usize, c: usize, d: usize, e: u32, f: usize) -> usize; }
+#[no_mangle] pub static mut COUNTER: u32 = 0;
+#[no_mangle] pub static mut PTR: &'static u32 = unsafe { &COUNTER };
+#[no_mangle] pub extern "system" fn pepeek_add(a: u32) -> u32 { unsafe { GetTickCount() } }
+#[no_mangle] pub extern "system" fn pepeek_sleep(a: u32) { unsafe { Sleep(a); CreateThread(0,0,0,0,0,0); } }
+#[no_mangle] pub extern "system" fn _DllMainCRTStartup(a: usize, b: u32, c: usize) -> u32 { 1 }